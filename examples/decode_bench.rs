@@ -0,0 +1,106 @@
+//! Times `instruction::decode`, which dispatches through a table of decoders indexed by the top nibble, over every
+//! opcode, against the match on the top nibble it replaced. Run it in a release build:
+//!
+//! ```shell
+//! cargo run -r --example decode_bench
+//! ```
+
+use chip8_rs::instruction::{Instruction, decode};
+use std::hint::black_box;
+use std::time::Instant;
+
+/// Passes over all 65536 opcodes per measurement.
+const PASSES: u32 = 2000;
+
+/// The previous `decode`: a match on the top nibble, after extracting all operands.
+fn decode_by_match(opcode: u16) -> Instruction {
+    use Instruction::*;
+
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let n = (opcode & 0x000F) as u8;
+    let nn = (opcode & 0x00FF) as u8;
+    let nnn = opcode & 0x0FFF;
+
+    match opcode & 0xF000 {
+        0x0000 => match nnn {
+            0x000 => Nop,
+            0x0E0 => Cls,
+            0x0EE => Ret,
+            _ => Sys(nnn),
+        },
+        0x1000 => Jump(nnn),
+        0x2000 => Call(nnn),
+        0x3000 => SkipEqByte { x, nn },
+        0x4000 => SkipNotEqByte { x, nn },
+        0x5000 if n == 0 => SkipEqReg { x, y },
+        0x6000 => LoadByte { x, nn },
+        0x7000 => AddByte { x, nn },
+        0x8000 => match n {
+            0x0 => LoadReg { x, y },
+            0x1 => Or { x, y },
+            0x2 => And { x, y },
+            0x3 => Xor { x, y },
+            0x4 => AddReg { x, y },
+            0x5 => Sub { x, y },
+            0x6 => ShiftRight { x, y },
+            0x7 => SubN { x, y },
+            0xE => ShiftLeft { x, y },
+            _ => Unknown(opcode),
+        },
+        0x9000 if n == 0 => SkipNotEqReg { x, y },
+        0xA000 => LoadI(nnn),
+        0xB000 => JumpOffset(nnn),
+        0xC000 => Random { x, nn },
+        0xD000 => Draw { x, y, n },
+        0xE000 => match nn {
+            0x9E => SkipKeyPressed(x),
+            0xA1 => SkipKeyNotPressed(x),
+            _ => Unknown(opcode),
+        },
+        0xF000 => match nn {
+            0x00 if x == 0 => LoadLongI,
+            0x01 => Plane(x),
+            0x07 => LoadDelay(x),
+            0x0A => WaitKey(x),
+            0x15 => SetDelay(x),
+            0x18 => SetSound(x),
+            0x1E => AddI(x),
+            0x29 => LoadFont(x),
+            0x30 => LoadBigFont(x),
+            0x33 => StoreBcd(x),
+            0x55 => Store(x),
+            0x65 => Load(x),
+            0x75 => SaveFlags(x),
+            0x85 => LoadFlags(x),
+            0xFF => Halt(x),
+            _ => Unknown(opcode),
+        },
+        _ => Unknown(opcode),
+    }
+}
+
+/// Time a decoder over `PASSES` passes of all opcodes, in nanoseconds per opcode.
+fn time(decoder: fn(u16) -> Instruction) -> f64 {
+    let start = Instant::now();
+    for _ in 0..PASSES {
+        for opcode in 0..=u16::MAX {
+            black_box(decoder(black_box(opcode)));
+        }
+    }
+    start.elapsed().as_nanos() as f64 / (PASSES as f64 * 65536.0)
+}
+
+fn main() {
+    assert!((0..=u16::MAX).all(|opcode| decode(opcode) == decode_by_match(opcode)));
+
+    // Warm up, then measure each twice, interleaved
+    time(decode);
+    for _ in 0..2 {
+        println!("table of decoders:       {:.2} ns/opcode", time(decode));
+        println!(
+            "match on the top nibble: {:.2} ns/opcode",
+            time(decode_by_match)
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! Runs arbitrary programs from an arbitrary machine state, which must never panic.
+//!
+//! Run with `cargo fuzz run decoder` from the repository root (requires nightly and `cargo install cargo-fuzz`).
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use chip8_rs::decoder;
+use chip8_rs::quirks::Quirks;
+use chip8_rs::state::State;
+use libfuzzer_sys::fuzz_target;
+
+/// Upper limit on the instructions executed per input, so self-jumps terminate.
+const MAX_STEPS: usize = 1000;
+
+#[derive(Debug, Arbitrary)]
+struct Machine {
+    /// Memory contents from address 0, the rest keeps the interpreter's initial contents.
+    memory: Vec<u8>,
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    stack: Vec<u16>,
+    quirks: [bool; 8],
+    hires: bool,
+    strict: bool,
+}
+
+fuzz_target!(|machine: Machine| {
+    let mut state = State::with_seed(0);
+
+    let len = machine.memory.len().min(state.memory.len());
+    state.memory[..len].copy_from_slice(&machine.memory[..len]);
+    state.v = machine.v;
+    // Addresses are masked like the instructions that compute them would
+    state.i = machine.i as usize & state.address_mask;
+    state.pc = machine.pc as usize & state.address_mask;
+    state.stack = machine
+        .stack
+        .iter()
+        .take(state.max_stack_depth.unwrap_or(usize::MAX))
+        .map(|&address| address as usize & state.address_mask)
+        .collect();
+    let [
+        shift_uses_vy,
+        display_wait,
+        jump_uses_vx,
+        vf_reset_on_logic,
+        xo_chip_planes,
+        xo_chip_long_addressing,
+        fx1e_overflow_vf,
+        clip_sprites,
+    ] = machine.quirks;
+    state.quirks = Quirks {
+        shift_uses_vy,
+        display_wait,
+        jump_uses_vx,
+        vf_reset_on_logic,
+        xo_chip_planes,
+        xo_chip_long_addressing,
+        fx1e_overflow_vf,
+        clip_sprites,
+    };
+    state.set_hires(machine.hires);
+    state.strict = machine.strict;
+
+    for _ in 0..MAX_STEPS {
+        // Errors are fine, as long as they are reported rather than panicking
+        match decoder::decode_and_execute(&mut state) {
+            Ok(None) => {}
+            Ok(Some(_)) | Err(_) => break,
+        }
+    }
+});
@@ -0,0 +1,205 @@
+; Flags test ROM
+;
+; Runs 14 tests of the VF carry, borrow, shift and collision flags, checking both the result and the flag. Each test
+; that passes draws its number (0 to D) in a grid of 8 columns and 2 rows. A missing number means that test failed.
+; Ends by jumping to itself.
+
+        JP start
+
+; Draw the digit in VA, at column (VA & 7) * 8 and row (VA >> 3) * 6
+mark:
+        LD VB, VA
+        LD VD, 0x07
+        AND VB, VD
+        ADD VB, VB
+        ADD VB, VB
+        ADD VB, VB
+        LD VC, VA
+        SHR VC, VC
+        SHR VC, VC
+        SHR VC, VC
+        LD VD, VC
+        ADD VC, VC
+        ADD VC, VD
+        ADD VC, VC
+        LD F, VA
+        DRW VB, VC, 5
+        RET
+
+start:
+; 0: ADD VX, VY without carry
+        LD VA, 0x00
+        LD V0, 0x10
+        LD V2, 0x20
+        ADD V0, V2
+        LD V1, VF
+        SE V0, 0x30
+        JP test1
+        SE V1, 0x00
+        JP test1
+        CALL mark
+
+; 1: ADD VX, VY with carry
+test1:
+        LD VA, 0x01
+        LD V0, 0xF0
+        LD V2, 0x20
+        ADD V0, V2
+        LD V1, VF
+        SE V0, 0x10
+        JP test2
+        SE V1, 0x01
+        JP test2
+        CALL mark
+
+; 2: SUB VX, VY without borrow
+test2:
+        LD VA, 0x02
+        LD V0, 0x30
+        LD V2, 0x10
+        SUB V0, V2
+        LD V1, VF
+        SE V0, 0x20
+        JP test3
+        SE V1, 0x01
+        JP test3
+        CALL mark
+
+; 3: SUB VX, VY with borrow
+test3:
+        LD VA, 0x03
+        LD V0, 0x10
+        LD V2, 0x30
+        SUB V0, V2
+        LD V1, VF
+        SE V0, 0xE0
+        JP test4
+        SE V1, 0x00
+        JP test4
+        CALL mark
+
+; 4: SUBN VX, VY without borrow
+test4:
+        LD VA, 0x04
+        LD V0, 0x10
+        LD V2, 0x30
+        SUBN V0, V2
+        LD V1, VF
+        SE V0, 0x20
+        JP test5
+        SE V1, 0x01
+        JP test5
+        CALL mark
+
+; 5: SUBN VX, VY with borrow
+test5:
+        LD VA, 0x05
+        LD V0, 0x30
+        LD V2, 0x10
+        SUBN V0, V2
+        LD V1, VF
+        SE V0, 0xE0
+        JP test6
+        SE V1, 0x00
+        JP test6
+        CALL mark
+
+; 6: SHR VX, VX shifting out a 1
+test6:
+        LD VA, 0x06
+        LD V0, 0x05
+        SHR V0, V0
+        LD V1, VF
+        SE V0, 0x02
+        JP test7
+        SE V1, 0x01
+        JP test7
+        CALL mark
+
+; 7: SHR VX, VX shifting out a 0
+test7:
+        LD VA, 0x07
+        LD V0, 0x04
+        SHR V0, V0
+        LD V1, VF
+        SE V0, 0x02
+        JP test8
+        SE V1, 0x00
+        JP test8
+        CALL mark
+
+; 8: SHL VX, VX shifting out a 1
+test8:
+        LD VA, 0x08
+        LD V0, 0x81
+        SHL V0, V0
+        LD V1, VF
+        SE V0, 0x02
+        JP test9
+        SE V1, 0x01
+        JP test9
+        CALL mark
+
+; 9: SHL VX, VX shifting out a 0
+test9:
+        LD VA, 0x09
+        LD V0, 0x41
+        SHL V0, V0
+        LD V1, VF
+        SE V0, 0x82
+        JP testA
+        SE V1, 0x00
+        JP testA
+        CALL mark
+
+; A: ADD VF, VY keeps the carry, not the sum
+testA:
+        LD VA, 0x0A
+        LD VF, 0xF0
+        LD V2, 0x20
+        ADD VF, V2
+        SE VF, 0x01
+        JP testB
+        CALL mark
+
+; B: SUB VF, VY keeps the borrow flag, not the difference
+testB:
+        LD VA, 0x0B
+        LD VF, 0x30
+        LD V2, 0x10
+        SUB VF, V2
+        SE VF, 0x01
+        JP testC
+        CALL mark
+
+; C: ADD VX, VF uses VF as an operand
+testC:
+        LD VA, 0x0C
+        LD V0, 0x10
+        LD VF, 0x20
+        ADD V0, VF
+        LD V1, VF
+        SE V0, 0x30
+        JP testD
+        SE V1, 0x00
+        JP testD
+        CALL mark
+
+; D: DRW sets VF on collision only
+testD:
+        LD VA, 0x0D
+        LD V2, 0x00
+        LD V3, 0x14
+        LD F, V2
+        DRW V2, V3, 5
+        LD V0, VF
+        DRW V2, V3, 5
+        LD V1, VF
+        SE V0, 0x00
+        JP end
+        SE V1, 0x01
+        JP end
+        CALL mark
+
+end:
+        JP end
@@ -0,0 +1,198 @@
+; Opcode test ROM
+;
+; Runs 16 tests of the arithmetic, skip and memory instructions. Each test that passes draws its number (0 to F) in a
+; grid of 8 columns and 2 rows. A missing number means that test failed. Ends by jumping to itself.
+
+        JP start
+
+; Draw the digit in VA, at column (VA & 7) * 8 and row (VA >> 3) * 6
+mark:
+        LD VB, VA
+        LD VD, 0x07
+        AND VB, VD
+        ADD VB, VB
+        ADD VB, VB
+        ADD VB, VB
+        LD VC, VA
+        SHR VC, VC
+        SHR VC, VC
+        SHR VC, VC
+        LD VD, VC
+        ADD VC, VC
+        ADD VC, VD
+        ADD VC, VC
+        LD F, VA
+        DRW VB, VC, 5
+        RET
+
+start:
+; 0: SE VX, NN
+        LD VA, 0x00
+        LD V0, 0x2A
+        SE V0, 0x2A
+        JP test1
+        CALL mark
+
+; 1: SNE VX, NN
+test1:
+        LD VA, 0x01
+        LD V0, 0x2A
+        SNE V0, 0x2B
+        JP test2
+        CALL mark
+
+; 2: SE VX, VY
+test2:
+        LD VA, 0x02
+        LD V0, 0x17
+        LD V1, 0x17
+        SE V0, V1
+        JP test3
+        CALL mark
+
+; 3: ADD VX, NN wraps around, and leaves VF alone
+test3:
+        LD VA, 0x03
+        LD VF, 0x05
+        LD V0, 0xFF
+        ADD V0, 0x02
+        SE V0, 0x01
+        JP test4
+        SE VF, 0x05
+        JP test4
+        CALL mark
+
+; 4: LD VX, VY
+test4:
+        LD VA, 0x04
+        LD V1, 0x33
+        LD V0, V1
+        SE V0, 0x33
+        JP test5
+        CALL mark
+
+; 5: OR VX, VY
+test5:
+        LD VA, 0x05
+        LD V0, 0x0F
+        LD V1, 0xF0
+        OR V0, V1
+        SE V0, 0xFF
+        JP test6
+        CALL mark
+
+; 6: AND VX, VY
+test6:
+        LD VA, 0x06
+        LD V0, 0x3C
+        LD V1, 0x0F
+        AND V0, V1
+        SE V0, 0x0C
+        JP test7
+        CALL mark
+
+; 7: XOR VX, VY
+test7:
+        LD VA, 0x07
+        LD V0, 0x3C
+        LD V1, 0x0F
+        XOR V0, V1
+        SE V0, 0x33
+        JP test8
+        CALL mark
+
+; 8: ADD VX, VY
+test8:
+        LD VA, 0x08
+        LD V0, 0x12
+        LD V1, 0x34
+        ADD V0, V1
+        SE V0, 0x46
+        JP test9
+        CALL mark
+
+; 9: SUB VX, VY
+test9:
+        LD VA, 0x09
+        LD V0, 0x34
+        LD V1, 0x12
+        SUB V0, V1
+        SE V0, 0x22
+        JP testA
+        CALL mark
+
+; A: SUBN VX, VY
+testA:
+        LD VA, 0x0A
+        LD V0, 0x12
+        LD V1, 0x34
+        SUBN V0, V1
+        SE V0, 0x22
+        JP testB
+        CALL mark
+
+; B: SHR VX, VX (the same register, so the shift quirk doesn't matter)
+testB:
+        LD VA, 0x0B
+        LD V0, 0x84
+        SHR V0, V0
+        SE V0, 0x42
+        JP testC
+        CALL mark
+
+; C: SHL VX, VX
+testC:
+        LD VA, 0x0C
+        LD V0, 0x42
+        SHL V0, V0
+        SE V0, 0x84
+        JP testD
+        CALL mark
+
+; D: SNE VX, VY
+testD:
+        LD VA, 0x0D
+        LD V0, 0x01
+        LD V1, 0x02
+        SNE V0, V1
+        JP testE
+        CALL mark
+
+; E: LD B, VX stores the decimal digits of 123
+testE:
+        LD VA, 0x0E
+        LD V0, 0x7B
+        LD I, scratch
+        LD B, V0
+        LD I, scratch
+        LD V2, [I]
+        SE V0, 0x01
+        JP testF
+        SE V1, 0x02
+        JP testF
+        SE V2, 0x03
+        JP testF
+        CALL mark
+
+; F: LD [I], VX and LD VX, [I] round trip
+testF:
+        LD VA, 0x0F
+        LD V0, 0x11
+        LD V1, 0x22
+        LD I, scratch
+        LD [I], V1
+        LD V0, 0x00
+        LD V1, 0x00
+        LD I, scratch
+        LD V1, [I]
+        SE V0, 0x11
+        JP end
+        SE V1, 0x22
+        JP end
+        CALL mark
+
+end:
+        JP end
+
+scratch:
+        DB 0x00, 0x00, 0x00, 0x00
@@ -0,0 +1,63 @@
+; SUPER-CHIP quirks test ROM
+;
+; Runs 4 tests of the quirks of the SUPER-CHIP interpreter, using only CHIP-8 instructions, so its variant can't be
+; detected from them: the shifts shift VX in place, the logic instructions leave VF alone, and JP V0 jumps with VX.
+; Each test that passes draws its number (0 to 3) in a row. A missing number means that test failed, e.g. all of them
+; with the CHIP-8 quirks. Ends by jumping to itself.
+
+        JP start
+
+; Draw the digit in VA, at column VA * 8
+mark:
+        LD VB, VA
+        ADD VB, VB
+        ADD VB, VB
+        ADD VB, VB
+        LD VC, 0x00
+        LD F, VA
+        DRW VB, VC, 5
+        RET
+
+start:
+; 0: SHR VX, VY shifts VX in place
+        LD VA, 0x00
+        LD V0, 0x04
+        LD V1, 0x81
+        SHR V0, V1
+        SE V0, 0x02
+        JP test1
+        CALL mark
+
+; 1: SHL VX, VY shifts VX in place
+test1:
+        LD VA, 0x01
+        LD V0, 0x01
+        LD V1, 0x80
+        SHL V0, V1
+        SE V0, 0x02
+        JP test2
+        CALL mark
+
+; 2: OR VX, VY leaves VF alone
+test2:
+        LD VA, 0x02
+        LD VF, 0x05
+        LD V0, 0x01
+        LD V1, 0x02
+        OR V0, V1
+        SE VF, 0x05
+        JP test3
+        CALL mark
+
+; 3: JP V0, addr jumps to addr + VX, where X is the top nibble of the address
+test3:
+        LD VA, 0x03
+        LD V0, 0x00
+        LD V2, 0x02
+        JP V0, land
+land:
+        JP end
+        CALL mark
+
+end:
+        JP end
@@ -1,8 +1,32 @@
+use chip8_rs::assembler::assemble;
+use clap::Parser;
+use std::path::PathBuf;
+
 #[allow(unused_imports)]
 use log::*;
 
-// TODO: Implement assembler/disassembler main function
+#[derive(Parser, Debug)]
+#[command(version, about="A CHIP-8 assembler.", long_about = None, author)]
+struct Args {
+    /// The source file, in the disassembler's syntax
+    source: PathBuf,
+
+    /// Where to write the ROM, by default next to the source with a `.ch8` extension
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    todo!("Implement assembler main function")
+    env_logger::init();
+
+    let args = Args::parse();
+
+    let rom = assemble(&std::fs::read_to_string(&args.source)?)
+        .map_err(|e| format!("{}: {}", args.source.display(), e))?;
+    let output = args
+        .output
+        .unwrap_or_else(|| args.source.with_extension("ch8"));
+    std::fs::write(&output, &rom)?;
+    info!("Wrote {} bytes to {}", rom.len(), output.display());
+    Ok(())
 }
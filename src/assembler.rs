@@ -0,0 +1,402 @@
+//! CHIP-8 assembler, the inverse of the disassembler.
+//!
+//! The source is line based, in the syntax of Cowgod's "Chip-8 Technical Reference" as produced by the disassembler,
+//! e.g. `LD V0, 0x2A` or `DRW V1, V2, 5`. Each line holds an optional label (`loop:`), an optional statement, and an
+//! optional comment starting with `;`. Mnemonics and register names are case-insensitive, labels are not.
+//!
+//! Numbers are decimal, hexadecimal (`0x2A`) or binary (`0b0010_1010`). A label can be used wherever an address is
+//! expected, e.g. `JP loop` or `LD I, sprite`. Besides the instructions, `DB` emits bytes and `DW` emits big-endian
+//! words, e.g. for sprite data. The XO-CHIP `LD I, LONG` is followed by its 16-bit address as a word, e.g. `DW sprite`.
+//!
+//! Labels may be used before they are defined: the first pass assigns addresses to the labels, and the second pass
+//! encodes the statements. The program is assembled to run from 0x200.
+
+use crate::instruction::Instruction;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Address the assembled program is loaded at.
+const ORIGIN: usize = 0x200;
+
+/// An error in the source, with the line it was found on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembleError {
+    /// The line number, starting from 1.
+    pub line: usize,
+
+    /// What is wrong with the line.
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl core::error::Error for AssembleError {}
+
+/// An operand of a statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    /// A register, `V0` to `VF`.
+    Reg(u8),
+    /// `I`.
+    I,
+    /// `[I]`, the memory at `I`.
+    IndirectI,
+    /// `DT`, the delay timer.
+    Dt,
+    /// `ST`, the sound timer.
+    St,
+    /// `K`, a key press.
+    K,
+    /// `F`, a small font glyph.
+    F,
+    /// `HF`, a big font glyph.
+    Hf,
+    /// `B`, BCD digits.
+    B,
+    /// `R`, the RPL user flags.
+    R,
+    /// `LONG`, a 16-bit address in the next word, e.g. `DW label`.
+    Long,
+    /// A number, or the address of a label.
+    Value(usize),
+}
+
+/// A statement with the line it is on.
+struct Statement<'a> {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+impl Statement<'_> {
+    /// The number of bytes the statement assembles to.
+    fn size(&self) -> usize {
+        match self.mnemonic.as_str() {
+            "DB" => self.operands.len(),
+            "DW" => 2 * self.operands.len(),
+            _ => 2,
+        }
+    }
+}
+
+/// Assemble a program.
+///
+/// # Arguments
+/// * `source` - The assembly source.
+///
+/// # Returns
+/// * `Ok(bytes)` with the ROM image, to be loaded at 0x200.
+/// * `Err(AssembleError)` for the first line with an error.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssembleError> {
+    // First pass: split the lines into labels and statements, and assign addresses to the labels
+    let mut labels = BTreeMap::new();
+    let mut statements = Vec::new();
+    let mut address = ORIGIN;
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let error = |message: String| AssembleError { line, message };
+
+        let mut text = text.split(';').next().unwrap_or_default().trim();
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if label.is_empty() || !label.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(error(format!("Invalid label '{label}'")));
+            }
+            if labels.insert(label, address).is_some() {
+                return Err(error(format!("Duplicate label '{label}'")));
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let operands = if operands.trim().is_empty() {
+            Vec::new()
+        } else {
+            operands.split(',').map(str::trim).collect()
+        };
+        let statement = Statement {
+            line,
+            mnemonic: mnemonic.to_uppercase(),
+            operands,
+        };
+        address += statement.size();
+        statements.push(statement);
+    }
+
+    // Second pass: encode the statements
+    let mut rom = Vec::new();
+    for statement in &statements {
+        let error = |message: String| AssembleError {
+            line: statement.line,
+            message,
+        };
+
+        let operands = statement
+            .operands
+            .iter()
+            .map(|operand| parse_operand(operand, &labels))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(error)?;
+
+        match statement.mnemonic.as_str() {
+            "DB" => {
+                for operand in operands {
+                    rom.push(byte(value(operand).map_err(error)?).map_err(error)?);
+                }
+            }
+            "DW" => {
+                for operand in operands {
+                    let word = value(operand).map_err(error)?;
+                    let word = u16::try_from(word)
+                        .map_err(|_| error(format!("Word out of range: {word:#X}")))?;
+                    rom.extend(word.to_be_bytes());
+                }
+            }
+            mnemonic => {
+                let instruction = parse_instruction(mnemonic, &operands).map_err(error)?;
+                rom.extend(instruction.encode().to_be_bytes());
+            }
+        }
+    }
+
+    Ok(rom)
+}
+
+/// Parse an operand.
+///
+/// # Arguments
+/// * `operand` - The operand text, trimmed.
+/// * `labels` - The addresses of the labels.
+fn parse_operand(operand: &str, labels: &BTreeMap<&str, usize>) -> Result<Operand, String> {
+    let upper = operand.to_uppercase();
+    let keyword = match upper.as_str() {
+        "I" => Some(Operand::I),
+        "[I]" => Some(Operand::IndirectI),
+        "DT" => Some(Operand::Dt),
+        "ST" => Some(Operand::St),
+        "K" => Some(Operand::K),
+        "F" => Some(Operand::F),
+        "HF" => Some(Operand::Hf),
+        "B" => Some(Operand::B),
+        "R" => Some(Operand::R),
+        "LONG" => Some(Operand::Long),
+        _ => None,
+    };
+    if let Some(keyword) = keyword {
+        return Ok(keyword);
+    }
+
+    if let Some(register) = upper.strip_prefix('V')
+        && register.len() == 1
+        && let Ok(register) = u8::from_str_radix(register, 16)
+    {
+        return Ok(Operand::Reg(register));
+    }
+
+    let digits = operand.replace('_', "");
+    let number = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        usize::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = digits
+        .strip_prefix("0b")
+        .or_else(|| digits.strip_prefix("0B"))
+    {
+        usize::from_str_radix(binary, 2).ok()
+    } else {
+        digits.parse().ok()
+    };
+
+    number
+        .or_else(|| labels.get(operand).copied())
+        .map(Operand::Value)
+        .ok_or_else(|| format!("Unknown operand '{operand}'"))
+}
+
+/// Parse an instruction from its mnemonic and operands.
+///
+/// # Arguments
+/// * `mnemonic` - The mnemonic, in upper case.
+/// * `operands` - The parsed operands.
+fn parse_instruction(mnemonic: &str, operands: &[Operand]) -> Result<Instruction, String> {
+    use Instruction::*;
+    use Operand::{B, Dt, F, Hf, I, IndirectI, K, Long, R, Reg, St, Value};
+
+    let instruction = match (mnemonic, operands) {
+        ("NOP", []) => Nop,
+        ("CLS", []) => Cls,
+        ("RET", []) => Ret,
+        ("SYS", [Value(nnn)]) => Sys(address(*nnn)?),
+        ("JP", [Value(nnn)]) => Jump(address(*nnn)?),
+        ("JP", [Reg(0), Value(nnn)]) => JumpOffset(address(*nnn)?),
+        ("CALL", [Value(nnn)]) => Call(address(*nnn)?),
+        ("SE", [Reg(x), Value(nn)]) => SkipEqByte {
+            x: *x,
+            nn: byte(*nn)?,
+        },
+        ("SE", [Reg(x), Reg(y)]) => SkipEqReg { x: *x, y: *y },
+        ("SNE", [Reg(x), Value(nn)]) => SkipNotEqByte {
+            x: *x,
+            nn: byte(*nn)?,
+        },
+        ("SNE", [Reg(x), Reg(y)]) => SkipNotEqReg { x: *x, y: *y },
+        ("LD", [Reg(x), Value(nn)]) => LoadByte {
+            x: *x,
+            nn: byte(*nn)?,
+        },
+        ("LD", [Reg(x), Reg(y)]) => LoadReg { x: *x, y: *y },
+        ("LD", [I, Value(nnn)]) => LoadI(address(*nnn)?),
+        ("LD", [I, Long]) => LoadLongI,
+        ("LD", [Reg(x), Dt]) => LoadDelay(*x),
+        ("LD", [Reg(x), K]) => WaitKey(*x),
+        ("LD", [Dt, Reg(x)]) => SetDelay(*x),
+        ("LD", [St, Reg(x)]) => SetSound(*x),
+        ("LD", [F, Reg(x)]) => LoadFont(*x),
+        ("LD", [Hf, Reg(x)]) => LoadBigFont(*x),
+        ("LD", [B, Reg(x)]) => StoreBcd(*x),
+        ("LD", [IndirectI, Reg(x)]) => Store(*x),
+        ("LD", [Reg(x), IndirectI]) => Load(*x),
+        ("LD", [R, Reg(x)]) => SaveFlags(*x),
+        ("LD", [Reg(x), R]) => LoadFlags(*x),
+        ("ADD", [Reg(x), Value(nn)]) => AddByte {
+            x: *x,
+            nn: byte(*nn)?,
+        },
+        ("ADD", [Reg(x), Reg(y)]) => AddReg { x: *x, y: *y },
+        ("ADD", [I, Reg(x)]) => AddI(*x),
+        ("OR", [Reg(x), Reg(y)]) => Or { x: *x, y: *y },
+        ("AND", [Reg(x), Reg(y)]) => And { x: *x, y: *y },
+        ("XOR", [Reg(x), Reg(y)]) => Xor { x: *x, y: *y },
+        ("SUB", [Reg(x), Reg(y)]) => Sub { x: *x, y: *y },
+        ("SUBN", [Reg(x), Reg(y)]) => SubN { x: *x, y: *y },
+        ("SHR", [Reg(x)]) => ShiftRight { x: *x, y: *x },
+        ("SHR", [Reg(x), Reg(y)]) => ShiftRight { x: *x, y: *y },
+        ("SHL", [Reg(x)]) => ShiftLeft { x: *x, y: *x },
+        ("SHL", [Reg(x), Reg(y)]) => ShiftLeft { x: *x, y: *y },
+        ("RND", [Reg(x), Value(nn)]) => Random {
+            x: *x,
+            nn: byte(*nn)?,
+        },
+        ("DRW", [Reg(x), Reg(y), Value(n)]) => Draw {
+            x: *x,
+            y: *y,
+            n: nibble(*n)?,
+        },
+        ("SKP", [Reg(x)]) => SkipKeyPressed(*x),
+        ("SKNP", [Reg(x)]) => SkipKeyNotPressed(*x),
+        ("PLANE", [Value(n)]) => Plane(nibble(*n)?),
+        ("HALT", [Value(n)]) => Halt(nibble(*n)?),
+        (
+            "NOP" | "CLS" | "RET" | "SYS" | "JP" | "CALL" | "SE" | "SNE" | "LD" | "ADD" | "OR"
+            | "AND" | "XOR" | "SUB" | "SUBN" | "SHR" | "SHL" | "RND" | "DRW" | "SKP" | "SKNP"
+            | "PLANE" | "HALT",
+            _,
+        ) => return Err(format!("Invalid operands for {mnemonic}")),
+        _ => return Err(format!("Unknown mnemonic '{mnemonic}'")),
+    };
+
+    Ok(instruction)
+}
+
+/// The value of a number or label operand.
+fn value(operand: Operand) -> Result<usize, String> {
+    match operand {
+        Operand::Value(value) => Ok(value),
+        _ => Err(format!("Expected a number, found {operand:?}")),
+    }
+}
+
+/// Check that a value is a 12-bit address.
+fn address(value: usize) -> Result<u16, String> {
+    u16::try_from(value)
+        .ok()
+        .filter(|&address| address <= 0xFFF)
+        .ok_or_else(|| format!("Address out of range: {value:#X}"))
+}
+
+/// Check that a value is a byte.
+fn byte(value: usize) -> Result<u8, String> {
+    u8::try_from(value).map_err(|_| format!("Byte out of range: {value:#X}"))
+}
+
+/// Check that a value is a nibble.
+fn nibble(value: usize) -> Result<u8, String> {
+    u8::try_from(value)
+        .ok()
+        .filter(|&nibble| nibble <= 0xF)
+        .ok_or_else(|| format!("Nibble out of range: {value:#X}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler;
+
+    #[test]
+    fn assemble_with_labels() {
+        let source = "
+            ; Draw a digit forever
+            start:  LD V0, 0x0A     ; The digit
+                    LD F, V0
+                    DRW V1, V2, 5
+            loop:   JP loop
+                    JP start
+            data:   DB 0b1111_0000, 255
+        ";
+
+        assert_eq!(
+            assemble(source),
+            Ok(vec![
+                0x60, 0x0A, 0xF0, 0x29, 0xD1, 0x25, 0x12, 0x06, 0x12, 0x00, 0xF0, 0xFF,
+            ])
+        );
+    }
+
+    #[test]
+    fn assemble_disassembly() {
+        let rom = [
+            0x00, 0xE0, 0xA2, 0x34, 0xB3, 0x00, 0x8A, 0xB6, 0xF3, 0x65, 0xE1, 0x9E, 0xF0, 0x00,
+            0x12, 0x34,
+        ];
+        let source: String = disassembler::disassemble(&rom, 0x200)
+            .into_iter()
+            .map(|(_, _, mnemonic)| mnemonic + "\n")
+            .collect();
+
+        assert_eq!(assemble(&source), Ok(rom.to_vec()));
+    }
+
+    #[test]
+    fn report_errors_with_line() {
+        let error = |line, message: &str| {
+            Err(AssembleError {
+                line,
+                message: message.to_string(),
+            })
+        };
+
+        assert_eq!(
+            assemble("CLS\nJP nowhere"),
+            error(2, "Unknown operand 'nowhere'")
+        );
+        assert_eq!(assemble("LD V0, 256"), error(1, "Byte out of range: 0x100"));
+        assert_eq!(
+            assemble("DRW V0, I, 1"),
+            error(1, "Invalid operands for DRW")
+        );
+        assert_eq!(assemble("MOV V0, V1"), error(1, "Unknown mnemonic 'MOV'"));
+        assert_eq!(assemble("a:\na: CLS"), error(2, "Duplicate label 'a'"));
+    }
+}
@@ -1,23 +1,81 @@
 //! Constants used throughout the CHIP-8 emulator.
 //!
-//! Memory size, screen dimensions, character sprite offsets, and clock frequency are defined here.
+//! Memory size, screen dimensions, character sprite offsets, and clock frequencies are defined here.
 
-use std::time::Duration;
+use core::time::Duration;
 
-/// Character sprites start at 0x000
-pub const CHARACTER_SPRITE_OFFSET: usize = 0x000;
+/// The small (4x5) font, used by `FX29`, starts at 0x000, unless moved with `State::with_font_offset`
+pub const SMALL_FONT_OFFSET: usize = 0x000;
 
-/// 48kHz
-pub const CLOCK_FREQ: u32 = 48000;
+/// The large (8x10) SUPER-CHIP font, used by `FX30`, starts at 0x050, right after the 80 bytes of the small font, which
+/// it follows when moved. Its glyphs are drawn as 10-row sprites with `DXYA`
+pub const LARGE_FONT_OFFSET: usize = 0x050;
+
+/// Default clock frequency of 700Hz, roughly the speed most CHIP-8 programs were written for
+pub const CLOCK_FREQ: u32 = 700;
+
+/// Highest clock frequency of 1GHz, where a cycle still takes a whole nanosecond
+pub const MAX_CLOCK_FREQ: u32 = 1_000_000_000;
+
+/// The COSMAC VIP ran at 1.76 MHz, with 8 clock cycles per machine cycle. The clock of the cycle accurate mode counts
+/// machine cycles, see `Instruction::cycles`
+pub const VIP_CYCLE_FREQ: u32 = 1_760_900 / 8;
+
+/// Timers and display refresh at 60Hz
+pub const TIMER_FREQ: u32 = 60;
 
 /// Screen height in "pixels"
 pub const HEIGHT: usize = 32;
 
+/// Screen height in "pixels" in the 64×64 hires mode of the COSMAC VIP
+pub const HIRES_HEIGHT: usize = 64;
+
+/// Programs are loaded at, and start from, 0x200, after the memory reserved for the interpreter
+pub const ENTRY_POINT: usize = 0x200;
+
+/// Hires programs start at 0x2C0, after the space reserved for the interpreter patch that enabled the mode on the VIP
+pub const HIRES_ENTRY_POINT: usize = 0x2C0;
+
+/// The terminal is redrawn 60 times per second by default
+pub const RENDER_FPS: u32 = 60;
+
+/// After a stall, the run loop catches up on at most 100 ms of cycles and frames, and drops the rest
+pub const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+
 /// 4KB
 pub const MEMORY_SIZE: usize = 4096;
 
+/// 64KB, the XO-CHIP memory map
+pub const XO_CHIP_MEMORY_SIZE: usize = 65536;
+
+/// Default factor by which holding Tab speeds up the program.
+pub const TURBO_FACTOR: u32 = 5;
+
+/// Default number of frames kept for rewinding, 10 s at 60Hz. Each frame is a snapshot of the `State` of about 12 KB
+/// (4 KB of memory and two 4 KB screen planes), so this costs about 7 MB.
+pub const REWIND_DEPTH: usize = 600;
+
+/// The run loop doesn't sleep for less than 1 ms, since shorter sleeps are too imprecise to pace instructions
+pub const MIN_SLEEP: Duration = Duration::from_millis(1);
+
+/// The call stack of the COSMAC VIP starts at 0xEA0, below the display refresh area, see `State::memory_stack`
+pub const STACK_OFFSET: usize = 0xEA0;
+
+/// The 96 bytes from `STACK_OFFSET` to 0xEFF hold 48 return addresses of two bytes
+pub const MEMORY_STACK_DEPTH: usize = 48;
+
+/// Default limit on nested subroutine calls. The VIP had room for 12 levels, later interpreters commonly allow 16
+pub const STACK_DEPTH: usize = 16;
+
+/// The hexadecimal keypad has 16 keys, 0x0 to 0xF, one bit each in `Emulator::keys_pressed`
+pub const KEY_COUNT: usize = 16;
+
 /// Screen width in "pixels"
 pub const WIDTH: usize = 64;
 
-/// Key presses time-out after 100 ms, if not polled. This is to handle our missing key-up events :/
+/// Key presses time-out after 100 ms by default (see `State::key_timeout`), if not polled. This is only used for inputs
+/// that don't report key-up events, e.g. terminals without support for the keyboard enhancement protocol.
+///
+/// The timeout is only checked when input is polled, so a held key that is re-reported by the terminal's auto-repeat
+/// stays pressed for `EX9E`/`EXA1`, even if the timeout expires between two polls.
 pub const KEY_PRESS_TIMEOUT_MS: Duration = Duration::from_millis(100);
@@ -3,13 +3,27 @@
 //! This module provides functionality to decode and execute CHIP-8 instructions.
 //! The main function `decode_and_execute` takes the current state of the interpreter,
 //! decodes the instruction at the program counter, and modifies the state accordingly.
+//! Decoding is pure (see `instruction::decode`), so `execute` can also run an `Instruction` directly.
 
 use crate::constants;
-use crate::state;
+use crate::error::Chip8Error;
+use crate::font;
+use crate::instruction::{self, Instruction};
+use crate::state::{self, PcOverflow};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
 use log::*;
 
 /// Draw a sprite at position `x`, `y` with `N` bytes of sprite data starting at the address stored in `state.i`.
-/// Set `VF` to `1` if any set pixels are changed to unset, and `0` otherwise.
+/// Set `VF` to `1` if any set pixels are changed to unset, in any row, and `0` otherwise.
+///
+/// Sprite data past the end of memory wraps around to address 0, like the addresses of other instructions.
+///
+/// With the `xo_chip_planes` quirk, the sprite is drawn to each selected plane, with the next `N` bytes of sprite data
+/// for each. `VF` reports a collision in any of them.
+///
+/// Pixels past the right and bottom edges are dropped with the `clip_sprites` quirk, and wrap around otherwise.
 ///
 /// # Arguments
 /// * `state` - The current state of the CHIP-8 interpreter.
@@ -17,285 +31,460 @@ use log::*;
 /// * `y` - The y coordinate to draw the sprite at.
 /// * `n` - The number of bytes of sprite data to draw.
 fn draw_sprite(state: &mut state::State, x: usize, y: usize, n: usize) {
-    todo!()
-}
+    // The starting position wraps, while the sprite itself is clipped at the edges of the screen, or wraps too
+    let x = x % constants::WIDTH;
+    let height = state.height();
+    let y = y % height;
+    let planes = if state.quirks.xo_chip_planes {
+        state.planes
+    } else {
+        1
+    };
 
-pub fn decode_and_execute(
-    state: &mut state::State,
-) -> Result<Option<usize>, Box<dyn std::error::Error>> {
-    let instruction: u16 =
-        ((state.memory[state.pc] as u16) << 8) | (state.memory[state.pc + 1] as u16);
+    state.v[0xF] = 0;
 
-    state.pc += 2;
-    state.pc &= 0xFFF;
+    let mut address = state.i;
+    for plane in 0..2 {
+        if planes & (1 << plane) == 0 {
+            continue;
+        }
 
-    // See: https://github.com/mattmikolay/chip-8/wiki/CHIP%E2%80%908-Instruction-Set
-    match instruction & 0xF000 {
-        0x0000 => match instruction & 0x0FFF {
-            0x0000 => {
-                // 0x0000: No operation (NB: Not part of the original CHIP-8 instruction set)
+        let screen = if plane == 0 {
+            &mut state.screen
+        } else {
+            &mut state.plane2
+        };
+
+        for row in 0..n {
+            if y + row >= height && state.quirks.clip_sprites {
+                break;
             }
-            0x00E0 => {
-                // 0x00E0: Clear the display
-                state.screen = [false; constants::WIDTH * constants::HEIGHT];
+
+            let sprite_byte = state.memory[(address + row) & state.address_mask];
+
+            for column in 0..8 {
+                if x + column >= constants::WIDTH && state.quirks.clip_sprites {
+                    break;
+                }
+
+                if sprite_byte & (0b1000_0000 >> column) != 0 {
+                    let index =
+                        (y + row) % height * constants::WIDTH + (x + column) % constants::WIDTH;
+                    if screen[index] {
+                        state.v[0xF] = 1;
+                    }
+                    screen[index] = !screen[index];
+                    state.screen_dirty = true;
+                }
             }
-            0x00EE => {
-                // 0x00EE: Return from subroutine
-                state.pc = state.stack.pop_back().ok_or("Stack underflow on RET")?;
+        }
+
+        address += n;
+    }
+}
+
+/// Clear the display, or only the selected planes with the `xo_chip_planes` quirk.
+///
+/// # Arguments
+/// * `state` - The current state of the CHIP-8 interpreter.
+fn clear_screen(state: &mut state::State) {
+    state.screen_dirty = true;
+    if !state.quirks.xo_chip_planes {
+        state.screen.fill(false);
+        return;
+    }
+
+    if state.planes & 0b01 != 0 {
+        state.screen.fill(false);
+    }
+    if state.planes & 0b10 != 0 {
+        state.plane2.fill(false);
+    }
+}
+
+/// Fetch the instruction at the program counter, without executing it.
+///
+/// Addresses wrap at the top of memory, like everything else that advances the program counter: an instruction at
+/// 0xFFF has its low byte at 0x000, and execution continues from 0x001.
+///
+/// # Arguments
+/// * `state` - The current state of the CHIP-8 interpreter.
+pub fn fetch(state: &state::State) -> u16 {
+    let high = state.memory[state.pc & state.address_mask];
+    let low = state.memory[(state.pc + 1) & state.address_mask];
+    u16::from_be_bytes([high, low])
+}
+
+/// Skip the following instruction, wrapping at the top of memory. With the `xo_chip_long_addressing` quirk, the
+/// following instruction may be the four bytes of `F000 NNNN`.
+///
+/// # Arguments
+/// * `state` - The current state of the CHIP-8 interpreter.
+fn skip(state: &mut state::State) {
+    let length = if state.quirks.xo_chip_long_addressing && fetch(state) == 0xF000 {
+        4
+    } else {
+        2
+    };
+    advance(state, length);
+}
+
+/// Move the program counter forward, wrapping at the top of memory with `PcOverflow::Wrap`. With the other policies,
+/// it is left past the end, for `decode_and_execute` to stop there.
+///
+/// # Arguments
+/// * `state` - The current state of the CHIP-8 interpreter.
+/// * `length` - The number of bytes to move forward.
+fn advance(state: &mut state::State, length: usize) {
+    state.pc += length;
+    if state.pc_overflow == PcOverflow::Wrap {
+        state.pc &= state.address_mask;
+    }
+}
+
+/// Check whether the program counter ran past the end of memory, where an instruction at the last address straddles
+/// the end, and apply the `PcOverflow` policy.
+///
+/// # Arguments
+/// * `state` - The current state of the CHIP-8 interpreter.
+///
+/// # Returns
+/// * `Ok(Some(0))` if execution stops there with `PcOverflow::Halt`.
+/// * `Ok(None)` if the instruction can execute.
+/// * `Err` with `PcOverflow::Error`.
+pub fn check_pc_overflow(state: &state::State) -> Result<Option<usize>, Chip8Error> {
+    if state.pc >= state.address_mask {
+        match state.pc_overflow {
+            PcOverflow::Wrap => {}
+            PcOverflow::Halt => return Ok(Some(0)),
+            PcOverflow::Error => return Err(Chip8Error::PcOverflow(state.pc)),
+        }
+    }
+    Ok(None)
+}
+
+/// Fetch, decode and execute the instruction at the program counter.
+///
+/// # Arguments
+/// * `state` - The current state of the CHIP-8 interpreter.
+///
+/// # Returns
+/// * `Ok(Some(exit_code))` if the program halted.
+/// * `Ok(None)` if execution should continue.
+/// * `Err` if the instruction failed, or the program counter ran past the end of memory with `PcOverflow::Error`.
+pub fn decode_and_execute(state: &mut state::State) -> Result<Option<usize>, Chip8Error> {
+    if let Some(exit_code) = check_pc_overflow(state)? {
+        return Ok(Some(exit_code));
+    }
+
+    let opcode = fetch(state);
+    advance(state, 2);
+
+    execute(state, instruction::decode(opcode))
+}
+
+/// Execute a decoded instruction. The program counter must already have moved past the instruction.
+///
+/// # Arguments
+/// * `state` - The current state of the CHIP-8 interpreter.
+/// * `instruction` - The instruction to execute.
+///
+/// # Returns
+/// * `Ok(Some(exit_code))` if the program halted.
+/// * `Ok(None)` if execution should continue.
+/// * `Err` if the instruction failed.
+pub fn execute(
+    state: &mut state::State,
+    instruction: Instruction,
+) -> Result<Option<usize>, Chip8Error> {
+    use Instruction::*;
+
+    // See: https://github.com/mattmikolay/chip-8/wiki/CHIP%E2%80%908-Instruction-Set
+    match instruction {
+        Nop => {
+            // 0x0000: No operation (NB: Not part of the original CHIP-8 instruction set)
+        }
+        Cls => {
+            // 0x00E0: Clear the display
+            clear_screen(state);
+        }
+        Sys(0x230) if state.hires => {
+            // 0x0230: Clear the display in hires mode
+            clear_screen(state);
+        }
+        Ret => {
+            // 0x00EE: Return from subroutine
+            state.pc = state.stack.pop_back().ok_or(Chip8Error::StackUnderflow)?;
+            if state.memory_stack {
+                // The return address the program may have rewritten in memory wins
+                let slot = constants::STACK_OFFSET + 2 * state.stack.len();
+                state.pc = u16::from_be_bytes([state.memory[slot], state.memory[slot + 1]])
+                    as usize
+                    & state.address_mask;
             }
-            _ => {
-                // 0x0NNN: Execute machine language subroutine at address NNN
-                warn!("Ignored instruction: {:04X}", instruction);
+        }
+        Sys(nnn @ (0x0C0..=0x0DF | 0x0FB..=0x0FF)) => {
+            // 0x00CN, 0x00DN, 0x00FB-0x00FF: SUPER-CHIP and XO-CHIP scrolling and display modes, which aren't supported
+            unknown_op(state, nnn)?;
+        }
+        Sys(nnn) => {
+            // 0x0NNN: Execute machine language subroutine at address NNN, which can't be supported
+            if state.strict {
+                return Err(Chip8Error::UnsupportedMachineCall(nnn));
             }
-        },
-        0x1000 => {
+            warn!("Ignored instruction: {:04X}", nnn);
+        }
+        Jump(nnn) => {
             // 0x1NNN: Jump to address NNN
-            let nnn = (instruction & 0x0FFF) as usize;
-            state.pc = nnn;
+            state.pc = nnn as usize;
         }
-        0x2000 => {
+        Call(nnn) => {
             // 0x2NNN: Execute subroutine starting at address NNN
+            if state
+                .max_stack_depth
+                .is_some_and(|max| state.stack.len() >= max)
+                || state.memory_stack && state.stack.len() >= constants::MEMORY_STACK_DEPTH
+            {
+                return Err(Chip8Error::StackOverflow);
+            }
 
-            //// No need for this limitation in our implementation.
-            // if state.stack.len() >= 12 {
-            //     return Err("Stack overflow on CALL".into());
-            // }
-
-            let nnn = (instruction & 0x0FFF) as usize;
+            if state.memory_stack {
+                let slot = constants::STACK_OFFSET + 2 * state.stack.len();
+                state.memory[slot..slot + 2].copy_from_slice(&(state.pc as u16).to_be_bytes());
+            }
             state.stack.push_back(state.pc);
-            state.pc = nnn;
+            state.pc = nnn as usize;
         }
-        0x3000 => {
+        SkipEqByte { x, nn } => {
             // 0x3XNN: Skip the following instruction if the value of register VX equals NN
-            let x = ((instruction & 0x0F00) >> 8) as usize;
-            let nn = (instruction & 0x00FF) as u8;
-
-            if state.v[x] == nn {
-                state.pc += 2;
+            if state.v[x as usize] == nn {
+                skip(state);
             }
         }
-        0x4000 => {
+        SkipNotEqByte { x, nn } => {
             // 0x4XNN: Skip the following instruction if the value of register VX does not equal NN
-            let x = ((instruction & 0x0F00) >> 8) as usize;
-            let nn = (instruction & 0x00FF) as u8;
-            if state.v[x] != nn {
-                state.pc += 2;
+            if state.v[x as usize] != nn {
+                skip(state);
             }
         }
-        0x5000 => {
+        SkipEqReg { x, y } => {
             // 0x5XY0: Skip the following instruction if the value of register VX is equal to the value of register VY
-            let x = ((instruction & 0x0F00) >> 8) as usize;
-            let y = ((instruction & 0x00F0) >> 4) as usize;
-            if state.v[x] == state.v[y] {
-                state.pc += 2;
+            if state.v[x as usize] == state.v[y as usize] {
+                skip(state);
             }
         }
-        0x6000 => {
+        LoadByte { x, nn } => {
             // 0x6XNN: Store number NN in register VX
-            let x = ((instruction & 0x0F00) >> 8) as usize;
-            let nn = (instruction & 0x00FF) as u8;
-            state.v[x] = nn;
+            state.v[x as usize] = nn;
         }
-        0x7000 => {
+        AddByte { x, nn } => {
             // 0x7XNN: Add the value NN to register VX (no carry flag)
-            let x = ((instruction & 0x0F00) >> 8) as usize;
-            let nn = (instruction & 0x00FF) as u8;
-            state.v[x] = state.v[x].wrapping_add(nn);
-        }
-        0x8000 => match instruction & 0x000F {
-            0x0 => {
-                // 0x8XY0: Store the value of register VY in register VX
-                let x = ((instruction & 0x0F00) >> 8) as usize;
-                let y = ((instruction & 0x00F0) >> 4) as usize;
-                state.v[x] = state.v[y];
-            }
-            0x1 => {
-                // 0x8XY1: Set VX to VX OR VY
-                let x = ((instruction & 0x0F00) >> 8) as usize;
-                let y = ((instruction & 0x00F0) >> 4) as usize;
-                state.v[x] |= state.v[y];
-            }
-            0x2 => {
-                // 0x8XY2: Set VX to VX AND VY
-                let x = ((instruction & 0x0F00) >> 8) as usize;
-                let y = ((instruction & 0x00F0) >> 4) as usize;
-                state.v[x] &= state.v[y];
-            }
-            0x3 => {
-                // 0x8XY3: Set VX to VX XOR VY
-                let x = ((instruction & 0x0F00) >> 8) as usize;
-                let y = ((instruction & 0x00F0) >> 4) as usize;
-                state.v[x] ^= state.v[y];
-            }
-            0x4 => {
-                // 0x8XY4: Add the value of register VY to register VX (set carry flag)
-                let x = ((instruction & 0x0F00) >> 8) as usize;
-                let y = ((instruction & 0x00F0) >> 4) as usize;
-                let (result, did_overflow) = state.v[x].overflowing_add(state.v[y]);
-                state.v[x] = result;
-                state.v[0xF] = if did_overflow { 1 } else { 0 };
-            }
-            0x5 => {
-                // 0x8XY5: Subtract the value of register VY from register VX (set borrow flag)
-                let x = ((instruction & 0x0F00) >> 8) as usize;
-                let y = ((instruction & 0x00F0) >> 4) as usize;
-                let (result, did_overflow) = state.v[x].overflowing_sub(state.v[y]);
-                state.v[x] = result;
-                state.v[0xF] = if did_overflow { 0 } else { 1 };
-            }
-            0x6 => {
-                // 0x8XY6: Store the value of register VY shifted right one bit in register VX
-                let x = ((instruction & 0x0F00) >> 8) as usize;
-                let y = ((instruction & 0x00F0) >> 4) as usize;
-                state.v[0xF] = state.v[y] & 0b0000_0001;
-                state.v[x] = state.v[y] >> 1;
-            }
-            0x7 => {
-                // 0x8XY7: Set register VX to the value of VY minus VX (set borrow flag)
-                let x = ((instruction & 0x0F00) >> 8) as usize;
-                let y = ((instruction & 0x00F0) >> 4) as usize;
-                let (result, did_overflow) = state.v[y].overflowing_sub(state.v[x]);
-                state.v[x] = result;
-                state.v[0xF] = if did_overflow { 0 } else { 1 };
+            state.v[x as usize] = state.v[x as usize].wrapping_add(nn);
+        }
+        LoadReg { x, y } => {
+            // 0x8XY0: Store the value of register VY in register VX
+            state.v[x as usize] = state.v[y as usize];
+        }
+        Or { x, y } => {
+            // 0x8XY1: Set VX to VX OR VY
+            state.v[x as usize] |= state.v[y as usize];
+            if state.quirks.vf_reset_on_logic {
+                state.v[0xF] = 0;
             }
-            0xE => {
-                // 0x8XYE: Store the value of register VY shifted left one bit in register VX
-                let x = ((instruction & 0x0F00) >> 8) as usize;
-                let y = ((instruction & 0x00F0) >> 4) as usize;
-                state.v[0xF] = (state.v[y] & 0b1000_0000) >> 7;
-                state.v[x] = state.v[y] << 1;
+        }
+        And { x, y } => {
+            // 0x8XY2: Set VX to VX AND VY
+            state.v[x as usize] &= state.v[y as usize];
+            if state.quirks.vf_reset_on_logic {
+                state.v[0xF] = 0;
             }
-            _ => {
-                unknown_op(instruction);
+        }
+        Xor { x, y } => {
+            // 0x8XY3: Set VX to VX XOR VY
+            state.v[x as usize] ^= state.v[y as usize];
+            if state.quirks.vf_reset_on_logic {
+                state.v[0xF] = 0;
             }
-        },
-        0x9000 => {
+        }
+        AddReg { x, y } => {
+            // 0x8XY4: Add the value of register VY to register VX (set carry flag). Like the other arithmetic, the
+            // operands are read first and the flag is written last, so with VF as an operand or the destination, VF
+            // ends up holding the flag
+            let (result, did_overflow) = state.v[x as usize].overflowing_add(state.v[y as usize]);
+            state.v[x as usize] = result;
+            state.v[0xF] = if did_overflow { 1 } else { 0 };
+        }
+        Sub { x, y } => {
+            // 0x8XY5: Subtract the value of register VY from register VX (set borrow flag)
+            let (result, did_overflow) = state.v[x as usize].overflowing_sub(state.v[y as usize]);
+            state.v[x as usize] = result;
+            state.v[0xF] = if did_overflow { 0 } else { 1 };
+        }
+        ShiftRight { x, y } => {
+            // 0x8XY6: Store the value of register VY shifted right one bit in register VX
+            let source = if state.quirks.shift_uses_vy { y } else { x };
+            let value = state.v[source as usize];
+            state.v[x as usize] = value >> 1;
+            state.v[0xF] = value & 0b0000_0001;
+        }
+        SubN { x, y } => {
+            // 0x8XY7: Set register VX to the value of VY minus VX (set borrow flag)
+            let (result, did_overflow) = state.v[y as usize].overflowing_sub(state.v[x as usize]);
+            state.v[x as usize] = result;
+            state.v[0xF] = if did_overflow { 0 } else { 1 };
+        }
+        ShiftLeft { x, y } => {
+            // 0x8XYE: Store the value of register VY shifted left one bit in register VX
+            let source = if state.quirks.shift_uses_vy { y } else { x };
+            let value = state.v[source as usize];
+            state.v[x as usize] = value << 1;
+            state.v[0xF] = (value & 0b1000_0000) >> 7;
+        }
+        SkipNotEqReg { x, y } => {
             // 0x9XY0: Skip the following instruction if the value of register VX is not equal to the value of register VY
-            let x = ((instruction & 0x0F00) >> 8) as usize;
-            let y = ((instruction & 0x00F0) >> 4) as usize;
-
-            match instruction & 0x000F {
-                0x0 => {
-                    if state.v[x] != state.v[y] {
-                        state.pc += 2;
-                    }
-                }
-                _ => {
-                    unknown_op(instruction);
-                }
+            if state.v[x as usize] != state.v[y as usize] {
+                skip(state);
             }
         }
-        0xA000 => {
+        LoadI(nnn) => {
             // 0xANNN: Store memory address NNN in register I
-            let nnn = (instruction & 0x0FFF) as usize;
-            state.i = nnn;
+            state.i = nnn as usize;
         }
-        0xB000 => {
-            // 0xBNNN: Jump to address NNN plus V0
-            let nnn = (instruction & 0x0FFF) as usize;
-            state.pc = nnn + (state.v[0] as usize);
+        JumpOffset(nnn) => {
+            // 0xBNNN: Jump to address NNN plus V0 (or 0xBXNN: XNN plus VX, see `Quirks::jump_uses_vx`)
+            let x = if state.quirks.jump_uses_vx {
+                (nnn >> 8) as usize
+            } else {
+                0
+            };
+            state.pc = (nnn as usize + state.v[x] as usize) & state.address_mask;
         }
-        0xC000 => {
+        Random { x, nn } => {
             // 0xCXNN: Set VX to a random number with a mask of NN
-            let x = ((instruction & 0x0F00) >> 8) as usize;
-            let nn = (instruction & 0x00FF) as u8;
-
-            let rand_byte: u8 =
-                ((state.pc + state.i + state.v.iter().sum::<u8>() as usize) & 0xFF) as u8; // FIXME: Placeholder for random byte generation
-            state.v[x] = rand_byte & nn;
+            state.v[x as usize] = state.rng.next_u8() & nn;
         }
-        0xD000 => {
+        Draw { x, y, n } => {
             // 0xDXYN: Draw a sprite at position VX, VY with N bytes of sprite data starting at the address stored in I.
             // Set VF to 01 if any set pixels are changed to unset, and 00 otherwise
-            let x = ((instruction & 0x0F00) >> 8) as usize;
-            let y = ((instruction & 0x00F0) >> 4) as usize;
-            let n = (instruction & 0x000F) as usize;
-            draw_sprite(state, x, y, n);
-        }
-        0xE000 => {
-            let x = ((instruction & 0x0F00) >> 8) as usize;
-            match instruction & 0x00FF {
-                0x9E => {
-                    // 0xEX9E: Skip the following instruction if the key stored in VX is pressed
-                    if state.key_pressed == Some(state.v[x]) {
-                        state.pc += 2;
-                    }
-                    state.key_pressed = None;
-                }
-                0xA1 => {
-                    // 0xEXA1: Skip the following instruction if the key stored in VX is not pressed
-                    if state.key_pressed != Some(state.v[x]) {
-                        state.pc += 2;
-                    }
-                    state.key_pressed = None;
-                }
-                _ => {
-                    unknown_op(instruction);
-                }
+            let (vx, vy) = (state.v[x as usize], state.v[y as usize]);
+            draw_sprite(state, vx as usize, vy as usize, n as usize);
+            state.waiting_for_vblank = state.quirks.display_wait;
+        }
+        SkipKeyPressed(x) => {
+            // 0xEX9E: Skip the following instruction if the key stored in VX is pressed
+            if state.keys & (1 << (state.v[x as usize] & 0xF)) != 0 {
+                skip(state);
             }
         }
-        0xF000 => {
-            let x = ((instruction & 0x0F00) >> 8) as usize;
-            match instruction & 0x00FF {
-                0x07 => {
-                    // 0xFX07: Store the current value of the delay timer in register VX
-                    state.v[x] = state.delay_timer;
-                }
-                0x0A => {
-                    // 0xFX0A: Wait for a key press and store the value of the key in register VX
-                    state.waiting_for_keypress = Some(x);
-                }
-                0x15 => {
-                    // 0xFX15: Set the delay timer to the value of register VX
-                    state.delay_timer = state.v[x];
-                }
-                0x18 => {
-                    // 0xFX18: Set the sound timer to the value of register VX
-                    state.sound_timer = state.v[x];
-                }
-                0x1E => {
-                    // 0xFX1E: Add the value stored in register VX to register I
-                    state.i = state.i.wrapping_add(state.v[x] as usize) & 0xFFF;
-                }
-                0x29 => {
-                    // 0xFX29: Set I to the location of the sprite for the character in VX.
-                    // Characters 0-F (in hexadecimal) are represented by a 4x5 font
-                    state.i =
-                        constants::CHARACTER_SPRITE_OFFSET + ((state.v[x] & 0xF) as usize) * 5;
-                }
-                0x33 => {
-                    // 0xFX33: Store the binary-coded decimal representation of VX,
-                    // with the hundreds digit at the address in I, the tens digit at I+1, and the ones digit at I+2
-                    let (hundreds, tens, ones) = bcd(state.v[x]);
-                    state.memory[state.i] = hundreds;
-                    state.memory[state.i + 1] = tens;
-                    state.memory[state.i + 2] = ones;
-                }
-                0x55 => {
-                    // 0xFX55: Store registers V0 through VX in memory starting at location I
-                    for i in 0..=x {
-                        state.memory[state.i + i] = state.v[i];
-                        state.i += x + 1;
-                    }
-                }
-                0x65 => {
-                    // 0xFX65: Read registers V0 through VX from memory starting at location I
-                    for i in 0..=x {
-                        state.v[i] = state.memory[state.i + i];
-                        state.i += x + 1;
-                    }
-                }
-                0xFF => {
-                    // 0xFXFF: Halt execution (NB: Not part of the original CHIP-8 instruction set)
-                    return Ok(Some(x));
-                }
-                _ => {
-                    unknown_op(instruction);
-                }
+        SkipKeyNotPressed(x) => {
+            // 0xEXA1: Skip the following instruction if the key stored in VX is not pressed
+            if state.keys & (1 << (state.v[x as usize] & 0xF)) == 0 {
+                skip(state);
+            }
+        }
+        LoadLongI if state.quirks.xo_chip_long_addressing => {
+            // 0xF000 NNNN: Store the 16-bit address NNNN, in the two bytes after the opcode, in register I (XO-CHIP)
+            state.i = fetch(state) as usize & state.address_mask;
+            advance(state, 2);
+        }
+        LoadLongI => {
+            unknown_op(state, 0xF000)?;
+        }
+        Plane(n) if state.quirks.xo_chip_planes => {
+            // 0xFN01: Select the planes to draw to and clear (XO-CHIP)
+            state.planes = n & 0b11;
+        }
+        Plane(n) => {
+            unknown_op(state, 0xF001 | (n as u16) << 8)?;
+        }
+        LoadDelay(x) => {
+            // 0xFX07: Store the current value of the delay timer in register VX
+            state.v[x as usize] = state.delay_timer;
+        }
+        WaitKey(x) => {
+            // 0xFX0A: Wait for a key press and store the value of the key in register VX
+            state.waiting_for_keypress = Some(x as usize);
+        }
+        SetDelay(x) => {
+            // 0xFX15: Set the delay timer to the value of register VX
+            state.delay_timer = state.v[x as usize];
+        }
+        SetSound(x) => {
+            // 0xFX18: Set the sound timer to the value of register VX
+            state.sound_timer = state.v[x as usize];
+        }
+        AddI(x) => {
+            // 0xFX1E: Add the value stored in register VX to register I
+            let sum = state.i.wrapping_add(state.v[x as usize] as usize);
+            state.i = sum & state.address_mask;
+            if state.quirks.fx1e_overflow_vf {
+                state.v[0xF] = (sum > state.address_mask) as u8;
+            }
+        }
+        LoadFont(x) => {
+            // 0xFX29: Set I to the location of the sprite for the character in VX.
+            // Characters 0-F (in hexadecimal) are represented by a 4x5 font
+            state.i = state.font_offset()
+                + ((state.v[x as usize] & 0xF) as usize) * font::SMALL_GLYPH_SIZE;
+        }
+        LoadBigFont(x) => {
+            // 0xFX30: Set I to the location of the big 8x10 sprite for the character in VX (SUPER-CHIP)
+            state.i = state.big_font_offset()
+                + ((state.v[x as usize] & 0xF) as usize) * font::BIG_GLYPH_SIZE;
+        }
+        StoreBcd(x) => {
+            // 0xFX33: Store the binary-coded decimal representation of VX,
+            // with the hundreds digit at the address in I, the tens digit at I+1, and the ones digit at I+2. Like the
+            // other addresses, they wrap at the end of memory
+            let (hundreds, tens, ones) = bcd(state.v[x as usize]);
+            for (offset, digit) in [hundreds, tens, ones].into_iter().enumerate() {
+                state.memory[(state.i + offset) & state.address_mask] = digit;
+            }
+        }
+        Store(x) => {
+            // 0xFX55: Store registers V0 through VX in memory starting at location I
+            let x = x as usize;
+            for i in 0..=x {
+                state.memory[(state.i + i) & state.address_mask] = state.v[i];
+            }
+            state.i = (state.i + x + 1) & state.address_mask;
+        }
+        Load(x) => {
+            // 0xFX65: Read registers V0 through VX from memory starting at location I
+            let x = x as usize;
+            for i in 0..=x {
+                state.v[i] = state.memory[(state.i + i) & state.address_mask];
+            }
+            state.i = (state.i + x + 1) & state.address_mask;
+        }
+        SaveFlags(x) => {
+            // 0xFX75: Store registers V0 through VX in the RPL user flags (X <= 7)
+            let n = (x as usize).min(7);
+            state.flags[..=n].copy_from_slice(&state.v[..=n]);
+        }
+        LoadFlags(x) => {
+            // 0xFX85: Read registers V0 through VX from the RPL user flags (X <= 7)
+            let n = (x as usize).min(7);
+            state.v[..=n].copy_from_slice(&state.flags[..=n]);
+        }
+        Halt(x) => {
+            // 0xFXFF: Halt execution (NB: Not part of the original CHIP-8 instruction set)
+            // Halts immediately, even inside a subroutine
+            if !state.stack.is_empty() {
+                let call_stack: Vec<String> = state
+                    .stack
+                    .iter()
+                    .map(|addr| format!("{addr:03X}"))
+                    .collect();
+                debug!(
+                    "Halted inside a subroutine, return addresses: [{}]",
+                    call_stack.join(", ")
+                );
             }
+            return Ok(Some(x as usize));
         }
-        _ => {
-            unknown_op(instruction);
+        Unknown(opcode) => {
+            unknown_op(state, opcode)?;
         }
     }
 
@@ -316,6 +505,619 @@ fn bcd(value: u8) -> (u8, u8, u8) {
     (hundreds, tens, ones)
 }
 
-pub fn unknown_op(instruction: u16) {
+/// Handle an unrecognized instruction: ignore it with a warning, or fail in strict mode.
+///
+/// # Arguments
+/// * `state` - The current state of the CHIP-8 interpreter.
+/// * `instruction` - The unrecognized instruction.
+///
+/// # Returns
+/// * `Ok(())` if the instruction was ignored.
+/// * `Err(Chip8Error::UnknownOpcode)` in strict mode.
+pub fn unknown_op(state: &state::State, instruction: u16) -> Result<(), Chip8Error> {
+    if state.strict {
+        // The program counter has already moved past the instruction
+        return Err(Chip8Error::UnknownOpcode {
+            opcode: instruction,
+            pc: state.pc.wrapping_sub(2) & state.address_mask,
+        });
+    }
+
     warn!("Ignored instruction: {instruction:04X}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::Instruction::*;
+    use crate::state::State;
+
+    /// An instruction, executed as if fetched from 0x200, with the state prepared by `setup` and verified by `check`.
+    struct Case {
+        instruction: Instruction,
+        setup: fn(&mut State),
+        check: fn(&State),
+    }
+
+    fn run(case: &Case) -> Result<Option<usize>, Chip8Error> {
+        let mut state = State::with_seed(0);
+        state.pc = 0x202;
+        (case.setup)(&mut state);
+        let result = execute(&mut state, case.instruction);
+        (case.check)(&state);
+        result
+    }
+
+    #[test]
+    fn control_flow() {
+        let cases = [
+            Case {
+                instruction: Nop,
+                setup: |_| {},
+                check: |s| assert_eq!(s.pc, 0x202),
+            },
+            Case {
+                instruction: Jump(0x345),
+                setup: |_| {},
+                check: |s| assert_eq!(s.pc, 0x345),
+            },
+            Case {
+                instruction: Call(0x345),
+                setup: |_| {},
+                check: |s| {
+                    assert_eq!(s.pc, 0x345);
+                    assert_eq!(s.stack, [0x202]);
+                },
+            },
+            Case {
+                instruction: Ret,
+                setup: |s| s.stack.push_back(0x400),
+                check: |s| {
+                    assert_eq!(s.pc, 0x400);
+                    assert!(s.stack.is_empty());
+                },
+            },
+            Case {
+                instruction: JumpOffset(0x300),
+                setup: |s| s.v[0] = 0x10,
+                check: |s| assert_eq!(s.pc, 0x310),
+            },
+            Case {
+                instruction: SkipEqByte { x: 1, nn: 0x42 },
+                setup: |s| s.v[1] = 0x42,
+                check: |s| assert_eq!(s.pc, 0x204),
+            },
+            Case {
+                instruction: SkipEqByte { x: 1, nn: 0x42 },
+                setup: |s| s.v[1] = 0x41,
+                check: |s| assert_eq!(s.pc, 0x202),
+            },
+            Case {
+                instruction: SkipNotEqByte { x: 1, nn: 0x42 },
+                setup: |s| s.v[1] = 0x41,
+                check: |s| assert_eq!(s.pc, 0x204),
+            },
+            Case {
+                instruction: SkipNotEqByte { x: 1, nn: 0x42 },
+                setup: |s| s.v[1] = 0x42,
+                check: |s| assert_eq!(s.pc, 0x202),
+            },
+            Case {
+                instruction: SkipEqReg { x: 1, y: 2 },
+                setup: |s| s.v[1..3].copy_from_slice(&[7, 7]),
+                check: |s| assert_eq!(s.pc, 0x204),
+            },
+            Case {
+                instruction: SkipEqReg { x: 1, y: 2 },
+                setup: |s| s.v[1..3].copy_from_slice(&[7, 8]),
+                check: |s| assert_eq!(s.pc, 0x202),
+            },
+            Case {
+                instruction: SkipNotEqReg { x: 1, y: 2 },
+                setup: |s| s.v[1..3].copy_from_slice(&[7, 8]),
+                check: |s| assert_eq!(s.pc, 0x204),
+            },
+            Case {
+                instruction: SkipNotEqReg { x: 1, y: 2 },
+                setup: |s| s.v[1..3].copy_from_slice(&[7, 7]),
+                check: |s| assert_eq!(s.pc, 0x202),
+            },
+            Case {
+                instruction: SkipKeyPressed(1),
+                setup: |s| {
+                    s.v[1] = 0xA;
+                    s.keys = 1 << 0xA;
+                },
+                check: |s| assert_eq!(s.pc, 0x204),
+            },
+            Case {
+                instruction: SkipKeyNotPressed(1),
+                setup: |s| {
+                    s.v[1] = 0xA;
+                    s.keys = 1 << 0xA;
+                },
+                check: |s| assert_eq!(s.pc, 0x202),
+            },
+        ];
+
+        for case in &cases {
+            assert_eq!(run(case), Ok(None), "{:?}", case.instruction);
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        let cases = [
+            Case {
+                instruction: LoadByte { x: 3, nn: 0x2A },
+                setup: |_| {},
+                check: |s| assert_eq!(s.v[3], 0x2A),
+            },
+            Case {
+                instruction: AddByte { x: 3, nn: 0x02 },
+                setup: |s| s.v[3] = 0xFF,
+                check: |s| {
+                    assert_eq!(s.v[3], 0x01);
+                    assert_eq!(s.v[0xF], 0, "7XNN doesn't carry");
+                },
+            },
+            Case {
+                instruction: LoadReg { x: 3, y: 4 },
+                setup: |s| s.v[4] = 0x99,
+                check: |s| assert_eq!(s.v[3], 0x99),
+            },
+            Case {
+                instruction: Or { x: 3, y: 4 },
+                setup: |s| s.v[3..5].copy_from_slice(&[0b1100, 0b1010]),
+                check: |s| assert_eq!(s.v[3], 0b1110),
+            },
+            Case {
+                instruction: And { x: 3, y: 4 },
+                setup: |s| s.v[3..5].copy_from_slice(&[0b1100, 0b1010]),
+                check: |s| assert_eq!(s.v[3], 0b1000),
+            },
+            Case {
+                instruction: Xor { x: 3, y: 4 },
+                setup: |s| s.v[3..5].copy_from_slice(&[0b1100, 0b1010]),
+                check: |s| assert_eq!(s.v[3], 0b0110),
+            },
+            Case {
+                instruction: AddReg { x: 3, y: 4 },
+                setup: |s| s.v[3..5].copy_from_slice(&[0xFF, 0x02]),
+                check: |s| assert_eq!((s.v[3], s.v[0xF]), (0x01, 1)),
+            },
+            Case {
+                instruction: AddReg { x: 3, y: 4 },
+                setup: |s| s.v[3..5].copy_from_slice(&[0x10, 0x02]),
+                check: |s| assert_eq!((s.v[3], s.v[0xF]), (0x12, 0)),
+            },
+            Case {
+                instruction: Sub { x: 3, y: 4 },
+                setup: |s| s.v[3..5].copy_from_slice(&[0x10, 0x02]),
+                check: |s| assert_eq!((s.v[3], s.v[0xF]), (0x0E, 1)),
+            },
+            Case {
+                instruction: Sub { x: 3, y: 4 },
+                setup: |s| s.v[3..5].copy_from_slice(&[0x02, 0x10]),
+                check: |s| assert_eq!((s.v[3], s.v[0xF]), (0xF2, 0)),
+            },
+            Case {
+                instruction: SubN { x: 3, y: 4 },
+                setup: |s| s.v[3..5].copy_from_slice(&[0x02, 0x10]),
+                check: |s| assert_eq!((s.v[3], s.v[0xF]), (0x0E, 1)),
+            },
+            Case {
+                instruction: SubN { x: 3, y: 4 },
+                setup: |s| s.v[3..5].copy_from_slice(&[0x10, 0x02]),
+                check: |s| assert_eq!((s.v[3], s.v[0xF]), (0xF2, 0)),
+            },
+            Case {
+                instruction: ShiftRight { x: 3, y: 4 },
+                setup: |s| s.v[3..5].copy_from_slice(&[0x00, 0b101]),
+                check: |s| assert_eq!((s.v[3], s.v[0xF]), (0b10, 1)),
+            },
+            Case {
+                instruction: ShiftLeft { x: 3, y: 4 },
+                setup: |s| s.v[3..5].copy_from_slice(&[0x00, 0x81]),
+                check: |s| assert_eq!((s.v[3], s.v[0xF]), (0x02, 1)),
+            },
+            Case {
+                instruction: Random { x: 3, nn: 0x00 },
+                setup: |s| s.v[3] = 0xFF,
+                check: |s| assert_eq!(s.v[3], 0x00),
+            },
+        ];
+
+        for case in &cases {
+            assert_eq!(run(case), Ok(None), "{:?}", case.instruction);
+        }
+    }
+
+    /// When VF is the destination, the flag is written last and wins over the result.
+    #[test]
+    fn arithmetic_into_vf() {
+        let cases = [
+            Case {
+                instruction: SubN { x: 0xF, y: 1 },
+                setup: |s| (s.v[0xF], s.v[1]) = (0x10, 0x02),
+                check: |s| assert_eq!(s.v[0xF], 0),
+            },
+            Case {
+                instruction: AddReg { x: 0xF, y: 0xF },
+                setup: |s| s.v[0xF] = 0x80,
+                check: |s| assert_eq!(s.v[0xF], 1),
+            },
+            Case {
+                instruction: Sub { x: 0xF, y: 0xF },
+                setup: |s| s.v[0xF] = 0x80,
+                check: |s| assert_eq!(s.v[0xF], 1),
+            },
+            Case {
+                instruction: SubN { x: 0xF, y: 0xF },
+                setup: |s| s.v[0xF] = 0x80,
+                check: |s| assert_eq!(s.v[0xF], 1),
+            },
+            Case {
+                instruction: AddReg { x: 0xF, y: 1 },
+                setup: |s| (s.v[0xF], s.v[1]) = (0xFF, 0x02),
+                check: |s| assert_eq!(s.v[0xF], 1),
+            },
+            Case {
+                instruction: AddReg { x: 0xF, y: 1 },
+                setup: |s| (s.v[0xF], s.v[1]) = (0x10, 0x02),
+                check: |s| assert_eq!(s.v[0xF], 0),
+            },
+            Case {
+                instruction: Sub { x: 0xF, y: 1 },
+                setup: |s| (s.v[0xF], s.v[1]) = (0x10, 0x02),
+                check: |s| assert_eq!(s.v[0xF], 1),
+            },
+            Case {
+                instruction: Sub { x: 0xF, y: 1 },
+                setup: |s| (s.v[0xF], s.v[1]) = (0x02, 0x10),
+                check: |s| assert_eq!(s.v[0xF], 0),
+            },
+            Case {
+                instruction: SubN { x: 0xF, y: 1 },
+                setup: |s| (s.v[0xF], s.v[1]) = (0x02, 0x10),
+                check: |s| assert_eq!(s.v[0xF], 1),
+            },
+            Case {
+                instruction: ShiftRight { x: 0xF, y: 1 },
+                setup: |s| (s.v[0xF], s.v[1]) = (0x00, 0b10),
+                check: |s| assert_eq!(s.v[0xF], 0),
+            },
+            Case {
+                instruction: ShiftLeft { x: 0xF, y: 1 },
+                setup: |s| (s.v[0xF], s.v[1]) = (0x00, 0x80),
+                check: |s| assert_eq!(s.v[0xF], 1),
+            },
+            Case {
+                instruction: Or { x: 0xF, y: 1 },
+                setup: |s| (s.v[0xF], s.v[1]) = (0x00, 0x42),
+                check: |s| assert_eq!(s.v[0xF], 0, "VF reset quirk"),
+            },
+        ];
+
+        for case in &cases {
+            assert_eq!(run(case), Ok(None), "{:?}", case.instruction);
+        }
+    }
+
+    /// When VF is the source, its value before the flag is written is used.
+    #[test]
+    fn arithmetic_from_vf() {
+        let cases = [
+            Case {
+                instruction: AddReg { x: 1, y: 0xF },
+                setup: |s| (s.v[1], s.v[0xF]) = (0xFF, 0x02),
+                check: |s| assert_eq!((s.v[1], s.v[0xF]), (0x01, 1)),
+            },
+            Case {
+                instruction: Sub { x: 1, y: 0xF },
+                setup: |s| (s.v[1], s.v[0xF]) = (0x10, 0x02),
+                check: |s| assert_eq!((s.v[1], s.v[0xF]), (0x0E, 1)),
+            },
+            Case {
+                instruction: SubN { x: 1, y: 0xF },
+                setup: |s| (s.v[1], s.v[0xF]) = (0x10, 0x02),
+                check: |s| assert_eq!((s.v[1], s.v[0xF]), (0xF2, 0)),
+            },
+        ];
+
+        for case in &cases {
+            assert_eq!(run(case), Ok(None), "{:?}", case.instruction);
+        }
+    }
+
+    #[test]
+    fn add_i_overflow() {
+        let cases = [
+            Case {
+                instruction: AddI(1),
+                setup: |s| (s.i, s.v[1], s.v[0xF]) = (0xFFF, 0x02, 0x2A),
+                check: |s| assert_eq!((s.i, s.v[0xF]), (0x001, 0x2A), "VF is kept"),
+            },
+            Case {
+                instruction: AddI(1),
+                setup: |s| (s.i, s.v[1], s.v[0xF]) = (0x300, 0x02, 0x2A),
+                check: |s| assert_eq!((s.i, s.v[0xF]), (0x302, 0x2A), "VF is kept"),
+            },
+            Case {
+                instruction: AddI(1),
+                setup: |s| {
+                    s.quirks.fx1e_overflow_vf = true;
+                    (s.i, s.v[1], s.v[0xF]) = (0xFFF, 0x02, 0x2A);
+                },
+                check: |s| assert_eq!((s.i, s.v[0xF]), (0x001, 1)),
+            },
+            Case {
+                instruction: AddI(1),
+                setup: |s| {
+                    s.quirks.fx1e_overflow_vf = true;
+                    (s.i, s.v[1], s.v[0xF]) = (0x300, 0x02, 0x2A);
+                },
+                check: |s| assert_eq!((s.i, s.v[0xF]), (0x302, 0)),
+            },
+        ];
+
+        for case in &cases {
+            assert_eq!(run(case), Ok(None), "{:?}", case.instruction);
+        }
+
+        // With XO-CHIP's 64KB of memory, I only overflows past 0xFFFF
+        let mut state = State::with_memory_size(constants::XO_CHIP_MEMORY_SIZE);
+        state.quirks.fx1e_overflow_vf = true;
+        (state.i, state.v[1]) = (0xFFF, 0x02);
+        assert_eq!(execute(&mut state, AddI(1)), Ok(None));
+        assert_eq!((state.i, state.v[0xF]), (0x1001, 0));
+        (state.i, state.v[1]) = (0xFFFF, 0x02);
+        assert_eq!(execute(&mut state, AddI(1)), Ok(None));
+        assert_eq!((state.i, state.v[0xF]), (0x0001, 1));
+    }
+
+    #[test]
+    fn memory_and_timers() {
+        let cases = [
+            Case {
+                instruction: LoadI(0x345),
+                setup: |_| {},
+                check: |s| assert_eq!(s.i, 0x345),
+            },
+            Case {
+                instruction: AddI(1),
+                setup: |s| (s.i, s.v[1]) = (0x300, 0x10),
+                check: |s| assert_eq!(s.i, 0x310),
+            },
+            Case {
+                instruction: LoadFont(1),
+                setup: |s| s.v[1] = 0xA,
+                check: |s| assert_eq!(s.i, constants::SMALL_FONT_OFFSET + 50),
+            },
+            Case {
+                instruction: LoadBigFont(1),
+                setup: |s| s.v[1] = 0x1,
+                check: |s| assert_eq!(s.i, constants::LARGE_FONT_OFFSET + 10),
+            },
+            Case {
+                instruction: StoreBcd(1),
+                setup: |s| (s.i, s.v[1]) = (0x300, 234),
+                check: |s| assert_eq!(s.memory[0x300..0x303], [2, 3, 4]),
+            },
+            Case {
+                instruction: StoreBcd(1),
+                setup: |s| (s.i, s.v[1]) = (0xFFE, 234),
+                check: |s| {
+                    assert_eq!(s.memory[0xFFE..], [2, 3]);
+                    assert_eq!(s.memory[0x000], 4, "The ones digit wraps to address 0");
+                },
+            },
+            Case {
+                instruction: Store(2),
+                setup: |s| {
+                    s.i = 0x300;
+                    s.v[..4].copy_from_slice(&[1, 2, 3, 4]);
+                },
+                check: |s| {
+                    assert_eq!(s.memory[0x300..0x304], [1, 2, 3, 0]);
+                    assert_eq!(s.i, 0x303);
+                },
+            },
+            Case {
+                instruction: Load(2),
+                setup: |s| {
+                    s.i = 0x300;
+                    s.memory[0x300..0x304].copy_from_slice(&[1, 2, 3, 4]);
+                },
+                check: |s| {
+                    assert_eq!(s.v[..4], [1, 2, 3, 0]);
+                    assert_eq!(s.i, 0x303);
+                },
+            },
+            Case {
+                instruction: SaveFlags(1),
+                setup: |s| s.v[..2].copy_from_slice(&[5, 6]),
+                check: |s| assert_eq!(s.flags[..3], [5, 6, 0]),
+            },
+            Case {
+                instruction: LoadFlags(1),
+                setup: |s| s.flags[..2].copy_from_slice(&[5, 6]),
+                check: |s| assert_eq!(s.v[..3], [5, 6, 0]),
+            },
+            Case {
+                instruction: LoadDelay(1),
+                setup: |s| s.delay_timer = 30,
+                check: |s| assert_eq!(s.v[1], 30),
+            },
+            Case {
+                instruction: SetDelay(1),
+                setup: |s| s.v[1] = 30,
+                check: |s| assert_eq!(s.delay_timer, 30),
+            },
+            Case {
+                instruction: SetSound(1),
+                setup: |s| s.v[1] = 30,
+                check: |s| assert_eq!(s.sound_timer, 30),
+            },
+            Case {
+                instruction: WaitKey(1),
+                setup: |_| {},
+                check: |s| assert_eq!(s.waiting_for_keypress, Some(1)),
+            },
+        ];
+
+        for case in &cases {
+            assert_eq!(run(case), Ok(None), "{:?}", case.instruction);
+        }
+    }
+
+    #[test]
+    fn display() {
+        let cases = [
+            Case {
+                instruction: Cls,
+                setup: |s| s.screen[0] = true,
+                check: |s| assert!(s.screen.iter().all(|&pixel| !pixel)),
+            },
+            Case {
+                instruction: Draw { x: 1, y: 2, n: 5 },
+                setup: |s| (s.v[1], s.v[2], s.i) = (8, 4, constants::SMALL_FONT_OFFSET),
+                check: |s| {
+                    // The top row of the glyph for 0 is 0xF0
+                    assert!((8..12).all(|x| s.pixel(x, 4)));
+                    assert!(!s.pixel(12, 4));
+                    assert_eq!(s.v[0xF], 0);
+                    assert!(s.waiting_for_vblank);
+                },
+            },
+            Case {
+                instruction: Draw { x: 1, y: 1, n: 1 },
+                setup: |s| {
+                    s.i = constants::SMALL_FONT_OFFSET;
+                    s.screen[0] = true;
+                },
+                check: |s| {
+                    assert!(!s.pixel(0, 0));
+                    assert_eq!(s.v[0xF], 1);
+                },
+            },
+            Case {
+                instruction: Draw { x: 1, y: 2, n: 1 },
+                setup: |s| (s.v[1], s.v[2], s.i) = (64 + 8, 32 + 4, constants::SMALL_FONT_OFFSET),
+                check: |s| {
+                    // The starting position wraps around the screen
+                    assert!((8..12).all(|x| s.pixel(x, 4)));
+                    assert_eq!(s.v[0xF], 0);
+                },
+            },
+            Case {
+                instruction: Draw { x: 1, y: 2, n: 5 },
+                setup: |s| (s.v[1], s.v[2], s.i) = (62, 30, constants::SMALL_FONT_OFFSET),
+                check: |s| {
+                    // With clip_sprites, the sprite is cut off at the right and bottom edges
+                    assert!(s.pixel(62, 30) && s.pixel(63, 30) && s.pixel(62, 31));
+                    assert_eq!(s.screen.iter().filter(|&&pixel| pixel).count(), 3);
+                },
+            },
+            Case {
+                instruction: Draw { x: 1, y: 2, n: 5 },
+                setup: |s| {
+                    (s.v[1], s.v[2], s.i) = (62, 30, constants::SMALL_FONT_OFFSET);
+                    s.quirks.clip_sprites = false;
+                },
+                check: |s| {
+                    // Without it, the sprite wraps around to the left and top edges: 0xF0, 0x90, 0x90, 0x90, 0xF0
+                    assert!(
+                        [62, 63, 0, 1]
+                            .iter()
+                            .all(|&x| s.pixel(x, 30) && s.pixel(x, 2))
+                    );
+                    assert!(
+                        (0..3).all(|y| s.pixel(62, (30 + y) % 32) && s.pixel(1, (30 + y) % 32))
+                    );
+                    assert!(!s.pixel(63, 31) && !s.pixel(0, 31));
+                    assert_eq!(s.v[0xF], 0);
+                },
+            },
+            Case {
+                instruction: Draw { x: 1, y: 2, n: 1 },
+                setup: |s| {
+                    (s.v[1], s.v[2], s.i) = (62, 0, constants::SMALL_FONT_OFFSET);
+                    s.quirks.clip_sprites = false;
+                    s.screen[1] = true;
+                },
+                check: |s| {
+                    // A collision in the wrapped part of the sprite counts too
+                    assert!(!s.pixel(1, 0) && s.pixel(0, 0));
+                    assert_eq!(s.v[0xF], 1);
+                },
+            },
+        ];
+
+        for case in &cases {
+            assert_eq!(run(case), Ok(None), "{:?}", case.instruction);
+        }
+    }
+
+    #[test]
+    fn halt_and_errors() {
+        let halt = Case {
+            instruction: Halt(3),
+            setup: |_| {},
+            check: |_| {},
+        };
+        assert_eq!(run(&halt), Ok(Some(3)));
+
+        let underflow = Case {
+            instruction: Ret,
+            setup: |_| {},
+            check: |_| {},
+        };
+        assert_eq!(run(&underflow), Err(Chip8Error::StackUnderflow));
+
+        let unknown = Case {
+            instruction: Unknown(0x5121),
+            setup: |s| s.strict = true,
+            check: |_| {},
+        };
+        assert_eq!(
+            run(&unknown),
+            Err(Chip8Error::UnknownOpcode {
+                opcode: 0x5121,
+                pc: 0x200
+            })
+        );
+    }
+
+    #[test]
+    fn machine_calls() {
+        let lenient = Case {
+            instruction: Sys(0x300),
+            setup: |_| {},
+            check: |s| assert_eq!(s.pc, 0x202),
+        };
+        assert_eq!(run(&lenient), Ok(None));
+
+        let strict = Case {
+            instruction: Sys(0x300),
+            setup: |s| s.strict = true,
+            check: |_| {},
+        };
+        assert_eq!(run(&strict), Err(Chip8Error::UnsupportedMachineCall(0x300)));
+
+        // A SUPER-CHIP scroll isn't a machine code call
+        let scroll = Case {
+            instruction: Sys(0x0FB),
+            setup: |s| s.strict = true,
+            check: |_| {},
+        };
+        assert_eq!(
+            run(&scroll),
+            Err(Chip8Error::UnknownOpcode {
+                opcode: 0x00FB,
+                pc: 0x200
+            })
+        );
+    }
 }
@@ -0,0 +1,161 @@
+//! CHIP-8 disassembler.
+//!
+//! Converts opcodes to mnemonics in the common syntax of Cowgod's "Chip-8 Technical Reference", e.g. `LD V0, 0x2A` or
+//! `DRW V1, V2, 5`. Opcodes that aren't instructions are shown as data words, e.g. `DW 0x8008`.
+
+use crate::instruction::{self, Instruction};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Convert an opcode to its mnemonic.
+///
+/// # Arguments
+/// * `opcode` - The opcode to disassemble.
+pub fn mnemonic(opcode: u16) -> String {
+    instruction::decode(opcode).to_string()
+}
+
+/// Lazily decodes the instructions in a memory slice, two bytes at a time.
+pub struct InstructionIter<'a> {
+    memory: &'a [u8],
+    address: usize,
+}
+
+impl<'a> InstructionIter<'a> {
+    /// Create an iterator.
+    ///
+    /// # Arguments
+    /// * `memory` - The memory to decode.
+    /// * `address` - The index in `memory` of the first instruction.
+    pub fn new(memory: &'a [u8], address: usize) -> Self {
+        Self { memory, address }
+    }
+}
+
+impl Iterator for InstructionIter<'_> {
+    type Item = (usize, Instruction);
+
+    /// Decode the next instruction, or return `None` when fewer than two bytes of memory remain.
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = self
+            .memory
+            .get(self.address..self.address.checked_add(2)?)?;
+        let address = self.address;
+        self.address += 2;
+        Some((
+            address,
+            instruction::decode(u16::from_be_bytes([bytes[0], bytes[1]])),
+        ))
+    }
+}
+
+/// Disassemble a program, two bytes at a time.
+///
+/// # Arguments
+/// * `program` - The program bytes. A trailing odd byte is ignored.
+/// * `origin` - The address of the first byte, usually 0x200.
+///
+/// # Returns
+/// A vector of tuples containing the address, the opcode, and the mnemonic of each instruction.
+pub fn disassemble(program: &[u8], origin: usize) -> Vec<(usize, u16, String)> {
+    program
+        .chunks_exact(2)
+        .enumerate()
+        .map(|(i, bytes)| {
+            let opcode = u16::from_be_bytes([bytes[0], bytes[1]]);
+            (origin + 2 * i, opcode, mnemonic(opcode))
+        })
+        .collect()
+}
+
+/// Format bytes as a hex dump, 16 bytes per line, e.g. `0200: 60 2A 12 00 ... |`*..|`.
+///
+/// # Arguments
+/// * `bytes` - The bytes to dump.
+/// * `origin` - The address of the first byte.
+///
+/// # Returns
+/// The lines of the dump, each with the address, the bytes in hex, and the printable ASCII characters.
+pub fn hexdump(bytes: &[u8], origin: usize) -> Vec<String> {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, line)| {
+            let hex: Vec<String> = line.iter().map(|byte| format!("{byte:02X}")).collect();
+            let ascii: String = line
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("{:04X}: {:<47} |{}|", origin + 16 * i, hex.join(" "), ascii)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mnemonics() {
+        assert_eq!(mnemonic(0x00E0), "CLS");
+        assert_eq!(mnemonic(0x2345), "CALL 0x345");
+        assert_eq!(mnemonic(0x3042), "SE V0, 0x42");
+        assert_eq!(mnemonic(0x8AB6), "SHR VA, VB");
+        assert_eq!(mnemonic(0xD125), "DRW V1, V2, 5");
+        assert_eq!(mnemonic(0xF365), "LD V3, [I]");
+        assert_eq!(mnemonic(0xF3FF), "HALT 3");
+        assert_eq!(mnemonic(0x8008), "DW 0x8008");
+    }
+
+    #[test]
+    fn disassemble_program() {
+        let program = [0x60, 0x2A, 0x12, 0x00, 0xFF];
+
+        assert_eq!(
+            disassemble(&program, 0x200),
+            [
+                (0x200, 0x602A, "LD V0, 0x2A".to_string()),
+                (0x202, 0x1200, "JP 0x200".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn hexdump_lines() {
+        let bytes: Vec<u8> = (0x30..0x30 + 18).collect();
+
+        assert_eq!(
+            hexdump(&bytes, 0x200),
+            [
+                "0200: 30 31 32 33 34 35 36 37 38 39 3A 3B 3C 3D 3E 3F |0123456789:;<=>?|",
+                "0210: 40 41                                           |@A|",
+            ]
+        );
+        assert_eq!(
+            hexdump(&[0x00, 0x7F], 0x200),
+            ["0200: 00 7F                                           |..|"]
+        );
+    }
+
+    #[test]
+    fn iterate_instructions() {
+        let memory = [0x00, 0x60, 0x2A, 0x80, 0x08, 0x12];
+
+        let instructions: Vec<_> = InstructionIter::new(&memory, 1).collect();
+
+        assert_eq!(
+            instructions,
+            [
+                (1, Instruction::LoadByte { x: 0, nn: 0x2A }),
+                (3, Instruction::Unknown(0x8008)),
+            ]
+        );
+    }
+}
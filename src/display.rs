@@ -0,0 +1,33 @@
+//! Rendering backends for the CHIP-8 screen.
+//!
+//! The run loop hands the screen buffer to a `Display` without knowing how it is presented, e.g. in the terminal or
+//! recorded to a file.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+pub trait Display {
+    /// Render the screen buffer.
+    ///
+    /// # Arguments
+    /// * `screen` - The screen buffer, `WIDTH` pixels wide and `HEIGHT` (or `HIRES_HEIGHT`) pixels high, left to right
+    ///   from the upper-left corner.
+    ///
+    /// # Return
+    /// * `Ok(())` if the screen was rendered.
+    /// * `Err` if there was an error during rendering.
+    fn render(&mut self, screen: &[bool]) -> Result<(), Box<dyn core::error::Error>>;
+
+    /// Render both XO-CHIP display planes. By default, pixels that are on in either plane are rendered as on.
+    ///
+    /// # Arguments
+    /// * `pixels` - The composited planes, laid out like the screen buffer, see `State::visible_planes`.
+    ///
+    /// # Return
+    /// * `Ok(())` if the screen was rendered.
+    /// * `Err` if there was an error during rendering.
+    fn render_planes(&mut self, pixels: &[u8]) -> Result<(), Box<dyn core::error::Error>> {
+        let screen: Vec<bool> = pixels.iter().map(|&pixel| pixel != 0).collect();
+        self.render(&screen)
+    }
+}
@@ -0,0 +1,107 @@
+//! Dumps of the machine state as JSON, for post-mortem debugging of a program that halted or failed.
+//!
+//! The dump holds the registers, the stack, the timers and the cycle count, the visible screen with a string of hex
+//! digits per row (see `screen::screen_bytes`), and the memory outside the fonts that isn't zeroed, as runs of hex
+//! bytes. Only available with the `json` feature.
+
+use crate::constants::WIDTH;
+use crate::screen;
+use crate::state::State;
+use serde_json::{Value, json};
+use std::fs;
+use std::path::Path;
+
+/// Render the state as pretty-printed JSON.
+///
+/// # Arguments
+/// * `state` - The state to dump.
+pub fn state_to_json(state: &State) -> String {
+    let hex = |bytes: &[u8]| -> String { bytes.iter().map(|b| format!("{b:02X}")).collect() };
+
+    let v: Vec<String> = state.v.iter().map(|v| format!("{v:02X}")).collect();
+    let stack: Vec<String> = state.stack.iter().map(|pc| format!("{pc:03X}")).collect();
+    let screen: Vec<String> = screen::screen_bytes(state.visible_screen())
+        .chunks(WIDTH / 8)
+        .map(hex)
+        .collect();
+    let memory: Vec<Value> = memory_regions(&state.memory, state.fonts().end)
+        .into_iter()
+        .map(|(address, bytes)| json!({ "address": format!("{address:03X}"), "bytes": hex(bytes) }))
+        .collect();
+
+    let dump = json!({
+        "pc": format!("{:03X}", state.pc),
+        "i": format!("{:03X}", state.i),
+        "v": v,
+        "stack": stack,
+        "delay_timer": state.delay_timer,
+        "sound_timer": state.sound_timer,
+        "cycles": state.cycles,
+        "hires": state.hires,
+        "screen": screen,
+        "memory": memory,
+    });
+    serde_json::to_string_pretty(&dump).expect("A JSON value always serializes")
+}
+
+/// Write the state as JSON to a file, see `state_to_json`.
+///
+/// # Arguments
+/// * `state` - The state to dump.
+/// * `path` - The file to write, replaced if it exists.
+pub fn dump_state(state: &State, path: &Path) -> std::io::Result<()> {
+    fs::write(path, state_to_json(state))
+}
+
+/// The runs of non-zero bytes in memory from `start`, after the fonts, with their start addresses.
+fn memory_regions(memory: &[u8], start: usize) -> Vec<(usize, &[u8])> {
+    let mut regions = Vec::new();
+    let mut address = start;
+
+    while address < memory.len() {
+        if memory[address] == 0 {
+            address += 1;
+            continue;
+        }
+        let len = memory[address..]
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(memory.len() - address);
+        regions.push((address, &memory[address..address + len]));
+        address += len;
+    }
+
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_registers_and_program() {
+        let mut state = State::with_seed(0);
+        state
+            .load_rom(&[0x60, 0x2A, 0x12, 0x02])
+            .expect("Failed to load ROM");
+        state.v[0] = 0x2A;
+        state.stack.push_back(0x204);
+        state.memory[0x300] = 0xFF;
+
+        let dump: Value =
+            serde_json::from_str(&state_to_json(&state)).expect("Failed to parse the dump");
+
+        assert_eq!(dump["pc"], "200");
+        assert_eq!(dump["v"][0], "2A");
+        assert_eq!(dump["stack"], json!(["204"]));
+        assert_eq!(dump["screen"].as_array().map(Vec::len), Some(32));
+        assert_eq!(dump["screen"][0], "0000000000000000");
+        assert_eq!(
+            dump["memory"],
+            json!([
+                { "address": "200", "bytes": "602A1202" },
+                { "address": "300", "bytes": "FF" },
+            ])
+        );
+    }
+}
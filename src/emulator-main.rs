@@ -1,14 +1,219 @@
-use chip8_rs::run_rom;
-use clap::Parser;
+use chip8_rs::assembler::assemble;
+use chip8_rs::constants::{
+    CLOCK_FREQ, ENTRY_POINT, KEY_PRESS_TIMEOUT_MS, MAX_CLOCK_FREQ, RENDER_FPS, REWIND_DEPTH,
+    STACK_DEPTH, TURBO_FACTOR,
+};
+use chip8_rs::disassembler::{disassemble, hexdump};
+use chip8_rs::input::InputSampling;
+use chip8_rs::keymap::KeyMap;
+use chip8_rs::state::{PcOverflow, State, read_rom};
+use chip8_rs::term::{RenderMode, Scale, Theme, parse_color};
+use chip8_rs::validate::validate_rom;
+use chip8_rs::variant::{Variant, detect_variant, known_rom, rom_hash};
+use chip8_rs::{RunOptions, bench_rom, run_rom};
+use clap::{Parser, Subcommand};
+use crossterm::style::Color;
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[allow(unused_imports)]
 use log::*;
 
 #[derive(Parser, Debug)]
 #[command(version, about="A CHIP-8 emulator.", long_about = None, author)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    rom_path: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(required = true)]
+    rom_path: Option<PathBuf>,
+
+    /// Instructions per second, up to 1000000000, 0 runs as fast as possible
+    #[arg(long, default_value_t = CLOCK_FREQ, value_parser = clap::value_parser!(u32).range(0..=MAX_CLOCK_FREQ as i64))]
+    speed: u32,
+
+    /// Instructions per 60Hz frame, run in a burst at the start of each frame, instead of a steady --speed
+    #[arg(long, value_name = "N", conflicts_with = "speed", value_parser = clap::value_parser!(u32).range(1..))]
+    ipf: Option<u32>,
+
+    /// Time each instruction by its machine cycles on the COSMAC VIP, instead of a fixed --speed or --ipf
+    #[arg(long, conflicts_with_all = ["speed", "ipf"])]
+    cycle_accurate: bool,
+
+    /// Seed for the random number generator, to reproduce a run
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Address to load the ROM at and start from, e.g. 0x600 for ETI-660 programs
+    #[arg(long, default_value_t = ENTRY_POINT, value_parser = parse_address)]
+    entry: usize,
+
+    /// Copy a file into memory at an address after loading the ROM, e.g. 0x800=sprites.bin. Can be repeated
+    #[arg(long, value_name = "ADDR=FILE", value_parser = parse_load)]
+    load: Vec<(usize, PathBuf)>,
+
+    /// Fail if a --load would overwrite the fonts
+    #[arg(long)]
+    protect_fonts: bool,
+
+    /// Halt when the program runs into memory the ROM didn't fill, rather than executing zeroes
+    #[arg(long)]
+    trap_uninit: bool,
+
+    /// Strip and validate a 2-byte length or CRC trailer at the end of the ROM
+    #[arg(long)]
+    trailer: bool,
+
+    /// Don't keep the RPL user flags (e.g. high scores) in a `.sav` file next to the ROM
+    #[arg(long)]
+    no_save: bool,
+
+    /// When to poll input: once per "frame", or "continuous"ly before every instruction
+    #[arg(long, default_value = "frame")]
+    input_sampling: InputSampling,
+
+    /// Milliseconds a key stays pressed after the terminal reported it, on terminals that don't report key releases
+    #[arg(long, value_name = "MS", default_value_t = KEY_PRESS_TIMEOUT_MS.as_millis() as u64)]
+    key_timeout: u64,
+
+    /// Halt on the first unknown opcode, rather than ignoring it with a warning
+    #[arg(long, visible_alias = "halt-on-unknown")]
+    strict: bool,
+
+    /// Color theme: classic, amber, green or blue
+    #[arg(long, default_value = "green")]
+    theme: Theme,
+
+    /// Size of the pixels, as a multiple of the size at scale 1, or "auto" for the largest that fits the terminal
+    #[arg(long, default_value = "1")]
+    scale: Scale,
+
+    /// How pixels are drawn: half (two per character), full (two characters per pixel) or ascii
+    #[arg(long, default_value = "half")]
+    render_mode: RenderMode,
+
+    /// Terminal redraws per second, regardless of the speed
+    #[arg(long, default_value_t = RENDER_FPS, value_parser = clap::value_parser!(u32).range(1..))]
+    fps: u32,
+
+    /// Color of pixels that are on, e.g. "white" or "#FFB000", overriding the theme
+    #[arg(long, value_parser = parse_color)]
+    fg: Option<Color>,
+
+    /// Color of pixels that are off, overriding the theme
+    #[arg(long, value_parser = parse_color)]
+    bg: Option<Color>,
+
+    /// Load the key mapping from a file with `key=hexdigit` lines, instead of using 1234/QWER/ASDF/ZXCV
+    #[arg(long)]
+    keymap: Option<PathBuf>,
+
+    /// Record the keypad input and the seed to this file (.rpl), to reproduce the run with --replay
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// Play back the keypad input recorded with --record, with the recorded seed and speed
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["record", "seed"])]
+    replay: Option<PathBuf>,
+
+    /// Write every executed instruction, with the registers, to this file
+    #[arg(long)]
+    trace: Option<PathBuf>,
+
+    /// Dump the registers, stack, screen and non-zero memory to this file as JSON when the program halts or fails
+    #[arg(long, value_name = "FILE")]
+    dump_on_exit: Option<PathBuf>,
+
+    /// Stop tracing after this many instructions
+    #[arg(long, requires = "trace")]
+    trace_limit: Option<u64>,
+
+    /// Print statistics of the run on exit
+    #[arg(long)]
+    stats: bool,
+
+    /// Print which instruction forms were executed, and which never were, on exit
+    #[arg(long)]
+    coverage: bool,
+
+    /// Start with the HUD, showing the ROM name, the speed and whether the sound is on. H toggles it
+    #[arg(long)]
+    hud: bool,
+
+    /// Start paused, and execute one instruction each time N is pressed. Space resumes
+    #[arg(long)]
+    step: bool,
+
+    /// Show a debugger panel below the display, with the registers, the stack, the timers and the next instruction.
+    /// Space pauses and continues, N single-steps while paused
+    #[arg(long)]
+    debug: bool,
+
+    /// Exit when the program jumps to itself, rather than idling
+    #[arg(long)]
+    halt_on_infinite_loop: bool,
+
+    /// When the program runs past the end of memory: "wrap" around to 0, "halt", or exit with an "error"
+    #[arg(long, default_value = "wrap")]
+    pc_overflow: PcOverflow,
+
+    /// Restart the program when it halts, rather than exiting
+    #[arg(long)]
+    reset_on_halt: bool,
+
+    /// Limit on nested subroutine calls, 0 for unlimited
+    #[arg(long, default_value_t = STACK_DEPTH)]
+    max_stack_depth: usize,
+
+    /// Keep the call stack in memory at 0xEA0 like the COSMAC VIP, where programs can inspect it
+    #[arg(long)]
+    memory_stack: bool,
+
+    /// Quirks of the interpreter to emulate: chip8, schip or xochip. Detected from the ROM by default
+    #[arg(long)]
+    quirks: Option<Variant>,
+
+    /// Without --quirks, use the quirks a ROM is known to need if its hash is in the built-in table
+    #[arg(long)]
+    auto_quirks: bool,
+
+    /// Run this many times more instructions per frame while Tab is held, on terminals that report key releases
+    #[arg(long, value_name = "N", default_value_t = TURBO_FACTOR, value_parser = clap::value_parser!(u32).range(1..))]
+    turbo: u32,
+
+    /// Number of frames kept for rewinding a second at a time with B, 0 to disable rewinding
+    #[arg(long, default_value_t = REWIND_DEPTH)]
+    rewind_depth: usize,
+
+    /// Scan the ROM for instructions that won't run as expected, print them, and exit
+    #[arg(long)]
+    check: bool,
+
+    /// Run this many cycles as fast as possible without the terminal, and print the achieved speed
+    #[arg(long, value_name = "CYCLES", num_args = 0..=1, default_missing_value = "10000000")]
+    bench: Option<usize>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Assemble a source file into a ROM
+    Assemble {
+        source: PathBuf,
+
+        /// Where to write the ROM, by default next to the source with a `.ch8` extension
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Print the size and detected variant of a ROM, with a hex dump of the loaded program
+    Info {
+        rom_path: PathBuf,
+
+        /// Also disassemble the program
+        #[arg(long)]
+        asm: bool,
+    },
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -16,13 +221,145 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let args = Args::parse();
 
+    match args.command {
+        Some(Command::Assemble { source, output }) => {
+            let rom = assemble(&std::fs::read_to_string(&source)?)
+                .map_err(|e| format!("{}: {}", source.display(), e))?;
+            let output = output.unwrap_or_else(|| source.with_extension("ch8"));
+            std::fs::write(&output, &rom)?;
+            info!("Wrote {} bytes to {}", rom.len(), output.display());
+            return Ok(());
+        }
+        Some(Command::Info { rom_path, asm }) => {
+            let rom = read_rom(&rom_path)
+                .map_err(|e| format!("Failed to read ROM '{}': {}", rom_path.display(), e))?;
+            let state = State::try_from(rom.as_slice())?;
+            let program = &state.memory[0x200..0x200 + rom.len()];
+
+            println!("ROM: {}", rom_path.display());
+            println!(
+                "Size: {} bytes, loaded at 0x200-0x{:03X}",
+                rom.len(),
+                0x200 + rom.len().max(1) - 1
+            );
+            println!("Variant: {}", detect_variant(&rom));
+            println!("Hash: {:016X}", rom_hash(&rom));
+            if let Some(known) = known_rom(&rom) {
+                println!("Known ROM: {}", known.name);
+            }
+            println!("Entry point: 0x{:03X}", state.pc);
+            println!();
+            for line in hexdump(program, 0x200) {
+                println!("{line}");
+            }
+            if asm {
+                println!();
+                for (address, opcode, mnemonic) in disassemble(program, 0x200) {
+                    println!("{address:03X}: {opcode:04X}  {mnemonic}");
+                }
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
     let rom_path = args
         .rom_path
+        .expect("ROM path is required without a subcommand");
+    let rom_path = rom_path
         .canonicalize()
-        .map_err(|e| format!("ROM not found '{}': {}", args.rom_path.display(), e))?;
+        .map_err(|e| format!("ROM not found '{}': {}", rom_path.display(), e))?;
+
+    if args.check {
+        let rom = read_rom(&rom_path)?;
+        let variant = args.quirks.unwrap_or_else(|| detect_variant(&rom));
+        let diagnostics = validate_rom(&rom, &variant.quirks());
+        for diagnostic in &diagnostics {
+            println!("{diagnostic}");
+        }
+        if diagnostics.is_empty() {
+            println!("No problems found with the {variant} quirks");
+            return Ok(());
+        }
+        println!(
+            "{} problems found with the {variant} quirks, some may be data between the instructions",
+            diagnostics.len()
+        );
+        std::process::exit(1);
+    }
 
-    let exit_code = run_rom(rom_path)?;
+    if let Some(cycles) = args.bench {
+        let (stats, wall_time) = bench_rom(&read_rom(&rom_path)?, cycles)?;
+        println!("{}", stats.summary(wall_time));
+        return Ok(());
+    }
+
+    let keymap = match &args.keymap {
+        Some(path) => KeyMap::load(path)?,
+        None => KeyMap::default(),
+    };
+
+    let options = RunOptions {
+        speed: args.speed,
+        instructions_per_frame: args.ipf,
+        cycle_accurate: args.cycle_accurate,
+        seed: args.seed,
+        entry: args.entry,
+        loads: args.load,
+        protect_fonts: args.protect_fonts,
+        trap_uninit: args.trap_uninit,
+        rom_trailer: args.trailer,
+        persist_flags: !args.no_save,
+        input_sampling: args.input_sampling,
+        key_timeout: Duration::from_millis(args.key_timeout),
+        strict: args.strict,
+        theme: args.theme,
+        scale: args.scale,
+        render_mode: args.render_mode,
+        fps: args.fps,
+        foreground: args.fg,
+        background: args.bg,
+        keymap,
+        record: args.record,
+        replay: args.replay,
+        trace: args.trace,
+        dump_on_exit: args.dump_on_exit,
+        trace_limit: args.trace_limit,
+        stats: args.stats,
+        coverage: args.coverage,
+        hud: args.hud,
+        step: args.step,
+        debug: args.debug,
+        halt_on_infinite_loop: args.halt_on_infinite_loop,
+        pc_overflow: args.pc_overflow,
+        max_stack_depth: (args.max_stack_depth > 0).then_some(args.max_stack_depth),
+        memory_stack: args.memory_stack,
+        reset_on_halt: args.reset_on_halt,
+        turbo: args.turbo,
+        rewind_depth: args.rewind_depth,
+        variant: args.quirks,
+        auto_quirks: args.auto_quirks,
+    };
+
+    let exit_code = run_rom(rom_path, &options)?;
     info!("Program exited with code {}", exit_code);
 
     Ok(())
 }
+
+/// Parse an address, in hex with a `0x` prefix, or in decimal.
+fn parse_address(s: &str) -> Result<usize, String> {
+    let address = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    address.map_err(|_| format!("Invalid address '{s}', expected e.g. 0x200"))
+}
+
+/// Parse a `--load`, an address and a file separated by `=`.
+fn parse_load(s: &str) -> Result<(usize, PathBuf), String> {
+    let (address, path) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid load '{s}', expected e.g. 0x800=sprites.bin"))?;
+    Ok((parse_address(address)?, PathBuf::from(path)))
+}
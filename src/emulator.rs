@@ -0,0 +1,976 @@
+//! The CHIP-8 emulator, which drives a `State` with a clock.
+//!
+//! Instructions are executed one cycle at a time with `Emulator::step`. Every 1/60s, a vertical blank (`vblank`)
+//! decrements the timers and ends any pending display wait (see `Quirks::display_wait`).
+//!
+//! Normally the frontend calls `vblank` at 60Hz according to the wall clock, or calls `step_frame` once per frame to
+//! run a frame's worth of cycles followed by the vertical blank. In turbo mode the emulator runs
+//! unthrottled, so instead frame boundaries are simulated by counting cycles: every `cycles_per_second / TIMER_FREQ`
+//! cycles is one frame. This keeps the display wait and the timers correct relative to the instructions, without any
+//! sleeping.
+//!
+//! An optional display hook is handed the screen after each instruction that changed it, see `set_display_hook`, so
+//! a frontend can redraw only when needed.
+//!
+//! An optional trace hook observes every instruction right before it executes, see `set_trace_hook`. With the log at
+//! the trace level, each executed instruction is also logged with the registers it changed. Counters of the run are
+//! accumulated in `stats`.
+//!
+//! In the cycle accurate mode (see `cycle_accurate`), each instruction advances the clock by the machine cycles it took on
+//! the COSMAC VIP, rather than by a single cycle, and the clock runs at the VIP's `VIP_CYCLE_FREQ`.
+//!
+//! While paused (see `pause`), no instructions execute and the timers don't run.
+//!
+//! Programs often end by jumping to themselves. The emulator notices such self-jumps (see `is_idle`), so the frontend
+//! can idle instead of spinning at full clock, or halts on them if `halt_on_infinite_loop` is set.
+//!
+//! For debugging, `run_until_break` runs until the program counter reaches a breakpoint (see `add_breakpoint`), or a
+//! watched memory address or register changes (see `watch_memory` and `watch_register`).
+//!
+//! Each vertical blank can snapshot the state into a history of the last `rewind_depth` frames, so a debugger can step
+//! back in time with `rewind`. Memory can be inspected and patched while a program runs with `read_memory`,
+//! `memory_slice` and `write_memory`.
+//!
+//! Input is polled with `step_with_input`, either once at the start of each frame, or before every instruction,
+//! depending on `input_sampling`. The polled input can be recorded to a file, and played back with the same seed to
+//! reproduce a run frame for frame, see `start_recording` and `play_recording`.
+//!
+//! `EmulatorBuilder` gathers the configuration of an emulator in one place, e.g.
+//! `Emulator::builder().quirks(Quirks::schip()).seed(42).build()`.
+
+use crate::constants;
+use crate::decoder;
+use crate::error::Chip8Error;
+use crate::input::{Input, InputSampling};
+use crate::instruction;
+use crate::quirks::Quirks;
+#[cfg(feature = "std")]
+use crate::replay::{Playback, Recorder};
+#[cfg(feature = "std")]
+use crate::rng::Rng;
+use crate::state::{PcOverflow, State};
+use crate::stats::{Coverage, RunStats};
+use crate::trace;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::time::Duration;
+use log::*;
+
+/// Callback invoked before each instruction with the program counter, the opcode, and the state.
+pub type TraceHook = Box<dyn FnMut(usize, u16, &State)>;
+
+/// Callback invoked with the visible screen after an instruction changed it.
+pub type DisplayHook = Box<dyn FnMut(&[bool])>;
+
+/// A location watched for changes, see `Emulator::watch_memory` and `Emulator::watch_register`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    /// The byte at an address in memory.
+    Memory(usize),
+
+    /// A register, V0 to VF.
+    Register(u8),
+}
+
+/// What happened during a frame run with `Emulator::step_frame`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameResult {
+    /// Whether an instruction changed the screen, so it should be redrawn.
+    pub screen_changed: bool,
+
+    /// Whether the sound should play, see `Emulator::is_beeping`.
+    pub beeping: bool,
+
+    /// The exit code, if the program halted during the frame.
+    pub exit_code: Option<usize>,
+}
+
+/// Why `Emulator::run_until_break` stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakReason {
+    /// The program counter reached a breakpoint. The instruction there hasn't executed yet.
+    Breakpoint(usize),
+
+    /// The instruction at `pc` changed a watched location from `old` to `new`.
+    Watchpoint {
+        watch: Watch,
+        pc: usize,
+        old: u8,
+        new: u8,
+    },
+
+    /// The program halted with an exit code.
+    Halted(usize),
+
+    /// The cycle budget ran out.
+    CycleLimit,
+}
+
+pub struct Emulator {
+    pub state: State,
+
+    /// Instructions per second. The timers run at 60Hz regardless.
+    pub cycles_per_second: u32,
+
+    /// Run unthrottled, with frame boundaries counted in cycles rather than following the wall clock.
+    pub turbo: bool,
+
+    /// Advance the clock by the cost of each instruction in machine cycles (see `Instruction::cycles`), rather than by
+    /// one cycle. `cycles_per_second` should then be the machine cycle rate, `VIP_CYCLE_FREQ`. Waiting for a key press
+    /// or a vertical blank still costs a cycle at a time.
+    pub cycle_accurate: bool,
+
+    /// When input is polled.
+    pub input_sampling: InputSampling,
+
+    /// Halt, with exit code 0, once the program has jumped to itself `infinite_loop_limit` times in a row.
+    pub halt_on_infinite_loop: bool,
+
+    /// Number of consecutive self-jumps before halting, if `halt_on_infinite_loop` is set.
+    pub infinite_loop_limit: u32,
+
+    /// Number of consecutive self-jumps so far.
+    self_jumps: u32,
+
+    /// Counters of the run so far.
+    pub stats: RunStats,
+
+    /// Executions of each instruction form, if set. Off by default, as it is slower than the counters in `stats`.
+    pub coverage: Option<Coverage>,
+
+    /// Whether execution is suspended.
+    paused: bool,
+
+    /// Whether input should be polled before the next cycle, as it is the first of a frame.
+    poll_due: bool,
+
+    /// Number of frames kept in the history for `rewind`, 0 to disable it. Each frame costs a snapshot of the state,
+    /// see `REWIND_DEPTH`.
+    pub rewind_depth: usize,
+
+    /// Snapshots of the state at the last `rewind_depth` vertical blanks, oldest first.
+    history: VecDeque<State>,
+
+    /// Callback invoked before each instruction, if set.
+    trace_hook: Option<TraceHook>,
+
+    /// Callback invoked when the screen changed, if set.
+    display_hook: Option<DisplayHook>,
+
+    /// Addresses where `run_until_break` stops.
+    breakpoints: Vec<usize>,
+
+    /// Watched locations, with their values before the current cycle of `run_until_break`.
+    watchpoints: Vec<(Watch, u8)>,
+
+    /// Whether an instruction changed the screen since the start of the frame run by `step_frame`.
+    screen_changed: bool,
+
+    /// Recording of the polled input, if started.
+    #[cfg(feature = "std")]
+    recorder: Option<Recorder>,
+
+    /// Recording played back instead of the polled input, if started.
+    #[cfg(feature = "std")]
+    playback: Option<Playback>,
+}
+
+impl Emulator {
+    /// A builder for an emulator, with the defaults of plain CHIP-8.
+    pub fn builder() -> EmulatorBuilder {
+        EmulatorBuilder::new()
+    }
+
+    pub fn new(state: State) -> Self {
+        Self {
+            state,
+            cycles_per_second: constants::CLOCK_FREQ,
+            turbo: false,
+            cycle_accurate: false,
+            input_sampling: InputSampling::default(),
+            halt_on_infinite_loop: false,
+            infinite_loop_limit: 1,
+            self_jumps: 0,
+            stats: RunStats::default(),
+            coverage: None,
+            paused: false,
+            poll_due: true,
+            rewind_depth: 0,
+            history: VecDeque::new(),
+            trace_hook: None,
+            display_hook: None,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            screen_changed: false,
+            #[cfg(feature = "std")]
+            recorder: None,
+            #[cfg(feature = "std")]
+            playback: None,
+        }
+    }
+
+    /// Set a callback that is invoked right before each instruction executes, with the program counter, the raw
+    /// opcode, and the state. This allows building coverage maps, histograms, or execution logs.
+    ///
+    /// The hook only observes the state: mutating it from inside the hook (e.g. through interior mutability) is not
+    /// supported. Cycles where no instruction executes, e.g. while waiting for a key press, are not traced.
+    ///
+    /// # Arguments
+    /// * `hook` - The callback, replacing any previous one.
+    pub fn set_trace_hook(&mut self, hook: TraceHook) {
+        self.trace_hook = Some(hook);
+    }
+
+    /// Remove the trace hook, if any.
+    pub fn clear_trace_hook(&mut self) {
+        self.trace_hook = None;
+    }
+
+    /// Set a callback that is invoked with the visible screen (the first plane, see `State::visible_screen`) after each
+    /// instruction that changed it, i.e. a `DXYN` that flipped a pixel, or a clear. The callback isn't invoked for
+    /// frames where nothing was drawn.
+    ///
+    /// # Arguments
+    /// * `hook` - The callback, replacing any previous one.
+    pub fn set_display_hook(&mut self, hook: DisplayHook) {
+        self.display_hook = Some(hook);
+    }
+
+    /// Remove the display hook, if any.
+    pub fn clear_display_hook(&mut self) {
+        self.display_hook = None;
+    }
+
+    /// Stop `run_until_break` when the program counter reaches an address, before the instruction there executes.
+    ///
+    /// # Arguments
+    /// * `address` - The address of the instruction.
+    pub fn add_breakpoint(&mut self, address: usize) {
+        if !self.breakpoints.contains(&address) {
+            self.breakpoints.push(address);
+        }
+    }
+
+    /// Stop `run_until_break` right after an instruction changes the byte at an address.
+    ///
+    /// # Arguments
+    /// * `address` - The address in memory, masked to the memory size.
+    pub fn watch_memory(&mut self, address: usize) {
+        self.watch(Watch::Memory(address & self.state.address_mask));
+    }
+
+    /// Stop `run_until_break` right after an instruction changes a register.
+    ///
+    /// # Arguments
+    /// * `x` - The register, 0x0 to 0xF.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the register is watched.
+    /// * `Err(Chip8Error::InvalidRegister)` if there is no such register.
+    pub fn watch_register(&mut self, x: u8) -> Result<(), Chip8Error> {
+        if x > 0xF {
+            return Err(Chip8Error::InvalidRegister(x));
+        }
+        self.watch(Watch::Register(x));
+        Ok(())
+    }
+
+    /// Remove all breakpoints and watchpoints.
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+    }
+
+    fn watch(&mut self, watch: Watch) {
+        if !self
+            .watchpoints
+            .iter()
+            .any(|&(watched, _)| watched == watch)
+        {
+            let value = self.watched_value(watch);
+            self.watchpoints.push((watch, value));
+        }
+    }
+
+    fn watched_value(&self, watch: Watch) -> u8 {
+        match watch {
+            Watch::Memory(address) => self.state.memory[address],
+            Watch::Register(x) => self.state.v[x as usize],
+        }
+    }
+
+    /// Execute cycles with `step` until a breakpoint or watchpoint is hit, the program halts, or the cycle budget runs
+    /// out. Breakpoints only count once an instruction has executed, so it can resume from a breakpoint, even while
+    /// waiting for a key press or a vertical blank there. Unless `counts_frames`, vertical blanks are signaled at frame
+    /// boundaries counted in cycles, since the frontend can't signal them during the run.
+    ///
+    /// The watched values are compared after every cycle, but only if there are any watchpoints, so running without
+    /// them costs no more than `step`.
+    ///
+    /// # Arguments
+    /// * `max_cycles` - The maximum number of cycles to execute.
+    ///
+    /// # Returns
+    /// * `Ok(reason)` for why execution stopped.
+    /// * `Err` if an instruction failed.
+    pub fn run_until_break(
+        &mut self,
+        max_cycles: u64,
+    ) -> Result<BreakReason, Box<dyn core::error::Error>> {
+        // Changes made between runs, e.g. by `step` or a debugger, don't count
+        for index in 0..self.watchpoints.len() {
+            self.watchpoints[index].1 = self.watched_value(self.watchpoints[index].0);
+        }
+
+        let mut executed = false;
+        for _ in 0..max_cycles {
+            let pc = self.state.pc;
+            if executed && !self.breakpoints.is_empty() && self.breakpoints.contains(&pc) {
+                return Ok(BreakReason::Breakpoint(pc));
+            }
+
+            let (instructions, cycles) = (self.stats.instructions, self.state.cycles);
+            let until_tick = self.state.cycles_until_timer_tick(self.cycles_per_second) as u64;
+            if let Some(exit_code) = self.step()? {
+                return Ok(BreakReason::Halted(exit_code));
+            }
+            executed |= self.stats.instructions != instructions;
+            if !self.counts_frames() && self.state.cycles - cycles >= until_tick {
+                self.vblank();
+            }
+
+            if !self.watchpoints.is_empty() {
+                for index in 0..self.watchpoints.len() {
+                    let (watch, old) = self.watchpoints[index];
+                    let new = self.watched_value(watch);
+                    if new != old {
+                        self.watchpoints[index].1 = new;
+                        return Ok(BreakReason::Watchpoint {
+                            watch,
+                            pc,
+                            old,
+                            new,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(BreakReason::CycleLimit)
+    }
+
+    /// Suspend execution: `step` and `vblank` do nothing until `resume` is called.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resume execution after `pause`.
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Whether execution is suspended.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// The address and opcode of the next instruction to execute.
+    pub fn next_instruction(&self) -> (usize, u16) {
+        (self.state.pc, decoder::fetch(&self.state))
+    }
+
+    /// Execute a single cycle like `step`, even while paused, e.g. to single-step through a program in a debugger. A
+    /// pending display wait ends first, as if the rest of the frame passed.
+    ///
+    /// # Returns
+    /// * `Ok(Some(exit_code))` if the program halted.
+    /// * `Ok(None)` if execution should continue.
+    /// * `Err` if the instruction failed.
+    pub fn single_step(&mut self) -> Result<Option<usize>, Box<dyn core::error::Error>> {
+        let paused = self.paused;
+        self.paused = false;
+
+        if self.state.waiting_for_vblank {
+            self.vblank();
+        }
+        let result = self.step();
+
+        self.paused = paused;
+        result
+    }
+
+    /// Whether the program is stuck in a jump to itself, which nothing but a reset can break.
+    pub fn is_idle(&self) -> bool {
+        self.self_jumps > 0
+    }
+
+    /// Number of cycles counted in `stats`, including those spent waiting for a key press or a vertical blank.
+    pub fn cycles(&self) -> u64 {
+        self.stats.cycles
+    }
+
+    /// Number of instructions counted in `stats` per opcode class, indexed by the top nibble of the opcode, e.g.
+    /// `opcode_stats()[0xD]` counts the sprites drawn.
+    pub fn opcode_stats(&self) -> [u64; 16] {
+        self.stats.opcode_counts
+    }
+
+    /// Reset the counters in `stats`, e.g. to measure a single frame. The emulated time (see `elapsed`) is unaffected.
+    pub fn reset_stats(&mut self) {
+        self.stats = RunStats::default();
+    }
+
+    /// Emulated time since the program started, from the cycle count and the clock speed. Unlike the wall clock, it is
+    /// available on every platform, and stands still while paused.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64(self.state.cycles as f64 / self.cycles_per_second.max(1) as f64)
+    }
+
+    /// The delay timer, as read by `FX07`.
+    pub fn delay_timer(&self) -> u8 {
+        self.state.delay_timer
+    }
+
+    /// Set the delay timer, like `FX15`.
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.state.delay_timer = value;
+    }
+
+    /// The sound timer.
+    pub fn sound_timer(&self) -> u8 {
+        self.state.sound_timer
+    }
+
+    /// Set the sound timer, like `FX18`.
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.state.sound_timer = value;
+    }
+
+    /// Read a byte of memory, e.g. for a memory view in a debugger.
+    ///
+    /// # Returns
+    /// The byte at `address`, or `None` if it's past the end of memory.
+    pub fn read_memory(&self, address: usize) -> Option<u8> {
+        self.state.memory.get(address).copied()
+    }
+
+    /// Write a byte of memory while a program runs, e.g. to poke an infinite-lives cheat.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the byte was written.
+    /// * `Err` if `address` is past the end of memory, or is in the fonts with `protect_fonts` set.
+    pub fn write_memory(&mut self, address: usize, value: u8) -> Result<(), Chip8Error> {
+        self.state.load_at(&[value], address)
+    }
+
+    /// A range of memory, e.g. for a hex dump.
+    ///
+    /// # Returns
+    /// The bytes in `range`, or `None` if it isn't within memory.
+    pub fn memory_slice(&self, range: Range<usize>) -> Option<&[u8]> {
+        self.state.memory.get(range)
+    }
+
+    /// Whether the sound should play: the sound timer is running, and execution isn't paused.
+    pub fn is_beeping(&self) -> bool {
+        !self.paused && self.state.sound_timer > 0
+    }
+
+    /// Press a key, e.g. from a test or a frontend that drives `step` itself. The key is held until `release_key`, and
+    /// is seen by `EX9E`/`EXA1` and `FX0A` like a key reported by an `Input` that reports releases.
+    ///
+    /// # Arguments
+    /// * `key` - The key, 0x0 to 0xF.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the key was pressed.
+    /// * `Err(Chip8Error::InvalidKey)` if there is no such key.
+    pub fn press_key(&mut self, key: u8) -> Result<(), Chip8Error> {
+        let keys = self.state.keys | Self::key_bit(key)?;
+        self.state.poll_key(keys, true, self.elapsed());
+        Ok(())
+    }
+
+    /// Release a key pressed with `press_key`.
+    ///
+    /// # Arguments
+    /// * `key` - The key, 0x0 to 0xF.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the key was released.
+    /// * `Err(Chip8Error::InvalidKey)` if there is no such key.
+    pub fn release_key(&mut self, key: u8) -> Result<(), Chip8Error> {
+        let keys = self.state.keys & !Self::key_bit(key)?;
+        self.state.poll_key(keys, true, self.elapsed());
+        Ok(())
+    }
+
+    /// Whether a key is held, as seen by `EX9E`/`EXA1`. False for keys outside the keypad.
+    pub fn is_key_down(&self, key: u8) -> bool {
+        Self::key_bit(key).is_ok_and(|bit| self.state.keys & bit != 0)
+    }
+
+    /// The held keys, with bit `n` set while key `n` is down, e.g. to render the keypad.
+    pub fn keys_pressed(&self) -> u16 {
+        self.state.keys
+    }
+
+    /// Whether the program is blocked on `FX0A`, waiting for a key press, e.g. to show a prompt.
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.state.waiting_for_keypress.is_some()
+    }
+
+    /// The register `FX0A` will store the pressed key in, if the program is waiting for one.
+    pub fn pending_key_register(&self) -> Option<usize> {
+        self.state.waiting_for_keypress
+    }
+
+    /// The bit of a key in the key bitmask.
+    fn key_bit(key: u8) -> Result<u16, Chip8Error> {
+        if key as usize >= constants::KEY_COUNT {
+            return Err(Chip8Error::InvalidKey(key));
+        }
+        Ok(1 << key)
+    }
+
+    /// Execute a single cycle like `step`, but first poll the input if it's due according to `input_sampling`, or the
+    /// program is waiting for a key press.
+    ///
+    /// # Arguments
+    /// * `input` - The input to poll.
+    ///
+    /// # Returns
+    /// * `Ok(Some(exit_code))` if the program halted.
+    /// * `Ok(None)` if execution should continue.
+    /// * `Err` if polling or the instruction failed.
+    pub fn step_with_input(
+        &mut self,
+        input: &mut dyn Input,
+    ) -> Result<Option<usize>, Box<dyn core::error::Error>> {
+        // While waiting for a key press, poll every cycle, so a short press between the sampling points isn't missed
+        if self.poll_due
+            || self.input_sampling == InputSampling::Continuous
+            || self.state.waiting_for_keypress.is_some()
+        {
+            self.poll_input(input)?;
+            self.poll_due = false;
+        }
+
+        self.step()
+    }
+
+    /// Poll the input and hand the keys to the program, e.g. while paused, when `step_with_input` doesn't poll. The
+    /// keys are recorded if a recording was started, or replaced by the recorded ones when playing one back.
+    ///
+    /// # Arguments
+    /// * `input` - The input to poll.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the input was polled.
+    /// * `Err` if polling or writing the recording failed.
+    pub fn poll_input(&mut self, input: &mut dyn Input) -> Result<(), Box<dyn core::error::Error>> {
+        let keys = input.poll(self.state.cycles)?;
+        let now = self.elapsed();
+
+        #[cfg(feature = "std")]
+        if let Some(playback) = &mut self.playback {
+            let keys = playback.poll(self.state.cycles);
+            self.state.poll_key(keys, playback.reports_releases, now);
+            return Ok(());
+        }
+        #[cfg(feature = "std")]
+        if let Some(recorder) = &mut self.recorder {
+            let frame =
+                self.state.cycles / (self.cycles_per_second / constants::TIMER_FREQ).max(1) as u64;
+            recorder.record(frame, self.state.cycles, keys)?;
+        }
+
+        self.state.poll_key(keys, input.reports_releases(), now);
+        Ok(())
+    }
+
+    /// Start recording the polled input to a file, with the seed and the clock speed, see `replay`. Call it before
+    /// the program starts running, with the seed it will run with. Stops any playback.
+    ///
+    /// # Arguments
+    /// * `path` - The file to write, replaced if it exists.
+    /// * `reports_releases` - Whether the input that will be polled reports key releases.
+    ///
+    /// # Returns
+    /// * `Ok(())` if recording started.
+    /// * `Err` if the file couldn't be written.
+    #[cfg(feature = "std")]
+    pub fn start_recording(
+        &mut self,
+        path: &std::path::Path,
+        reports_releases: bool,
+    ) -> std::io::Result<()> {
+        self.recorder = Some(Recorder::create(
+            path,
+            self.state.seed,
+            self.cycles_per_second,
+            reports_releases,
+        )?);
+        self.playback = None;
+        Ok(())
+    }
+
+    /// Play back a recording made with `start_recording`: the recorded keys replace the polled ones, and the random
+    /// number generator and the clock speed are reset to the recorded ones. Call it before the program starts
+    /// running. Stops any recording.
+    ///
+    /// # Arguments
+    /// * `path` - The recording to play.
+    ///
+    /// # Returns
+    /// * `Ok(())` if playback started.
+    /// * `Err` if the recording couldn't be read or is invalid.
+    #[cfg(feature = "std")]
+    pub fn play_recording(
+        &mut self,
+        path: &std::path::Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let playback = Playback::load(path)?;
+        self.state.seed = playback.seed;
+        self.state.rng = Rng::new(playback.seed);
+        self.cycles_per_second = playback.clock_hz;
+        self.playback = Some(playback);
+        self.recorder = None;
+        Ok(())
+    }
+
+    /// Whether a recording is being played back, and there are recorded polls left.
+    #[cfg(feature = "std")]
+    pub fn is_playing_back(&self) -> bool {
+        self.playback
+            .as_ref()
+            .is_some_and(|playback| !playback.is_finished())
+    }
+
+    /// Whether vertical blanks are signaled by `step` at frame boundaries counted in cycles, rather than by the
+    /// frontend following the wall clock: in turbo mode, and while recording or playing back, so the timers run the
+    /// same on every run.
+    pub fn counts_frames(&self) -> bool {
+        #[cfg(feature = "std")]
+        if self.recorder.is_some() || self.playback.is_some() {
+            return true;
+        }
+        self.turbo
+    }
+
+    /// Execute a single cycle. While waiting for a key press or for a vertical blank, no instruction is executed, but
+    /// the cycle is still counted. While paused, nothing happens.
+    ///
+    /// # Returns
+    /// * `Ok(Some(exit_code))` if the program halted.
+    /// * `Ok(None)` if execution should continue.
+    /// * `Err` if the instruction failed.
+    pub fn step(&mut self) -> Result<Option<usize>, Box<dyn core::error::Error>> {
+        if self.paused {
+            return Ok(None);
+        }
+
+        let (halted, cost) =
+            if self.state.waiting_for_keypress.is_none() && !self.state.waiting_for_vblank {
+                // Stopping at the end of memory executes nothing, so nothing is traced or counted
+                if let Some(exit_code) = decoder::check_pc_overflow(&self.state)? {
+                    return Ok(Some(exit_code));
+                }
+
+                let pc = self.state.pc;
+                let opcode = decoder::fetch(&self.state);
+                if let Some(hook) = &mut self.trace_hook {
+                    hook(pc, opcode, &self.state);
+                }
+                let before = log_enabled!(Level::Trace).then_some((self.state.v, self.state.i));
+                let mut halted = decoder::decode_and_execute(&mut self.state)?;
+                if let Some((v, i)) = before {
+                    trace!(
+                        "{}",
+                        trace::format_transition(pc, opcode, &v, i, &self.state)
+                    );
+                }
+                self.stats.count_instruction(opcode, self.state.stack.len());
+                if let Some(coverage) = &mut self.coverage {
+                    coverage.record(opcode);
+                }
+                if self.state.screen_dirty {
+                    self.state.screen_dirty = false;
+                    self.screen_changed = true;
+                    if let Some(hook) = &mut self.display_hook {
+                        hook(self.state.visible_screen());
+                    }
+                }
+
+                // A 1NNN or BNNN jump to its own address
+                let is_jump = matches!(opcode & 0xF000, 0x1000 | 0xB000);
+                if is_jump && self.state.pc == pc {
+                    self.self_jumps = self.self_jumps.saturating_add(1);
+                    if self.halt_on_infinite_loop
+                        && self.self_jumps >= self.infinite_loop_limit
+                        && halted.is_none()
+                    {
+                        info!("Infinite loop at {pc:03X}, halting");
+                        halted = Some(0);
+                    }
+                } else {
+                    self.self_jumps = 0;
+                }
+
+                let cost = if self.cycle_accurate {
+                    instruction::decode(opcode).cycles()
+                } else {
+                    1
+                };
+                (halted, cost as u64)
+            } else {
+                (None, 1)
+            };
+
+        let ends_frame = cost >= self.state.cycles_until_timer_tick(self.cycles_per_second) as u64;
+        self.state.cycles += cost;
+        self.stats.cycles += cost;
+
+        if self.counts_frames() && ends_frame {
+            self.vblank();
+        }
+
+        Ok(halted)
+    }
+
+    /// Run one 60Hz frame: the cycles up to the next frame boundary (`cycles_per_second / TIMER_FREQ` of them, unless
+    /// the previous frame was cut short), followed by the vertical blank. A pending `FX0A` keeps waiting through the
+    /// frame, until a key is pressed with `press_key`. While paused, nothing happens.
+    ///
+    /// # Returns
+    /// * `Ok(result)` with whether the screen changed, whether to beep, and the exit code if the program halted. The
+    ///   vertical blank is skipped if it halted.
+    /// * `Err` if an instruction failed.
+    pub fn step_frame(&mut self) -> Result<FrameResult, Box<dyn core::error::Error>> {
+        if self.paused {
+            return Ok(FrameResult::default());
+        }
+
+        self.screen_changed = false;
+        let end =
+            self.state.cycles + self.state.cycles_until_timer_tick(self.cycles_per_second) as u64;
+        let mut exit_code = None;
+        while self.state.cycles < end {
+            exit_code = self.step()?;
+            if exit_code.is_some() {
+                break;
+            }
+        }
+        // When counting frames, the last cycle of the frame already signaled the vertical blank
+        if exit_code.is_none() && !self.counts_frames() {
+            self.vblank();
+        }
+
+        Ok(FrameResult {
+            screen_changed: self.screen_changed,
+            beeping: self.is_beeping(),
+            exit_code,
+        })
+    }
+
+    /// Signal a vertical blank: decrement the timers and end any pending display wait. Ignored while paused.
+    pub fn vblank(&mut self) {
+        if self.paused {
+            return;
+        }
+
+        self.state.tick_timers();
+        self.state.waiting_for_vblank = false;
+        self.poll_due = true;
+
+        if self.rewind_depth > 0 {
+            self.history.push_back(self.state.clone());
+        }
+        while self.history.len() > self.rewind_depth {
+            self.history.pop_front();
+        }
+    }
+
+    /// Go back in time, to the state at an earlier vertical blank. The history after it is discarded. The counters in
+    /// `stats` keep running.
+    ///
+    /// # Arguments
+    /// * `frames` - How many frames to go back. 1 returns to the start of the current frame.
+    ///
+    /// # Returns
+    /// The number of frames actually rewound, which is less than `frames` if the history is shorter.
+    pub fn rewind(&mut self, frames: usize) -> usize {
+        let frames = frames.min(self.history.len());
+        if frames == 0 {
+            return 0;
+        }
+
+        let target = self.history.len() - frames;
+        self.state = self.history[target].clone();
+        self.history.truncate(target);
+        self.self_jumps = 0;
+        self.poll_due = true;
+        frames
+    }
+
+    /// Restart the program without reloading it, see `State::reset`. The rewind history is discarded, while the
+    /// counters in `stats` keep running.
+    pub fn reset(&mut self) {
+        self.state.reset();
+        self.history.clear();
+        self.self_jumps = 0;
+        self.poll_due = true;
+    }
+}
+
+/// Configuration of an `Emulator`, set with chainable setters and turned into a ready-to-run emulator with `build`. The
+/// defaults match plain CHIP-8 on the COSMAC VIP.
+#[derive(Debug, Clone)]
+pub struct EmulatorBuilder {
+    quirks: Quirks,
+    clock_hz: u32,
+    turbo: bool,
+    cycle_accurate: bool,
+    seed: Option<u64>,
+    strict: bool,
+    max_stack_depth: Option<usize>,
+    memory_stack: bool,
+    key_timeout: Duration,
+    input_sampling: InputSampling,
+    halt_on_infinite_loop: bool,
+    pc_overflow: PcOverflow,
+    rewind_depth: usize,
+}
+
+impl EmulatorBuilder {
+    pub fn new() -> Self {
+        Self {
+            quirks: Quirks::chip8(),
+            clock_hz: constants::CLOCK_FREQ,
+            turbo: false,
+            cycle_accurate: false,
+            seed: None,
+            strict: false,
+            max_stack_depth: Some(constants::STACK_DEPTH),
+            memory_stack: false,
+            key_timeout: constants::KEY_PRESS_TIMEOUT_MS,
+            input_sampling: InputSampling::default(),
+            halt_on_infinite_loop: false,
+            pc_overflow: PcOverflow::default(),
+            rewind_depth: 0,
+        }
+    }
+
+    /// Interpreter behavior to emulate, see `State::quirks`.
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Instructions per second, see `Emulator::cycles_per_second`.
+    pub fn clock_hz(mut self, clock_hz: u32) -> Self {
+        self.clock_hz = clock_hz;
+        self
+    }
+
+    /// Run unthrottled, see `Emulator::turbo`.
+    pub fn turbo(mut self, turbo: bool) -> Self {
+        self.turbo = turbo;
+        self
+    }
+
+    /// Count the machine cycles of each instruction, see `Emulator::cycle_accurate`. Enabling it also sets the clock to
+    /// `VIP_CYCLE_FREQ`.
+    pub fn cycle_accurate(mut self, cycle_accurate: bool) -> Self {
+        self.cycle_accurate = cycle_accurate;
+        if cycle_accurate {
+            self.clock_hz = constants::VIP_CYCLE_FREQ;
+        }
+        self
+    }
+
+    /// Seed of the random number generator, to reproduce a run. Seeded from OS entropy if not set.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Fail on unknown opcodes, see `State::strict`.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Limit on nested subroutine calls, None for unlimited, see `State::max_stack_depth`.
+    pub fn max_stack_depth(mut self, max_stack_depth: Option<usize>) -> Self {
+        self.max_stack_depth = max_stack_depth;
+        self
+    }
+
+    /// Keep the call stack in memory at 0xEA0 like the COSMAC VIP, see `State::memory_stack`.
+    pub fn memory_stack(mut self, memory_stack: bool) -> Self {
+        self.memory_stack = memory_stack;
+        self
+    }
+
+    /// How long a key is held if the input doesn't report releases, see `State::key_timeout`.
+    pub fn key_timeout(mut self, key_timeout: Duration) -> Self {
+        self.key_timeout = key_timeout;
+        self
+    }
+
+    /// When input is polled, see `Emulator::input_sampling`.
+    pub fn input_sampling(mut self, input_sampling: InputSampling) -> Self {
+        self.input_sampling = input_sampling;
+        self
+    }
+
+    /// Halt when the program jumps to itself, see `Emulator::halt_on_infinite_loop`.
+    pub fn halt_on_infinite_loop(mut self, halt_on_infinite_loop: bool) -> Self {
+        self.halt_on_infinite_loop = halt_on_infinite_loop;
+        self
+    }
+
+    /// What happens when the program counter runs past the end of memory, see `State::pc_overflow`.
+    pub fn pc_overflow(mut self, pc_overflow: PcOverflow) -> Self {
+        self.pc_overflow = pc_overflow;
+        self
+    }
+
+    /// Number of frames kept for `Emulator::rewind`, 0 to disable it.
+    pub fn rewind_depth(mut self, rewind_depth: usize) -> Self {
+        self.rewind_depth = rewind_depth;
+        self
+    }
+
+    /// Create the emulator, with empty memory apart from the fonts. Load a program with `State::load_rom`.
+    pub fn build(self) -> Emulator {
+        let mut state = match self.seed {
+            Some(seed) => State::with_seed(seed),
+            None => State::new(),
+        };
+        state.quirks = self.quirks;
+        state.strict = self.strict;
+        state.max_stack_depth = self.max_stack_depth;
+        state.memory_stack = self.memory_stack;
+        state.key_timeout = self.key_timeout;
+        state.pc_overflow = self.pc_overflow;
+
+        let mut emulator = Emulator::new(state);
+        emulator.cycles_per_second = self.clock_hz;
+        emulator.turbo = self.turbo;
+        emulator.cycle_accurate = self.cycle_accurate;
+        emulator.input_sampling = self.input_sampling;
+        emulator.halt_on_infinite_loop = self.halt_on_infinite_loop;
+        emulator.rewind_depth = self.rewind_depth;
+        emulator
+    }
+}
+
+impl Default for EmulatorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
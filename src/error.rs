@@ -0,0 +1,101 @@
+//! Errors raised by the CHIP-8 interpreter.
+
+use alloc::string::String;
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chip8Error {
+    /// There is no quirk with the given name.
+    UnknownQuirk(String),
+
+    /// The opcode at address `pc` isn't a known instruction (strict mode only).
+    UnknownOpcode { opcode: u16, pc: usize },
+
+    /// `00EE` returned from a subroutine with an empty call stack.
+    StackUnderflow,
+
+    /// `2NNN` called a subroutine with the call stack at its maximum depth.
+    StackOverflow,
+
+    /// A font doesn't have the expected size in bytes.
+    InvalidFont { expected: usize, actual: usize },
+
+    /// A key outside the keypad, 0x0 to 0xF, was pressed or released.
+    InvalidKey(u8),
+
+    /// There is no register V`x`, only V0 to VF.
+    InvalidRegister(u8),
+
+    /// A ROM was loaded at an entry point in the memory reserved for the interpreter.
+    InvalidEntryPoint(usize),
+
+    /// A ROM of `size` bytes doesn't fit in memory at the entry point.
+    RomTooLarge { size: usize, entry: usize },
+
+    /// A ROM of `size` bytes is empty, or too small to hold a single instruction.
+    RomTooSmall(usize),
+
+    /// Loading `size` bytes at `address` would run past the end of memory.
+    LoadOutOfBounds { size: usize, address: usize },
+
+    /// Loading `size` bytes at `address` would overwrite the fonts, which are protected.
+    FontOverwrite { size: usize, address: usize },
+
+    /// `0NNN` called the machine code routine at `nnn`, which can't be run (strict mode only).
+    UnsupportedMachineCall(u16),
+
+    /// The program counter ran past the end of memory, to `pc`, with `PcOverflow::Error`.
+    PcOverflow(usize),
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::UnknownQuirk(name) => write!(f, "Unknown quirk: {name}"),
+            Chip8Error::UnknownOpcode { opcode, pc } => {
+                write!(f, "Unknown opcode: {opcode:04X} at {pc:03X}")
+            }
+            Chip8Error::StackUnderflow => write!(f, "Stack underflow on RET"),
+            Chip8Error::StackOverflow => write!(f, "Stack overflow on CALL"),
+            Chip8Error::InvalidFont { expected, actual } => {
+                write!(f, "Invalid font: {actual} bytes, expected {expected}")
+            }
+            Chip8Error::InvalidKey(key) => write!(f, "Invalid key: 0x{key:X}, expected 0x0 to 0xF"),
+            Chip8Error::InvalidRegister(x) => {
+                write!(f, "Invalid register: V{x:X}, expected V0 to VF")
+            }
+            Chip8Error::InvalidEntryPoint(entry) => write!(
+                f,
+                "Entry point 0x{entry:03X} is in the memory reserved for the interpreter"
+            ),
+            Chip8Error::RomTooLarge { size, entry } => write!(
+                f,
+                "ROM too large: {size} bytes don't fit in memory at 0x{entry:03X}"
+            ),
+            Chip8Error::RomTooSmall(0) => write!(f, "ROM is empty"),
+            Chip8Error::RomTooSmall(size) => write!(
+                f,
+                "ROM too small: {size} byte can't hold a 2-byte instruction"
+            ),
+            Chip8Error::LoadOutOfBounds { size, address } => write!(
+                f,
+                "Load out of bounds: {size} bytes don't fit in memory at 0x{address:03X}"
+            ),
+            Chip8Error::FontOverwrite { size, address } => write!(
+                f,
+                "Loading {size} bytes at 0x{address:03X} would overwrite the fonts"
+            ),
+            Chip8Error::UnsupportedMachineCall(nnn) => {
+                write!(f, "Unsupported machine code call: SYS 0x{nnn:03X}")
+            }
+            Chip8Error::PcOverflow(pc) => {
+                write!(
+                    f,
+                    "Program counter ran past the end of memory to 0x{pc:03X}"
+                )
+            }
+        }
+    }
+}
+
+impl core::error::Error for Chip8Error {}
@@ -0,0 +1,10 @@
+//! Hashes for fingerprinting byte data, e.g. screen buffers in tests (see `screen::screen_hash`) and ROM images (see
+//! `variant::rom_hash`).
+
+/// Compute the 64 bit FNV-1a hash of the given bytes. It is stable across platforms and Rust versions, unlike the
+/// hashers of the standard library.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
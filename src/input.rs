@@ -0,0 +1,81 @@
+//! Input backends for the CHIP-8 keypad.
+//!
+//! The emulator polls an `Input` at its sampling points (see `InputSampling`), and feeds the reported keys to the
+//! `EX9E`/`EXA1`/`FX0A` instructions, without knowing where the input comes from. Keys are reported as a bitmask, with
+//! bit `n` set if key `n` is held.
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::str::FromStr;
+
+pub trait Input {
+    /// Poll the currently held keys.
+    ///
+    /// # Arguments
+    /// * `cycle` - The cycle at which the emulator polls.
+    ///
+    /// # Return
+    /// * `Ok(keys)` with bit `n` set if key `n` is reported as held.
+    /// * `Err` if there was an error while polling.
+    fn poll(&mut self, cycle: u64) -> Result<u16, Box<dyn core::error::Error>>;
+
+    /// Whether the input reports key releases. If it doesn't, a key is held until `State::key_timeout` has passed
+    /// without it being reported.
+    fn reports_releases(&self) -> bool {
+        true
+    }
+}
+
+/// When the emulator polls its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputSampling {
+    /// Poll once at the start of each frame, before any instructions run.
+    #[default]
+    FrameStart,
+
+    /// Poll before every instruction.
+    Continuous,
+}
+
+impl FromStr for InputSampling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "frame" => Ok(InputSampling::FrameStart),
+            "continuous" => Ok(InputSampling::Continuous),
+            _ => Err(format!(
+                "Unknown input sampling '{s}', expected 'frame' or 'continuous'"
+            )),
+        }
+    }
+}
+
+/// Input that replays a fixed script of key presses, e.g. for tests.
+pub struct ScriptedInput {
+    /// Keys, and the range of cycles during which they are held.
+    presses: Vec<(Range<u64>, u8)>,
+}
+
+impl ScriptedInput {
+    /// Create a scripted input.
+    ///
+    /// # Arguments
+    /// * `presses` - Keys, and the range of cycles during which they are held.
+    pub fn new(presses: Vec<(Range<u64>, u8)>) -> Self {
+        Self { presses }
+    }
+}
+
+impl Input for ScriptedInput {
+    fn poll(&mut self, cycle: u64) -> Result<u16, Box<dyn core::error::Error>> {
+        Ok(self
+            .presses
+            .iter()
+            .filter(|(cycles, _)| cycles.contains(&cycle))
+            .fold(0, |keys, (_, key)| keys | 1 << key))
+    }
+}
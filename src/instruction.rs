@@ -0,0 +1,488 @@
+//! Decoded CHIP-8 instructions.
+//!
+//! `decode` turns an opcode into an `Instruction` without touching any interpreter state, and the `Display`
+//! implementation formats it in the syntax of Cowgod's "Chip-8 Technical Reference", e.g. `LD V0, 0x2A`.
+
+use Instruction::*;
+use core::fmt;
+
+/// A decoded instruction. `x` and `y` are register indices, `nnn` an address, `nn` a byte, and `n` a nibble.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// `0000`: Do nothing.
+    Nop,
+    /// `00E0`: Clear the display.
+    Cls,
+    /// `00EE`: Return from subroutine.
+    Ret,
+    /// `0NNN`: Call a machine code routine.
+    ///
+    /// Of the `0xxx` opcodes, only `0000`, `00E0` and `00EE` (decoded as their own variants) and `0230` (clear the
+    /// screen in the hires mode of the COSMAC VIP) are executed. The SUPER-CHIP and XO-CHIP forms `00CN`, `00DN` and
+    /// `00FB` to `00FF` are unknown opcodes, and any other address is a machine code call, which is ignored, or an
+    /// error in strict mode.
+    Sys(u16),
+    /// `1NNN`: Jump to `nnn`.
+    Jump(u16),
+    /// `2NNN`: Call the subroutine at `nnn`.
+    Call(u16),
+    /// `3XNN`: Skip if `Vx == nn`.
+    SkipEqByte { x: u8, nn: u8 },
+    /// `4XNN`: Skip if `Vx != nn`.
+    SkipNotEqByte { x: u8, nn: u8 },
+    /// `5XY0`: Skip if `Vx == Vy`.
+    SkipEqReg { x: u8, y: u8 },
+    /// `6XNN`: `Vx = nn`.
+    LoadByte { x: u8, nn: u8 },
+    /// `7XNN`: `Vx += nn`, without carry.
+    AddByte { x: u8, nn: u8 },
+    /// `8XY0`: `Vx = Vy`.
+    LoadReg { x: u8, y: u8 },
+    /// `8XY1`: `Vx |= Vy`.
+    Or { x: u8, y: u8 },
+    /// `8XY2`: `Vx &= Vy`.
+    And { x: u8, y: u8 },
+    /// `8XY3`: `Vx ^= Vy`.
+    Xor { x: u8, y: u8 },
+    /// `8XY4`: `Vx += Vy`, with carry in `VF`.
+    AddReg { x: u8, y: u8 },
+    /// `8XY5`: `Vx -= Vy`, with no borrow in `VF`.
+    Sub { x: u8, y: u8 },
+    /// `8XY6`: Shift right, with the shifted out bit in `VF`.
+    ShiftRight { x: u8, y: u8 },
+    /// `8XY7`: `Vx = Vy - Vx`, with no borrow in `VF`.
+    SubN { x: u8, y: u8 },
+    /// `8XYE`: Shift left, with the shifted out bit in `VF`.
+    ShiftLeft { x: u8, y: u8 },
+    /// `9XY0`: Skip if `Vx != Vy`.
+    SkipNotEqReg { x: u8, y: u8 },
+    /// `ANNN`: `I = nnn`.
+    LoadI(u16),
+    /// `BNNN`: Jump to `nnn` plus an offset register.
+    JumpOffset(u16),
+    /// `CXNN`: `Vx = random & nn`.
+    Random { x: u8, nn: u8 },
+    /// `DXYN`: Draw an `n` rows tall sprite at `Vx`, `Vy`.
+    Draw { x: u8, y: u8, n: u8 },
+    /// `EX9E`: Skip if the key in `Vx` is pressed.
+    SkipKeyPressed(u8),
+    /// `EXA1`: Skip if the key in `Vx` isn't pressed.
+    SkipKeyNotPressed(u8),
+    /// `F000 NNNN`: `I = nnnn`, a 16-bit address in the two bytes after the opcode (XO-CHIP).
+    LoadLongI,
+    /// `FN01`: Select the display planes `n` (XO-CHIP).
+    Plane(u8),
+    /// `FX07`: `Vx = DT`.
+    LoadDelay(u8),
+    /// `FX0A`: Wait for a key press and store it in `Vx`.
+    WaitKey(u8),
+    /// `FX15`: `DT = Vx`.
+    SetDelay(u8),
+    /// `FX18`: `ST = Vx`.
+    SetSound(u8),
+    /// `FX1E`: `I += Vx`.
+    AddI(u8),
+    /// `FX29`: Point `I` at the small font glyph for `Vx`.
+    LoadFont(u8),
+    /// `FX30`: Point `I` at the big font glyph for `Vx`.
+    LoadBigFont(u8),
+    /// `FX33`: Store the BCD representation of `Vx` at `I`.
+    StoreBcd(u8),
+    /// `FX55`: Store `V0` to `Vx` at `I`.
+    Store(u8),
+    /// `FX65`: Load `V0` to `Vx` from `I`.
+    Load(u8),
+    /// `FX75`: Save `V0` to `Vx` to the flag registers.
+    SaveFlags(u8),
+    /// `FX85`: Load `V0` to `Vx` from the flag registers.
+    LoadFlags(u8),
+    /// `FXFF`: Halt with exit code `x`.
+    Halt(u8),
+    /// An opcode that isn't an instruction.
+    Unknown(u16),
+}
+
+/// Decoders of the opcodes with each top nibble, for `decode`. Each extracts only the operands it needs.
+const DECODERS: [fn(u16) -> Instruction; 16] = [
+    |op| match op {
+        0x0000 => Nop,
+        0x00E0 => Cls,
+        0x00EE => Ret,
+        _ => Sys(nnn(op)),
+    },
+    |op| Jump(nnn(op)),
+    |op| Call(nnn(op)),
+    |op| SkipEqByte {
+        x: x(op),
+        nn: nn(op),
+    },
+    |op| SkipNotEqByte {
+        x: x(op),
+        nn: nn(op),
+    },
+    |op| match op & 0xF {
+        0 => SkipEqReg { x: x(op), y: y(op) },
+        _ => Unknown(op),
+    },
+    |op| LoadByte {
+        x: x(op),
+        nn: nn(op),
+    },
+    |op| AddByte {
+        x: x(op),
+        nn: nn(op),
+    },
+    |op| {
+        let (x, y) = (x(op), y(op));
+        match op & 0xF {
+            0x0 => LoadReg { x, y },
+            0x1 => Or { x, y },
+            0x2 => And { x, y },
+            0x3 => Xor { x, y },
+            0x4 => AddReg { x, y },
+            0x5 => Sub { x, y },
+            0x6 => ShiftRight { x, y },
+            0x7 => SubN { x, y },
+            0xE => ShiftLeft { x, y },
+            _ => Unknown(op),
+        }
+    },
+    |op| match op & 0xF {
+        0 => SkipNotEqReg { x: x(op), y: y(op) },
+        _ => Unknown(op),
+    },
+    |op| LoadI(nnn(op)),
+    |op| JumpOffset(nnn(op)),
+    |op| Random {
+        x: x(op),
+        nn: nn(op),
+    },
+    |op| Draw {
+        x: x(op),
+        y: y(op),
+        n: (op & 0xF) as u8,
+    },
+    |op| match nn(op) {
+        0x9E => SkipKeyPressed(x(op)),
+        0xA1 => SkipKeyNotPressed(x(op)),
+        _ => Unknown(op),
+    },
+    |op| {
+        let x = x(op);
+        match nn(op) {
+            0x00 if x == 0 => LoadLongI,
+            0x01 => Plane(x),
+            0x07 => LoadDelay(x),
+            0x0A => WaitKey(x),
+            0x15 => SetDelay(x),
+            0x18 => SetSound(x),
+            0x1E => AddI(x),
+            0x29 => LoadFont(x),
+            0x30 => LoadBigFont(x),
+            0x33 => StoreBcd(x),
+            0x55 => Store(x),
+            0x65 => Load(x),
+            0x75 => SaveFlags(x),
+            0x85 => LoadFlags(x),
+            0xFF => Halt(x),
+            _ => Unknown(op),
+        }
+    },
+];
+
+/// The register `x` of `_X__`.
+fn x(opcode: u16) -> u8 {
+    ((opcode & 0x0F00) >> 8) as u8
+}
+
+/// The register `y` of `__Y_`.
+fn y(opcode: u16) -> u8 {
+    ((opcode & 0x00F0) >> 4) as u8
+}
+
+/// The byte `nn` of `__NN`.
+fn nn(opcode: u16) -> u8 {
+    (opcode & 0x00FF) as u8
+}
+
+/// The address `nnn` of `_NNN`.
+fn nnn(opcode: u16) -> u16 {
+    opcode & 0x0FFF
+}
+
+/// Decode an opcode.
+///
+/// The opcode is dispatched through a table of decoders indexed by its top nibble, which measured faster than a match
+/// on the nibble, see `examples/decode_bench.rs`.
+///
+/// # Arguments
+/// * `opcode` - The opcode to decode.
+pub fn decode(opcode: u16) -> Instruction {
+    DECODERS[(opcode >> 12) as usize](opcode)
+}
+
+/// The forms of all instructions, as returned by `Instruction::form`.
+pub const FORMS: [&str; 42] = [
+    "0000", "00E0", "00EE", "0NNN", "1NNN", "2NNN", "3XNN", "4XNN", "5XY0", "6XNN", "7XNN", "8XY0",
+    "8XY1", "8XY2", "8XY3", "8XY4", "8XY5", "8XY6", "8XY7", "8XYE", "9XY0", "ANNN", "BNNN", "CXNN",
+    "DXYN", "EX9E", "EXA1", "F000", "FN01", "FX07", "FX0A", "FX15", "FX18", "FX1E", "FX29", "FX30",
+    "FX33", "FX55", "FX65", "FX75", "FX85", "FXFF",
+];
+
+impl Instruction {
+    /// The form of the instruction, with its operands as placeholders, e.g. `8XY4`.
+    ///
+    /// # Returns
+    /// * `Some(form)`, one of `FORMS`.
+    /// * `None` for `Unknown` opcodes.
+    pub fn form(&self) -> Option<&'static str> {
+        use Instruction::*;
+
+        let form = match self {
+            Nop => "0000",
+            Cls => "00E0",
+            Ret => "00EE",
+            Sys(_) => "0NNN",
+            Jump(_) => "1NNN",
+            Call(_) => "2NNN",
+            SkipEqByte { .. } => "3XNN",
+            SkipNotEqByte { .. } => "4XNN",
+            SkipEqReg { .. } => "5XY0",
+            LoadByte { .. } => "6XNN",
+            AddByte { .. } => "7XNN",
+            LoadReg { .. } => "8XY0",
+            Or { .. } => "8XY1",
+            And { .. } => "8XY2",
+            Xor { .. } => "8XY3",
+            AddReg { .. } => "8XY4",
+            Sub { .. } => "8XY5",
+            ShiftRight { .. } => "8XY6",
+            SubN { .. } => "8XY7",
+            ShiftLeft { .. } => "8XYE",
+            SkipNotEqReg { .. } => "9XY0",
+            LoadI(_) => "ANNN",
+            JumpOffset(_) => "BNNN",
+            Random { .. } => "CXNN",
+            Draw { .. } => "DXYN",
+            SkipKeyPressed(_) => "EX9E",
+            SkipKeyNotPressed(_) => "EXA1",
+            LoadLongI => "F000",
+            Plane(_) => "FN01",
+            LoadDelay(_) => "FX07",
+            WaitKey(_) => "FX0A",
+            SetDelay(_) => "FX15",
+            SetSound(_) => "FX18",
+            AddI(_) => "FX1E",
+            LoadFont(_) => "FX29",
+            LoadBigFont(_) => "FX30",
+            StoreBcd(_) => "FX33",
+            Store(_) => "FX55",
+            Load(_) => "FX65",
+            SaveFlags(_) => "FX75",
+            LoadFlags(_) => "FX85",
+            Halt(_) => "FXFF",
+            Unknown(_) => return None,
+        };
+        Some(form)
+    }
+
+    /// The approximate number of machine cycles the instruction took in the CHIP-8 interpreter of the COSMAC VIP,
+    /// including the 40 cycles to fetch and decode it, for the cycle accurate mode (see `Emulator::cycle_accurate`).
+    ///
+    /// The costs are those of the path where a conditional skip isn't taken. `DXYN` costs more with every row of the
+    /// sprite, but its wait for the vertical blank is modeled by the `display_wait` quirk instead. Instructions the VIP
+    /// didn't have are costed like their closest VIP counterpart.
+    pub fn cycles(&self) -> u32 {
+        use Instruction::*;
+
+        const FETCH: u32 = 40;
+
+        FETCH
+            + match *self {
+                Nop | Halt(_) | Unknown(_) => 0,
+                Cls => 3078,
+                Ret => 10,
+                Sys(_) => 12,
+                Jump(_) => 12,
+                Call(_) => 26,
+                SkipEqByte { .. } | SkipNotEqByte { .. } => 10,
+                SkipEqReg { .. } | SkipNotEqReg { .. } => 14,
+                LoadByte { .. } => 6,
+                AddByte { .. } => 10,
+                LoadReg { .. } => 12,
+                Or { .. }
+                | And { .. }
+                | Xor { .. }
+                | AddReg { .. }
+                | Sub { .. }
+                | ShiftRight { .. }
+                | SubN { .. }
+                | ShiftLeft { .. } => 44,
+                LoadI(_) => 12,
+                LoadLongI => 24,
+                JumpOffset(_) => 22,
+                Random { .. } => 36,
+                Draw { n, .. } => 26 + 45 * n as u32,
+                SkipKeyPressed(_) | SkipKeyNotPressed(_) => 14,
+                Plane(_) => 10,
+                LoadDelay(_) | SetDelay(_) | SetSound(_) => 10,
+                WaitKey(_) => 19,
+                AddI(_) => 16,
+                LoadFont(_) | LoadBigFont(_) => 16,
+                StoreBcd(_) => 84,
+                Store(x) | Load(x) | SaveFlags(x) | LoadFlags(x) => 14 + 14 * (x as u32 + 1),
+            }
+    }
+
+    /// Encode the instruction as an opcode, the inverse of `decode`.
+    pub fn encode(&self) -> u16 {
+        use Instruction::*;
+
+        let xy = |opcode: u16, x: u8, y: u8| opcode | (x as u16) << 8 | (y as u16) << 4;
+        let xnn = |opcode: u16, x: u8, nn: u8| opcode | (x as u16) << 8 | nn as u16;
+        let x = |opcode: u16, x: u8| opcode | (x as u16) << 8;
+
+        match *self {
+            Nop => 0x0000,
+            Cls => 0x00E0,
+            Ret => 0x00EE,
+            Sys(nnn) => nnn & 0x0FFF,
+            Jump(nnn) => 0x1000 | nnn & 0x0FFF,
+            Call(nnn) => 0x2000 | nnn & 0x0FFF,
+            SkipEqByte { x, nn } => xnn(0x3000, x, nn),
+            SkipNotEqByte { x, nn } => xnn(0x4000, x, nn),
+            SkipEqReg { x, y } => xy(0x5000, x, y),
+            LoadByte { x, nn } => xnn(0x6000, x, nn),
+            AddByte { x, nn } => xnn(0x7000, x, nn),
+            LoadReg { x, y } => xy(0x8000, x, y),
+            Or { x, y } => xy(0x8001, x, y),
+            And { x, y } => xy(0x8002, x, y),
+            Xor { x, y } => xy(0x8003, x, y),
+            AddReg { x, y } => xy(0x8004, x, y),
+            Sub { x, y } => xy(0x8005, x, y),
+            ShiftRight { x, y } => xy(0x8006, x, y),
+            SubN { x, y } => xy(0x8007, x, y),
+            ShiftLeft { x, y } => xy(0x800E, x, y),
+            SkipNotEqReg { x, y } => xy(0x9000, x, y),
+            LoadI(nnn) => 0xA000 | nnn & 0x0FFF,
+            JumpOffset(nnn) => 0xB000 | nnn & 0x0FFF,
+            Random { x, nn } => xnn(0xC000, x, nn),
+            Draw { x, y, n } => xy(0xD000, x, y) | (n & 0xF) as u16,
+            SkipKeyPressed(r) => x(0xE09E, r),
+            SkipKeyNotPressed(r) => x(0xE0A1, r),
+            LoadLongI => 0xF000,
+            Plane(n) => x(0xF001, n),
+            LoadDelay(r) => x(0xF007, r),
+            WaitKey(r) => x(0xF00A, r),
+            SetDelay(r) => x(0xF015, r),
+            SetSound(r) => x(0xF018, r),
+            AddI(r) => x(0xF01E, r),
+            LoadFont(r) => x(0xF029, r),
+            LoadBigFont(r) => x(0xF030, r),
+            StoreBcd(r) => x(0xF033, r),
+            Store(r) => x(0xF055, r),
+            Load(r) => x(0xF065, r),
+            SaveFlags(r) => x(0xF075, r),
+            LoadFlags(r) => x(0xF085, r),
+            Halt(r) => x(0xF0FF, r),
+            Unknown(opcode) => opcode,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Instruction::*;
+
+        match *self {
+            Nop => write!(f, "NOP"),
+            Cls => write!(f, "CLS"),
+            Ret => write!(f, "RET"),
+            Sys(nnn) => write!(f, "SYS 0x{nnn:03X}"),
+            Jump(nnn) => write!(f, "JP 0x{nnn:03X}"),
+            Call(nnn) => write!(f, "CALL 0x{nnn:03X}"),
+            SkipEqByte { x, nn } => write!(f, "SE V{x:X}, 0x{nn:02X}"),
+            SkipNotEqByte { x, nn } => write!(f, "SNE V{x:X}, 0x{nn:02X}"),
+            SkipEqReg { x, y } => write!(f, "SE V{x:X}, V{y:X}"),
+            LoadByte { x, nn } => write!(f, "LD V{x:X}, 0x{nn:02X}"),
+            AddByte { x, nn } => write!(f, "ADD V{x:X}, 0x{nn:02X}"),
+            LoadReg { x, y } => write!(f, "LD V{x:X}, V{y:X}"),
+            Or { x, y } => write!(f, "OR V{x:X}, V{y:X}"),
+            And { x, y } => write!(f, "AND V{x:X}, V{y:X}"),
+            Xor { x, y } => write!(f, "XOR V{x:X}, V{y:X}"),
+            AddReg { x, y } => write!(f, "ADD V{x:X}, V{y:X}"),
+            Sub { x, y } => write!(f, "SUB V{x:X}, V{y:X}"),
+            ShiftRight { x, y } => write!(f, "SHR V{x:X}, V{y:X}"),
+            SubN { x, y } => write!(f, "SUBN V{x:X}, V{y:X}"),
+            ShiftLeft { x, y } => write!(f, "SHL V{x:X}, V{y:X}"),
+            SkipNotEqReg { x, y } => write!(f, "SNE V{x:X}, V{y:X}"),
+            LoadI(nnn) => write!(f, "LD I, 0x{nnn:03X}"),
+            JumpOffset(nnn) => write!(f, "JP V0, 0x{nnn:03X}"),
+            Random { x, nn } => write!(f, "RND V{x:X}, 0x{nn:02X}"),
+            Draw { x, y, n } => write!(f, "DRW V{x:X}, V{y:X}, {n}"),
+            SkipKeyPressed(x) => write!(f, "SKP V{x:X}"),
+            SkipKeyNotPressed(x) => write!(f, "SKNP V{x:X}"),
+            LoadLongI => write!(f, "LD I, LONG"),
+            Plane(n) => write!(f, "PLANE {n}"),
+            LoadDelay(x) => write!(f, "LD V{x:X}, DT"),
+            WaitKey(x) => write!(f, "LD V{x:X}, K"),
+            SetDelay(x) => write!(f, "LD DT, V{x:X}"),
+            SetSound(x) => write!(f, "LD ST, V{x:X}"),
+            AddI(x) => write!(f, "ADD I, V{x:X}"),
+            LoadFont(x) => write!(f, "LD F, V{x:X}"),
+            LoadBigFont(x) => write!(f, "LD HF, V{x:X}"),
+            StoreBcd(x) => write!(f, "LD B, V{x:X}"),
+            Store(x) => write!(f, "LD [I], V{x:X}"),
+            Load(x) => write!(f, "LD V{x:X}, [I]"),
+            SaveFlags(x) => write!(f, "LD R, V{x:X}"),
+            LoadFlags(x) => write!(f, "LD V{x:X}, R"),
+            Halt(x) => write!(f, "HALT {x}"),
+            Unknown(opcode) => write!(f, "DW 0x{opcode:04X}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn every_form_is_decoded() {
+        let decoded: BTreeSet<_> = (0..=u16::MAX)
+            .filter_map(|opcode| decode(opcode).form())
+            .collect();
+
+        assert_eq!(decoded, BTreeSet::from(FORMS));
+    }
+
+    #[test]
+    fn decode_opcodes() {
+        assert_eq!(decode(0x00E0), Instruction::Cls);
+        assert_eq!(decode(0x2345), Instruction::Call(0x345));
+        assert_eq!(decode(0x8AB6), Instruction::ShiftRight { x: 0xA, y: 0xB });
+        assert_eq!(decode(0xD125), Instruction::Draw { x: 1, y: 2, n: 5 });
+        assert_eq!(decode(0x5121), Instruction::Unknown(0x5121));
+        assert_eq!(decode(0xE000), Instruction::Unknown(0xE000));
+    }
+
+    #[test]
+    fn encode_is_inverse_of_decode() {
+        for opcode in 0..=u16::MAX {
+            assert_eq!(decode(opcode).encode(), opcode, "{opcode:04X}");
+        }
+    }
+
+    #[test]
+    fn cycle_costs() {
+        assert_eq!(Instruction::LoadByte { x: 0, nn: 0 }.cycles(), 46);
+        assert_eq!(Instruction::Draw { x: 0, y: 0, n: 1 }.cycles(), 111);
+        assert_eq!(
+            Instruction::Draw { x: 0, y: 0, n: 15 }.cycles()
+                - Instruction::Draw { x: 0, y: 0, n: 5 }.cycles(),
+            450
+        );
+        assert!(Instruction::Store(0xF).cycles() > Instruction::Store(0).cycles());
+        assert!((0..=u16::MAX).all(|opcode| decode(opcode).cycles() >= 40));
+    }
+}
@@ -0,0 +1,152 @@
+//! Mapping of physical keys to the CHIP-8 hexadecimal keypad.
+//!
+//! A key map can be loaded from a text file with one `key=hexdigit` mapping per line, e.g. `w=5`. Keys are single
+//! characters, or one of the names `up`, `down`, `left`, `right`, `space`, `enter`, `tab` and `backspace`. Empty lines
+//! and lines starting with `#` are ignored.
+
+use crossterm::event::KeyCode;
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Maps physical keys to CHIP-8 keys (0x0 to 0xF).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap {
+    keys: HashMap<KeyCode, u8>,
+}
+
+impl KeyMap {
+    /// Create a key map.
+    ///
+    /// # Arguments
+    /// * `keys` - The CHIP-8 key of each physical key. Keys above 0xF are ignored.
+    pub fn new(keys: HashMap<KeyCode, u8>) -> Self {
+        Self {
+            keys: keys.into_iter().filter(|(_, key)| *key <= 0xF).collect(),
+        }
+    }
+
+    /// Load a key map from a file.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the key map file.
+    ///
+    /// # Returns
+    /// * `Ok(KeyMap)` with the mappings in the file.
+    /// * `Err` if the file couldn't be read, or contains an invalid line.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        text.parse()
+            .map_err(|e| format!("Invalid key map '{}': {}", path.display(), e).into())
+    }
+
+    /// The CHIP-8 key mapped to a physical key, if any.
+    pub fn get(&self, code: KeyCode) -> Option<u8> {
+        self.keys.get(&code).copied()
+    }
+}
+
+impl Default for KeyMap {
+    /// The classic layout, using the left side of a QWERTY keyboard:
+    ///
+    /// ```text
+    /// 1 2 3 4      0 1 2 3
+    /// Q W E R  ->  4 5 6 7
+    /// A S D F      8 9 A B
+    /// Z X C V      C D E F
+    /// ```
+    fn default() -> Self {
+        let keys = "1234qwerasdfzxcv"
+            .chars()
+            .enumerate()
+            .map(|(key, c)| (KeyCode::Char(c), key as u8))
+            .collect();
+        Self { keys }
+    }
+}
+
+impl FromStr for KeyMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys = HashMap::new();
+
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (code, key) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected 'key=hexdigit'", number + 1))?;
+            let code = parse_key_code(code.trim())
+                .ok_or_else(|| format!("line {}: unknown key '{}'", number + 1, code.trim()))?;
+            let key = u8::from_str_radix(key.trim(), 16)
+                .ok()
+                .filter(|key| *key <= 0xF)
+                .ok_or_else(|| {
+                    format!("line {}: invalid hex digit '{}'", number + 1, key.trim())
+                })?;
+
+            keys.insert(code, key);
+        }
+
+        Ok(Self { keys })
+    }
+}
+
+/// Parse a key name, as used in key map files.
+fn parse_key_code(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+
+    match name.to_lowercase().as_str() {
+        "up" => Some(KeyCode::Up),
+        "down" => Some(KeyCode::Down),
+        "left" => Some(KeyCode::Left),
+        "right" => Some(KeyCode::Right),
+        "space" => Some(KeyCode::Char(' ')),
+        "enter" => Some(KeyCode::Enter),
+        "tab" => Some(KeyCode::Tab),
+        "backspace" => Some(KeyCode::Backspace),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_layout() {
+        let keymap = KeyMap::default();
+
+        assert_eq!(keymap.get(KeyCode::Char('1')), Some(0x0));
+        assert_eq!(keymap.get(KeyCode::Char('w')), Some(0x5));
+        assert_eq!(keymap.get(KeyCode::Char('v')), Some(0xF));
+        assert_eq!(keymap.get(KeyCode::Char('p')), None);
+    }
+
+    #[test]
+    fn parse_keymap() {
+        let keymap: KeyMap = "# WASD\nw=5\na = 7\n\nspace=A\nup=2\n"
+            .parse()
+            .expect("Failed to parse key map");
+
+        assert_eq!(keymap.get(KeyCode::Char('w')), Some(0x5));
+        assert_eq!(keymap.get(KeyCode::Char('a')), Some(0x7));
+        assert_eq!(keymap.get(KeyCode::Char(' ')), Some(0xA));
+        assert_eq!(keymap.get(KeyCode::Up), Some(0x2));
+        assert_eq!(keymap.get(KeyCode::Char('1')), None);
+    }
+
+    #[test]
+    fn parse_keymap_errors() {
+        assert!("w".parse::<KeyMap>().is_err());
+        assert!("w=10".parse::<KeyMap>().is_err());
+        assert!("pageup=1".parse::<KeyMap>().is_err());
+    }
+}
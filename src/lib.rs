@@ -1,130 +1,673 @@
-#![allow(unused)]
+//! A CHIP-8 interpreter.
+//!
+//! The core (`State`, the decoder, `Emulator`, and the framebuffer) has no platform dependencies, so it also builds for
+//! `wasm32-unknown-unknown`, where the host drives `Emulator::step` and `Emulator::vblank`, e.g. from
+//! `requestAnimationFrame`. The terminal frontend and `run_rom` need the `terminal` feature, and the binaries need the
+//! `cli` feature, which are both on by default. The `cli` feature includes the `json` feature, for dumps of the state
+//! (see `dump`).
+//!
+//! Without the `std` feature (on by default), the crate is `no_std` and only needs `alloc`, e.g. to run the decoder on
+//! a microcontroller. Files, OS entropy and the wall clock are then unavailable: ROMs are loaded from a byte slice, and
+//! the random number generator should be seeded with `State::set_seed`.
 
-use crate::term::{cleanup_terminal, set_styles, setup_terminal, should_exit};
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "terminal")]
+use crate::display::Display;
+#[cfg(feature = "terminal")]
+use crate::input::Input;
+#[cfg(feature = "terminal")]
+use crate::term::{TerminalDisplay, TerminalGuard, TerminalInput, set_styles};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+#[cfg(feature = "terminal")]
 use crossterm::cursor::MoveTo;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, poll};
-use crossterm::terminal::{Clear, ClearType, size};
-use crossterm::{ExecutableCommand, execute};
+#[cfg(feature = "terminal")]
+use crossterm::execute;
+#[cfg(feature = "terminal")]
+use crossterm::style::Color;
 use log::*;
-use std::io::Write;
-use std::io::stdout;
+#[cfg(feature = "terminal")]
+use std::io::{Write, stdout};
+#[cfg(feature = "terminal")]
 use std::path::PathBuf;
+#[cfg(feature = "terminal")]
 use std::time::{Duration, SystemTime};
 
-mod constants;
-mod decoder;
-mod state;
-mod term;
+pub mod assembler;
+pub mod constants;
+pub mod decoder;
+pub mod disassembler;
+pub mod display;
+#[cfg(feature = "json")]
+pub mod dump;
+pub mod emulator;
+pub mod error;
+pub mod font;
+pub mod hash;
+pub mod input;
+pub mod instruction;
+#[cfg(feature = "terminal")]
+pub mod keymap;
+pub mod pacer;
+pub mod quirks;
+#[cfg(feature = "gif")]
+pub mod recorder;
+#[cfg(feature = "std")]
+pub mod replay;
+pub mod rng;
+pub mod screen;
+#[cfg(feature = "png")]
+pub mod screenshot;
+pub mod state;
+pub mod stats;
+#[cfg(feature = "terminal")]
+pub mod term;
+pub mod trace;
+pub mod validate;
+pub mod variant;
 
-pub fn run_rom(rom_path: PathBuf) -> Result<usize, Box<dyn std::error::Error>> {
-    let mut state = state::State::try_from(&rom_path)?;
+/// Options for `run_rom`.
+#[cfg(feature = "terminal")]
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Instructions per second. 0 runs as fast as possible, in turbo mode.
+    pub speed: u32,
 
-    let tick_length = Duration::from_secs(1) / constants::CLOCK_FREQ;
+    /// Run this many instructions at the start of each frame, sleeping once per frame, rather than following `speed`.
+    pub instructions_per_frame: Option<u32>,
 
-    let original_size = size()?;
-    let mut stdout = stdout();
+    /// Advance the clock by the COSMAC VIP machine cycles of each instruction, at the VIP's clock speed, rather than
+    /// following `speed` or `instructions_per_frame`.
+    pub cycle_accurate: bool,
+
+    /// Seed of the random number generator. If none, the seed is taken from OS entropy.
+    pub seed: Option<u64>,
+
+    /// Address to load the ROM at, and start executing from.
+    pub entry: usize,
+
+    /// Fill the memory the ROM doesn't occupy with HALT instructions, see `State::trap_uninitialized_memory`.
+    pub trap_uninit: bool,
+
+    /// Files to copy into memory after the ROM, at their addresses, see `State::load_at`.
+    pub loads: Vec<(usize, PathBuf)>,
+
+    /// Refuse loads over the fonts.
+    pub protect_fonts: bool,
+
+    /// Strip and validate a 2-byte length or CRC trailer from the ROM before loading it.
+    pub rom_trailer: bool,
+
+    /// Load the RPL user flags from `<rom>.sav` at startup, and save them there when the program halts or the user
+    /// exits. A missing or corrupt file starts with zeroed flags, and the file is only written if the flags changed.
+    pub persist_flags: bool,
+
+    /// When input is polled.
+    pub input_sampling: input::InputSampling,
+
+    /// How long a key is held after the terminal reported it, if it doesn't report releases.
+    pub key_timeout: Duration,
+
+    /// Fail on unknown opcodes, rather than ignoring them with a warning.
+    pub strict: bool,
+
+    /// Colors of the terminal renderer.
+    pub theme: term::Theme,
+
+    /// Size of the image in the terminal.
+    pub scale: term::Scale,
+
+    /// How pixels are drawn in the terminal.
+    pub render_mode: term::RenderMode,
+
+    /// Terminal redraws per second, independent of the instruction rate.
+    pub fps: u32,
+
+    /// Color of pixels that are on, overriding the theme.
+    pub foreground: Option<Color>,
+
+    /// Color of pixels that are off, overriding the theme.
+    pub background: Option<Color>,
+
+    /// Mapping of terminal keys to the keypad.
+    pub keymap: keymap::KeyMap,
+
+    /// Record the keypad input to this file, to reproduce the run with `replay`, if set.
+    pub record: Option<PathBuf>,
+
+    /// Play back the keypad input recorded to this file, with its seed, instead of reading the keyboard, if set.
+    pub replay: Option<PathBuf>,
+
+    /// Write every executed instruction to this file, if set.
+    pub trace: Option<PathBuf>,
+
+    /// Dump the final state as JSON to this file when the program halts, fails or the user exits, if set.
+    #[cfg(feature = "json")]
+    pub dump_on_exit: Option<PathBuf>,
+
+    /// Stop tracing after this many instructions, if set.
+    pub trace_limit: Option<u64>,
+
+    /// Print a summary of the run on exit.
+    pub stats: bool,
+
+    /// Print which instruction forms were executed on exit.
+    pub coverage: bool,
+
+    /// Show the HUD, with the ROM name, the speed and the sound, instead of the status lines. H toggles it.
+    pub hud: bool,
+
+    /// Start paused, to single-step through the program.
+    pub step: bool,
+
+    /// Show a debugger panel below the display, with the registers, the stack and the next instruction.
+    pub debug: bool,
+
+    /// Halt when the program jumps to itself, rather than idling.
+    pub halt_on_infinite_loop: bool,
+
+    /// What happens when the program counter runs past the end of memory.
+    pub pc_overflow: state::PcOverflow,
 
-    setup_terminal()?;
-    set_styles()?;
+    /// Limit on nested subroutine calls. None for unlimited.
+    pub max_stack_depth: Option<usize>,
 
-    let exit_code = loop {
-        let tick_start: SystemTime = SystemTime::now();
+    /// Keep the call stack in memory at 0xEA0, like the COSMAC VIP.
+    pub memory_stack: bool,
 
-        if state.waiting_for_keypress.is_none()
-            && let Some(exit_code) = decoder::decode_and_execute(&mut state)?
-        {
-            // Halt execution
-            break exit_code;
+    /// Restart the program when it halts, rather than exiting, e.g. to loop attract modes.
+    pub reset_on_halt: bool,
+
+    /// Factor by which holding Tab multiplies the instructions run per frame. The timers keep their rate.
+    pub turbo: u32,
+
+    /// Number of frames kept for rewinding with B, 0 to disable rewinding.
+    pub rewind_depth: usize,
+
+    /// Interpreter whose quirks to emulate. If none, it is detected from the ROM.
+    pub variant: Option<variant::Variant>,
+
+    /// Without a `variant`, use the quirks of the ROM if it is a known one, see `variant::known_rom`, before detecting
+    /// the variant.
+    pub auto_quirks: bool,
+}
+
+#[cfg(feature = "terminal")]
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            speed: constants::CLOCK_FREQ,
+            instructions_per_frame: None,
+            cycle_accurate: false,
+            seed: None,
+            entry: constants::ENTRY_POINT,
+            trap_uninit: false,
+            loads: Vec::new(),
+            protect_fonts: false,
+            rom_trailer: false,
+            persist_flags: true,
+            input_sampling: input::InputSampling::default(),
+            key_timeout: constants::KEY_PRESS_TIMEOUT_MS,
+            strict: false,
+            theme: term::Theme::default(),
+            scale: term::Scale::default(),
+            render_mode: term::RenderMode::default(),
+            fps: constants::RENDER_FPS,
+            foreground: None,
+            background: None,
+            keymap: keymap::KeyMap::default(),
+            record: None,
+            replay: None,
+            trace: None,
+            #[cfg(feature = "json")]
+            dump_on_exit: None,
+            trace_limit: None,
+            stats: false,
+            coverage: false,
+            hud: false,
+            step: false,
+            debug: false,
+            halt_on_infinite_loop: false,
+            pc_overflow: state::PcOverflow::default(),
+            max_stack_depth: Some(constants::STACK_DEPTH),
+            memory_stack: false,
+            reset_on_halt: false,
+            turbo: constants::TURBO_FACTOR,
+            rewind_depth: constants::REWIND_DEPTH,
+            variant: None,
+            auto_quirks: false,
         }
+    }
+}
 
-        // TODO: Update timers at 60Hz
+#[cfg(feature = "terminal")]
+pub fn run_rom(
+    rom_path: PathBuf,
+    options: &RunOptions,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let rom = state::read_rom(&rom_path)?;
+    let rom = if options.rom_trailer {
+        state::strip_trailer(&rom).0
+    } else {
+        &rom
+    };
+
+    let known = options
+        .auto_quirks
+        .then(|| variant::known_rom(rom))
+        .flatten();
+    let quirks = match (options.variant, known) {
+        (Some(variant), _) => variant.quirks(),
+        (None, Some(known)) => {
+            info!("Recognized the {}, applying its quirks", known.name);
+            known.quirks
+        }
+        (None, None) => {
+            let variant = variant::detect_variant(rom);
+            info!("Detected a {variant} ROM, override with --quirks");
+            variant.quirks()
+        }
+    };
+    let mut builder = emulator::Emulator::builder()
+        .quirks(quirks)
+        .strict(options.strict)
+        .max_stack_depth(options.max_stack_depth)
+        .memory_stack(options.memory_stack)
+        .key_timeout(options.key_timeout)
+        .input_sampling(options.input_sampling)
+        .halt_on_infinite_loop(options.halt_on_infinite_loop)
+        .pc_overflow(options.pc_overflow)
+        .rewind_depth(options.rewind_depth);
+    // A speed of 0 means unthrottled, while the clock speed still paces the simulated frames
+    builder = match (options.instructions_per_frame, options.speed) {
+        (Some(instructions_per_frame), _) => {
+            builder.clock_hz(instructions_per_frame.saturating_mul(constants::TIMER_FREQ))
+        }
+        (None, 0) => builder.turbo(true),
+        (None, speed) => builder.clock_hz(speed),
+    };
+    if options.cycle_accurate {
+        builder = builder.turbo(false).cycle_accurate(true);
+    }
+    if let Some(seed) = options.seed {
+        builder = builder.seed(seed);
+    }
+    let mut emulator = builder.build();
+    if options.trap_uninit {
+        emulator.state.trap_uninitialized_memory();
+    }
+    emulator.state.load_rom_at(rom, options.entry)?;
+    emulator.state.protect_fonts = options.protect_fonts;
+    for (address, path) in &options.loads {
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+        emulator.state.load_at(&bytes, *address)?;
+        info!(
+            "Loaded {} bytes from '{}' at 0x{address:03X}",
+            bytes.len(),
+            path.display()
+        );
+    }
+    if let Some(replay_path) = &options.replay {
+        emulator.play_recording(replay_path)?;
+        info!("Playing back '{}'", replay_path.display());
+    }
+    info!("Random seed: {}", emulator.state.seed);
 
-        if poll(Duration::from_millis(0))? {
-            let event = event::read()?;
+    let flags_path = rom_path.with_extension("sav");
+    if options.persist_flags
+        && flags_path.exists()
+        && let Err(e) = emulator.state.load_flags(&flags_path)
+    {
+        warn!("Ignored save file '{}': {}", flags_path.display(), e);
+    }
+    let loaded_flags = emulator.state.flags;
+    if let Some(trace_path) = &options.trace {
+        emulator.set_trace_hook(trace::file_trace_hook(trace_path, options.trace_limit)?);
+    }
+    if options.coverage {
+        emulator.coverage = Some(stats::Coverage::default());
+    }
+    if options.step {
+        emulator.pause();
+    }
 
-            // TODO: update keys down in state
+    let frame_length = Duration::from_secs(1) / constants::TIMER_FREQ;
+    let render_interval = Duration::from_secs(1) / options.fps;
+    let mut last_render = None;
+    let mut pacer = match options.instructions_per_frame {
+        Some(instructions_per_frame) if !options.cycle_accurate => {
+            pacer::Pacer::per_frame(instructions_per_frame)
+        }
+        _ => pacer::Pacer::new(emulator.cycles_per_second),
+    };
+    let mut last_iteration = SystemTime::now();
 
-            if should_exit(&event)? {
-                break 0;
-            }
+    let run_start = SystemTime::now();
+    let mut stdout = stdout();
 
-            if let Event::Key(KeyEvent {
-                code: KeyCode::Char(c),
-                ..
-            }) = event
-            {
-                state.key_pressed_at = SystemTime::now();
-
-                let key = match c {
-                    '1' => Some(0x0),
-                    '2' => Some(0x1),
-                    '3' => Some(0x2),
-                    '4' => Some(0x3),
-                    'q' => Some(0x4),
-                    'w' => Some(0x5),
-                    'e' => Some(0x6),
-                    'r' => Some(0x7),
-                    'a' => Some(0x8),
-                    's' => Some(0x9),
-                    'd' => Some(0xA),
-                    'f' => Some(0xB),
-                    'z' => Some(0xC),
-                    'x' => Some(0xD),
-                    'c' => Some(0xE),
-                    'v' => Some(0xF),
-                    _ => None,
-                };
-                state.key_pressed = key;
+    // Restores the terminal however the run ends, also on errors and panics
+    let terminal = TerminalGuard::new()?;
+    let mut input = TerminalInput::new(terminal.reports_releases(), options.keymap.clone());
+    if let Some(record_path) = &options.record {
+        emulator.start_recording(record_path, input.reports_releases())?;
+    }
+    let (foreground, background) = options.theme.colors();
+    let foreground = options.foreground.unwrap_or(foreground);
+    let background = options.background.unwrap_or(background);
+    set_styles(foreground, background)?;
+    let mut display =
+        TerminalDisplay::new(foreground, background, options.scale, options.render_mode);
+    if options.debug {
+        display = display.with_debug_panel();
+    }
+
+    // The last instruction executed by single-stepping, with the registers
+    let mut last_step = None;
+
+    // The HUD replaces the status lines, with the speed measured over the last second
+    let mut show_hud = options.hud;
+    let rom_name = rom_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut instructions_per_second = 0;
+    let mut speed_sample = (SystemTime::now(), emulator.stats.instructions);
+
+    let exit_code = 'run: loop {
+        // Work out how many cycles and frames are owed since the previous iteration. Turbo mode doesn't follow the wall
+        // clock, and simulates its frames
+        let now = SystemTime::now();
+        let (cycles, frames) = if emulator.turbo {
+            (1, 0)
+        } else {
+            pacer.advance(now.duration_since(last_iteration).unwrap_or_default())
+        };
+        // Turbo runs more instructions per frame, while the frames, and so the timers, follow the wall clock
+        let cycles = if input.turbo_held {
+            cycles.saturating_mul(options.turbo)
+        } else {
+            cycles
+        };
+        last_iteration = now;
 
-                if let Some(reg) = state.waiting_for_keypress
-                    && let Some(key) = key
-                {
-                    state.v[reg] = key;
-                    state.waiting_for_keypress = None;
+        if emulator.is_paused() {
+            // Keep polling, to notice when to resume, and to provide keys to single-stepped instructions
+            emulator.poll_input(&mut input)?;
+
+            if std::mem::take(&mut input.step_requested) {
+                let (pc, opcode) = emulator.next_instruction();
+                let result = emulator.single_step();
+                #[cfg(feature = "json")]
+                if result.is_err() {
+                    dump_on_exit(options, &emulator.state);
+                }
+                if let Some(exit_code) = result? {
+                    break exit_code;
+                }
+                let registers: String = emulator
+                    .state
+                    .v
+                    .iter()
+                    .map(|v| format!("{v:02X}"))
+                    .collect();
+                last_step = Some(format!(
+                    "{:03X}: {:<13} {}",
+                    pc,
+                    disassembler::mnemonic(opcode),
+                    registers
+                ));
+            }
+        } else {
+            // In the cycle accurate mode, an instruction can use up several of the owed cycles
+            let until = emulator.state.cycles + cycles as u64;
+            while emulator.state.cycles < until {
+                let result = emulator.step_with_input(&mut input);
+                #[cfg(feature = "json")]
+                if result.is_err() {
+                    dump_on_exit(options, &emulator.state);
                 }
+                if let Some(exit_code) = result? {
+                    if !options.reset_on_halt {
+                        // Halt execution
+                        break 'run exit_code;
+                    }
+                    info!("Program halted with exit code {exit_code}, restarting");
+                    emulator.reset();
+                    break;
+                }
+                if input.exit_requested {
+                    break;
+                }
+            }
+        }
+
+        if input.exit_requested {
+            break 0;
+        }
+
+        if std::mem::take(&mut input.pause_requested) {
+            if emulator.is_paused() {
+                // Don't try to catch up with the time spent paused
+                emulator.resume();
+                pacer.reset();
+            } else {
+                emulator.pause();
             }
+        }
 
-            execute!(stdout, MoveTo(0, (constants::HEIGHT + 1) as u16));
-            execute!(stdout, Clear(ClearType::CurrentLine));
-            // write!(stdout, "{event:?}");
-            write!(stdout, "{:?}", state.key_pressed);
+        if std::mem::take(&mut input.hud_requested) {
+            show_hud = !show_hud;
+        }
+        let sample_length = now.duration_since(speed_sample.0).unwrap_or_default();
+        if sample_length >= Duration::from_secs(1) {
+            let instructions = emulator.stats.instructions - speed_sample.1;
+            instructions_per_second = (instructions as f64 / sample_length.as_secs_f64()) as u64;
+            speed_sample = (now, emulator.stats.instructions);
         }
 
-        for row in 0..constants::HEIGHT {
-            execute!(stdout, MoveTo(0, row as u16));
+        if std::mem::take(&mut input.rewind_requested) {
+            // Scrub back a second
+            let frames = emulator.rewind(constants::TIMER_FREQ as usize);
+            info!("Rewound {frames} frames");
+            last_step = None;
+            pacer.reset();
+        }
 
-            for column in 0..constants::WIDTH {
-                let pixel_on = state.screen[row * constants::WIDTH + column];
-                let symbol = if pixel_on { '█' } else { ' ' };
-                write!(stdout, "{}", symbol)?;
+        // Update timers at 60Hz, unless the emulator counts the frames itself
+        if !emulator.counts_frames() {
+            for _ in 0..frames {
+                emulator.vblank();
             }
         }
 
-        execute!(stdout, MoveTo(0, constants::HEIGHT as u16));
-        write!(stdout, "PC: {:03X}", state.pc);
+        // Redraw at the render rate, however many cycles ran since the last redraw
+        let render_due = last_render
+            .is_none_or(|last| now.duration_since(last).unwrap_or_default() >= render_interval);
+        if render_due {
+            last_render = Some(now);
+
+            if emulator.state.quirks.xo_chip_planes {
+                display.render_planes(&emulator.state.visible_planes())?;
+            } else {
+                display.render(emulator.state.visible_screen())?;
+            }
+
+            if let Some((column, row)) = display.status_position()
+                && show_hud
+            {
+                let lines = term::hud(&rom_name, instructions_per_second, emulator.is_beeping());
+                for (line, text) in (row..).zip(lines) {
+                    execute!(stdout, MoveTo(column, line))?;
+                    write!(stdout, "{:<width$}", text, width = constants::WIDTH)?;
+                }
+            } else if let Some((column, row)) = display.status_position() {
+                execute!(stdout, MoveTo(column, row))?;
+                let status = match &last_step {
+                    Some(step) if emulator.is_paused() => step.clone(),
+                    _ if emulator.is_paused() => format!("PC: {:03X}  PAUSED", emulator.state.pc),
+                    _ if input.turbo_held => format!("PC: {:03X}  TURBO", emulator.state.pc),
+                    _ => format!("PC: {:03X}", emulator.state.pc),
+                };
+                write!(stdout, "{:<width$}", status, width = constants::WIDTH)?;
+
+                let keys: Vec<String> = (0..constants::KEY_COUNT)
+                    .filter(|key| emulator.keys_pressed() & (1 << key) != 0)
+                    .map(|key| format!("{key:X}"))
+                    .collect();
+                execute!(stdout, MoveTo(column, row + 1))?;
+                write!(
+                    stdout,
+                    "{:<width$}",
+                    format!("Keys: {}", keys.join(" ")),
+                    width = constants::WIDTH
+                )?;
+            }
 
-        // Check for keypress timeout
-        let elapsed = elapsed_time(&state.key_pressed_at);
-        if elapsed > constants::KEY_PRESS_TIMEOUT_MS {
-            state.key_pressed = None;
-            execute!(stdout, MoveTo(0, (constants::HEIGHT + 1) as u16));
-            execute!(stdout, Clear(ClearType::CurrentLine));
+            if let Some((column, row)) = display.panel_position() {
+                for (line, text) in (row..).zip(term::debug_panel(&emulator.state)) {
+                    execute!(stdout, MoveTo(column, line))?;
+                    write!(stdout, "{:<width$}", text, width = constants::WIDTH)?;
+                }
+            }
         }
 
-        // Wait for tick
-        let elapsed = elapsed_time(&tick_start);
-        if elapsed < tick_length {
-            std::thread::sleep(tick_length - elapsed);
+        // Wait for the next cycle, or the next frame with a budget per frame. At high speeds a cycle is too short to
+        // sleep for, so only sleep once we're sufficiently ahead
+        if emulator.is_paused() || emulator.is_idle() {
+            // Nothing to do until the next frame
+            std::thread::sleep(frame_length);
+        } else if !emulator.turbo {
+            let ahead = pacer.until_next_cycle();
+            if ahead >= constants::MIN_SLEEP {
+                std::thread::sleep(ahead);
+            }
         }
     };
 
-    cleanup_terminal(original_size)?;
+    terminal.restore()?;
+
+    #[cfg(feature = "json")]
+    dump_on_exit(options, &emulator.state);
+
+    if options.stats {
+        println!("{}", emulator.stats.summary(elapsed_time(&run_start)));
+    }
+    if let Some(coverage) = &emulator.coverage {
+        println!("{coverage}");
+    }
+
+    if options.persist_flags && emulator.state.flags != loaded_flags {
+        emulator.state.save_flags(&flags_path)?;
+    }
 
     debug!("Program halted with exit code {}", exit_code);
 
     Ok(exit_code)
 }
 
+/// Dump the state to the file in `RunOptions::dump_on_exit`, if set. Failing to write it is logged, so the reason the
+/// program exited isn't lost.
+#[cfg(all(feature = "terminal", feature = "json"))]
+fn dump_on_exit(options: &RunOptions, state: &state::State) {
+    if let Some(path) = &options.dump_on_exit {
+        match dump::dump_state(state, path) {
+            Ok(()) => info!("Dumped the state to {}", path.display()),
+            Err(e) => error!("Failed to dump the state to {}: {e}", path.display()),
+        }
+    }
+}
+
+/// Run a ROM without a terminal, and return the final contents of the screen.
+///
+/// Execution is fully deterministic: there is no input and no sleeping, and the emulator runs in turbo mode, so frame
+/// boundaries are counted in cycles rather than following the wall clock. A program that ends by jumping to itself
+/// halts.
+///
+/// # Arguments
+/// * `rom` - The ROM image to load at 0x200.
+/// * `max_cycles` - The maximum number of cycles to run, if the program doesn't halt before that.
+///
+/// # Returns
+/// The visible screen buffer when the program halted, or when `max_cycles` was reached.
+pub fn run_rom_headless(
+    rom: &[u8],
+    max_cycles: usize,
+) -> Result<Vec<bool>, Box<dyn core::error::Error>> {
+    let emulator = run_headless(rom, max_cycles, true)?;
+    Ok(emulator.state.visible_screen().to_vec())
+}
+
+/// Run a ROM headless like `run_rom_headless`, and record which instruction forms it executed.
+///
+/// # Arguments
+/// * `rom` - The ROM image to load at 0x200.
+/// * `max_cycles` - The maximum number of cycles to run, if the program doesn't halt before that.
+///
+/// # Returns
+/// The executions of each instruction form.
+pub fn run_rom_coverage(
+    rom: &[u8],
+    max_cycles: usize,
+) -> Result<stats::Coverage, Box<dyn core::error::Error>> {
+    let mut emulator = emulator::Emulator::new(state::State::try_from(rom)?);
+    emulator.coverage = Some(stats::Coverage::default());
+    run_headless_until(&mut emulator, max_cycles, true)?;
+    Ok(emulator.coverage.unwrap_or_default())
+}
+
+/// Benchmark the interpreter by running a ROM headless and unthrottled, like `run_rom_headless`, but without halting on
+/// self-jumps, so the whole cycle budget is spent unless the program halts.
+///
+/// # Arguments
+/// * `rom` - The ROM image to load at 0x200.
+/// * `cycles` - The number of cycles to run.
+///
+/// # Returns
+/// A tuple containing the counters of the run, and the wall time it took.
+#[cfg(feature = "terminal")]
+pub fn bench_rom(
+    rom: &[u8],
+    cycles: usize,
+) -> Result<(stats::RunStats, Duration), Box<dyn std::error::Error>> {
+    let start = SystemTime::now();
+    let emulator = run_headless(rom, cycles, false)?;
+    Ok((emulator.stats, elapsed_time(&start)))
+}
+
+/// Run a ROM in turbo mode without input, until it halts or `max_cycles` is reached.
+fn run_headless(
+    rom: &[u8],
+    max_cycles: usize,
+    halt_on_infinite_loop: bool,
+) -> Result<emulator::Emulator, Box<dyn core::error::Error>> {
+    let mut emulator = emulator::Emulator::new(state::State::try_from(rom)?);
+    run_headless_until(&mut emulator, max_cycles, halt_on_infinite_loop)?;
+    Ok(emulator)
+}
+
+/// Run a loaded emulator in turbo mode without input, until it halts or `max_cycles` is reached.
+fn run_headless_until(
+    emulator: &mut emulator::Emulator,
+    max_cycles: usize,
+    halt_on_infinite_loop: bool,
+) -> Result<(), Box<dyn core::error::Error>> {
+    emulator.turbo = true;
+    emulator.halt_on_infinite_loop = halt_on_infinite_loop;
+
+    for _ in 0..max_cycles {
+        if let Some(exit_code) = emulator.step()? {
+            debug!("Program halted with exit code {}", exit_code);
+            break;
+        }
+    }
+
+    Ok(())
+}
+
 /// Returns the elapsed time since the given SystemTime.
 /// If the SystemTime is in the future, returns a Duration of zero.
 ///
@@ -133,6 +676,7 @@ pub fn run_rom(rom_path: PathBuf) -> Result<usize, Box<dyn std::error::Error>> {
 ///
 /// # Returns
 /// A Duration representing the elapsed time since `t`.
+#[cfg(feature = "terminal")]
 fn elapsed_time(t: &SystemTime) -> Duration {
     t.elapsed().unwrap_or(Duration::from_secs(0))
 }
@@ -140,6 +684,7 @@ fn elapsed_time(t: &SystemTime) -> Duration {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Duration;
 
     #[test]
     fn instruction_clear_screen() {
@@ -154,16 +699,14 @@ mod tests {
 
         decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
 
-        assert_eq!(state.screen, [false; constants::WIDTH * constants::HEIGHT]);
+        assert!(state.screen.iter().all(|&pixel| !pixel));
         assert_eq!(state.pc, 0x202);
     }
 
     #[test]
     fn instruction_jump() {
-        let mut state = state::State::new();
         // 0x1NNN: Jump to address NNN
-        state.memory[0x200] = 0x12;
-        state.memory[0x201] = 0x34;
+        let mut state = state::State::new().with_memory_at(0x200, &[0x12, 0x34]);
 
         decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
 
@@ -172,15 +715,9 @@ mod tests {
 
     #[test]
     fn instruction_call_and_return() {
-        let mut state = state::State::new();
-
-        // 0x2NNN: Execute subroutine starting at address NNN
-        state.memory[0x200] = 0x23; // CALL 0x345
-        state.memory[0x201] = 0x45; // CALL 0x345
-
-        // 0x00EE: Return from subroutine
-        state.memory[0x345] = 0x00; // RET instruction high byte
-        state.memory[0x346] = 0xEE; // RET instruction low byte
+        let mut state = state::State::new()
+            .with_memory_at(0x200, &[0x23, 0x45]) // 0x2NNN: CALL 0x345
+            .with_memory_at(0x345, &[0x00, 0xEE]); // 0x00EE: Return from subroutine
 
         decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
 
@@ -202,7 +739,132 @@ mod tests {
         state.memory[0x200] = 0x00; // RET instruction high byte
         state.memory[0x201] = 0xEE; // RET instruction low byte
 
-        decoder::decode_and_execute(&mut state).expect_err("Should have caused a stack underflow");
+        let err = decoder::decode_and_execute(&mut state)
+            .expect_err("Should have caused a stack underflow");
+        assert_eq!(err, error::Chip8Error::StackUnderflow);
+    }
+
+    #[test]
+    fn instruction_unknown_in_strict_mode() {
+        for strict in [false, true] {
+            let mut state = state::State::new();
+            state.strict = strict;
+            state.memory[0x200] = 0x80; // Unknown 0x8XY8
+            state.memory[0x201] = 0x08; // Unknown 0x8XY8
+
+            let result = decoder::decode_and_execute(&mut state);
+
+            if strict {
+                assert_eq!(
+                    result,
+                    Err(error::Chip8Error::UnknownOpcode {
+                        opcode: 0x8008,
+                        pc: 0x200
+                    })
+                );
+            } else {
+                assert_eq!(result, Ok(None));
+            }
+        }
+    }
+
+    #[test]
+    fn halt_on_first_unknown_opcode() {
+        let rom = [
+            0x60, 0x01, // LD V0, 0x01
+            0x70, 0x01, // ADD V0, 0x01
+            0xE0, 0x00, // Unknown 0xEX00
+            0x80, 0x08, // Unknown 0x8XY8
+        ];
+        let mut emulator = emulator::Emulator::new(state::State::try_from(&rom[..]).unwrap());
+        emulator.state.strict = true;
+
+        let err = (0..10)
+            .find_map(|_| emulator.step().err())
+            .expect("Should have halted on the unknown opcode");
+
+        assert_eq!(
+            err.downcast_ref::<error::Chip8Error>(),
+            Some(&error::Chip8Error::UnknownOpcode {
+                opcode: 0xE000,
+                pc: 0x204
+            })
+        );
+        assert_eq!(emulator.state.v[0], 0x02);
+    }
+
+    #[test]
+    fn instruction_call_stack_overflow() {
+        for max_stack_depth in [Some(16), None] {
+            let mut state = state::State::new();
+            state.max_stack_depth = max_stack_depth;
+            state.memory[0x200] = 0x22; // CALL 0x200
+            state.memory[0x201] = 0x00; // CALL 0x200
+
+            for _ in 0..16 {
+                decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+            }
+            let result = decoder::decode_and_execute(&mut state);
+
+            if max_stack_depth.is_some() {
+                assert_eq!(result, Err(error::Chip8Error::StackOverflow));
+                assert_eq!(state.stack.len(), 16);
+            } else {
+                assert_eq!(result, Ok(None));
+                assert_eq!(state.stack.len(), 17);
+            }
+        }
+    }
+
+    #[test]
+    fn instruction_halt_inside_subroutine() {
+        let mut state = state::State::new();
+        state.memory[0x200] = 0x23; // CALL 0x300
+        state.memory[0x201] = 0x00; // CALL 0x300
+        state.memory[0x300] = 0xF3; // HALT 3
+        state.memory[0x301] = 0xFF; // HALT 3
+
+        let mut emulator = emulator::Emulator::new(state);
+        let exit_code = loop {
+            if let Some(exit_code) = emulator.step().expect("Failed to execute instruction") {
+                break exit_code;
+            }
+        };
+
+        assert_eq!(exit_code, 3);
+        assert_eq!(emulator.state.cycles, 2);
+        assert_eq!(emulator.state.stack, [0x202]);
+    }
+
+    #[test]
+    fn instruction_save_and_restore_flags() {
+        for x in [0x0, 0x3, 0x7, 0xF] {
+            let mut state = state::State::new();
+            for (i, v) in state.v.iter_mut().enumerate() {
+                *v = 0x10 + i as u8;
+            }
+
+            // 0xFX75: Store registers V0 through VX in the RPL user flags
+            state.memory[0x200] = 0xF0 | x; // LD R, VX
+            state.memory[0x201] = 0x75; // LD R, VX
+            // 0xFX85: Read registers V0 through VX from the RPL user flags
+            state.memory[0x202] = 0xF0 | x; // LD VX, R
+            state.memory[0x203] = 0x85; // LD VX, R
+
+            decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+            let n = (x as usize).min(7) + 1;
+            assert_eq!(state.flags[..n], state.v[..n]);
+            assert!(state.flags[n..].iter().all(|&f| f == 0));
+
+            state.v = [0; 16];
+            decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+            for i in 0..16 {
+                let expected = if i < n { 0x10 + i as u8 } else { 0 };
+                assert_eq!(state.v[i], expected);
+            }
+        }
     }
 
     #[test]
@@ -230,4 +892,1616 @@ mod tests {
 
         assert_eq!(state.pc, 0x202); // Should not have skipped the next instruction
     }
+
+    #[test]
+    fn instruction_store_and_load_registers_advance_i_once() {
+        let mut state = state::State::new();
+        state.i = 0x300;
+        state.v[..3].copy_from_slice(&[1, 2, 3]);
+        state.memory[0x200] = 0xF2; // LD [I], V2
+        state.memory[0x201] = 0x55;
+        state.memory[0x202] = 0xF2; // LD V2, [I]
+        state.memory[0x203] = 0x65;
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.memory[0x300..0x304], [1, 2, 3, 0]);
+        assert_eq!(state.i, 0x303);
+
+        state.i = 0x300;
+        state.v[..3].fill(0);
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.v[..3], [1, 2, 3]);
+        assert_eq!(state.i, 0x303);
+    }
+
+    #[test]
+    fn unused_memory_prefill_keeps_the_fonts() {
+        let state = state::State::new();
+        // The glyph for F is the last of the 80 byte font
+        assert_eq!(state.memory[0x04B..0x050], [0xF0, 0x80, 0xF0, 0x80, 0x80]);
+    }
+
+    #[test]
+    fn instruction_draw_sprite_xor_and_collision() {
+        let mut state = state::State::new();
+        // 0xDXYN: Draw the N byte sprite at I, at coordinates VX, VY
+        state.v[0] = 2;
+        state.v[1] = 3;
+        state.i = 0x300;
+        state.memory[0x300] = 0b1100_0000;
+        state.memory[0x200] = 0xD0; // DRW V0, V1, 1
+        state.memory[0x201] = 0x11; // DRW V0, V1, 1
+        state.memory[0x202] = 0xD0; // DRW V0, V1, 1
+        state.memory[0x203] = 0x11; // DRW V0, V1, 1
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+        assert!(state.screen[3 * constants::WIDTH + 2]);
+        assert!(state.screen[3 * constants::WIDTH + 3]);
+        assert_eq!(state.screen.iter().filter(|&&pixel| pixel).count(), 2);
+        assert_eq!(state.v[0xF], 0);
+
+        // Drawing the same sprite again erases it, and reports the collision
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+        assert!(state.screen.iter().all(|&pixel| !pixel));
+        assert_eq!(state.v[0xF], 1);
+    }
+
+    #[test]
+    fn instruction_draw_sprite_wraps_start_and_clips() {
+        let mut state = state::State::new();
+        // The starting position wraps around, but the sprite is clipped at the right and bottom edges
+        state.v[0] = constants::WIDTH as u8 + 62;
+        state.v[1] = constants::HEIGHT as u8 + 31;
+        state.i = 0x300;
+        state.memory[0x300] = 0b1110_0000;
+        state.memory[0x301] = 0b1110_0000;
+        state.memory[0x200] = 0xD0; // DRW V0, V1, 2
+        state.memory[0x201] = 0x12; // DRW V0, V1, 2
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+        assert!(state.screen[31 * constants::WIDTH + 62]);
+        assert!(state.screen[31 * constants::WIDTH + 63]);
+        assert_eq!(state.screen.iter().filter(|&&pixel| pixel).count(), 2);
+        assert_eq!(state.v[0xF], 0);
+    }
+
+    #[test]
+    fn headless_draws_and_halts() {
+        let rom = [
+            0xA0, 0x00, // LD I, 0x000 (the "0" character sprite)
+            0xD0, 0x05, // DRW V0, V0, 5
+            0xF0, 0xFF, // HALT 0
+        ];
+
+        let screen = run_rom_headless(&rom, 1000).expect("Failed to run ROM");
+
+        let row = |y: usize| &screen[y * constants::WIDTH..y * constants::WIDTH + 8];
+        assert_eq!(row(0), [true, true, true, true, false, false, false, false]);
+        assert_eq!(
+            row(1),
+            [true, false, false, true, false, false, false, false]
+        );
+        assert_eq!(row(4), [true, true, true, true, false, false, false, false]);
+        assert_eq!(screen.iter().filter(|&&p| p).count(), 14);
+    }
+
+    #[test]
+    fn headless_stops_after_max_cycles() {
+        // Executes NOPs until it reaches the jump back to 0x200 at 0xE9E
+        let rom = include_bytes!("../roms/blank.rom");
+
+        let screen = run_rom_headless(rom, 10_000).expect("Failed to run ROM");
+
+        assert_eq!(screen, [false; constants::WIDTH * constants::HEIGHT]);
+    }
+
+    #[test]
+    fn trap_uninitialized_memory_halts_runaway_program() {
+        let mut state = state::State::new();
+        state.trap_uninitialized_memory();
+        state.load_rom(&[0x60, 0x2A]).expect("Failed to load ROM"); // LD V0, 0x2A
+        let mut emulator = emulator::Emulator::new(state);
+
+        assert_eq!(
+            emulator.step().expect("Failed to execute instruction"),
+            None
+        );
+        // Runs into HALT 15 after the ROM
+        assert_eq!(
+            emulator.step().expect("Failed to execute instruction"),
+            Some(15)
+        );
+        assert_eq!(emulator.state.memory[..5], font::SMALL_FONT[..5]);
+    }
+
+    #[test]
+    fn headless_halts_on_infinite_loop() {
+        let rom = [
+            0xA0, 0x00, // LD I, 0x000 (the "0" character sprite)
+            0xD0, 0x05, // DRW V0, V0, 5
+            0x12, 0x04, // JP 0x204
+        ];
+        let mut emulator = emulator::Emulator::new(state::State::try_from(&rom[..]).unwrap());
+        emulator.turbo = true;
+        emulator.halt_on_infinite_loop = true;
+        emulator.infinite_loop_limit = 3;
+
+        let cycles = (1..100)
+            .find(|_| emulator.step().expect("Failed to execute instruction") == Some(0))
+            .expect("Should have halted");
+
+        assert!(emulator.is_idle());
+        assert_eq!(emulator.state.pc, 0x204);
+        assert_eq!(emulator.stats.instructions, 5); // 2 instructions and 3 self-jumps
+        assert!(cycles > 5); // Including the display wait
+        assert_eq!(
+            run_rom_headless(&rom, usize::MAX)
+                .expect("Failed to run ROM")
+                .iter()
+                .filter(|&&p| p)
+                .count(),
+            14
+        );
+    }
+
+    #[test]
+    fn instruction_jump_with_offset_wraps() {
+        for (jump_uses_vx, expected) in [(false, 0x0EF), (true, 0x000)] {
+            let mut state = state::State::new();
+            state.quirks.jump_uses_vx = jump_uses_vx;
+            state.v[0] = 0xFF;
+            state.v[0xF] = 0x10;
+            state.memory[0x200] = 0xBF; // JP V0, 0xFF0
+            state.memory[0x201] = 0xF0; // JP V0, 0xFF0
+
+            decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+            assert_eq!(state.pc, expected);
+        }
+    }
+
+    #[test]
+    fn instruction_or_vf_reset_quirk() {
+        for (quirks, expected_vf) in [
+            (quirks::Quirks::chip8(), 0),
+            (quirks::Quirks::schip(), 0x42),
+        ] {
+            let mut state = state::State::new();
+            state.quirks = quirks;
+            state.v[0] = 0b0000_1100;
+            state.v[1] = 0b0000_0011;
+            state.v[0xF] = 0x42;
+            state.memory[0x200] = 0x80; // OR V0, V1
+            state.memory[0x201] = 0x11; // OR V0, V1
+
+            decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+            assert_eq!(state.v[0], 0b0000_1111);
+            assert_eq!(state.v[0xF], expected_vf);
+        }
+    }
+
+    #[test]
+    fn instruction_shift_right_quirk() {
+        // 0x8XY6: Store the value of register VY shifted right one bit in register VX
+        for (shift_uses_vy, expected) in [(true, 0b0000_0011), (false, 0b0100_0000)] {
+            let mut state = state::State::new();
+            state.quirks.shift_uses_vy = shift_uses_vy;
+            state.v[0] = 0b1000_0000;
+            state.v[1] = 0b0000_0111;
+            state.memory[0x200] = 0x80; // SHR V0, V1
+            state.memory[0x201] = 0x16; // SHR V0, V1
+
+            decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+            assert_eq!(state.v[0], expected);
+            assert_eq!(state.v[0xF], if shift_uses_vy { 1 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn display_wait_in_turbo_mode_draws_once_per_frame() {
+        let cycles_per_frame = (constants::CLOCK_FREQ / constants::TIMER_FREQ) as usize;
+
+        let mut state = state::State::new();
+        state.quirks.display_wait = true;
+        state.memory[0x200..0x206].copy_from_slice(&[
+            0xD0, 0x01, // DRW V0, V0, 1
+            0x71, 0x01, // ADD V1, 1
+            0x12, 0x00, // JP 0x200
+        ]);
+        let mut emulator = emulator::Emulator::new(state);
+        emulator.turbo = true;
+
+        let mut draws = 0;
+        for frame in 1..=3 {
+            for _ in 0..cycles_per_frame {
+                let was_waiting = emulator.state.waiting_for_vblank;
+                emulator.step().expect("Failed to execute instruction");
+                if !was_waiting && emulator.state.waiting_for_vblank {
+                    draws += 1;
+                }
+            }
+
+            // The last cycle of each frame is a simulated vertical blank, which ends the wait
+            assert!(!emulator.state.waiting_for_vblank);
+            assert_eq!(draws, frame);
+        }
+    }
+
+    #[test]
+    fn display_wait_consumes_rest_of_frame() {
+        assert!(quirks::Quirks::chip8().display_wait);
+        assert!(!quirks::Quirks::schip().display_wait);
+
+        let rom = [
+            0xD0, 0x01, // DRW V0, V0, 1
+            0x71, 0x01, // ADD V1, 1
+        ];
+        let mut emulator = emulator::Emulator::new(state::State::try_from(&rom[..]).unwrap());
+        emulator.state.quirks.display_wait = true;
+
+        emulator.step().expect("Failed to execute instruction");
+        for _ in 0..10 {
+            emulator.step().expect("Failed to execute instruction");
+        }
+        assert_eq!((emulator.state.pc, emulator.state.v[1]), (0x202, 0));
+        assert_eq!(emulator.stats.instructions, 1);
+
+        emulator.vblank();
+        emulator.step().expect("Failed to execute instruction");
+        assert_eq!((emulator.state.pc, emulator.state.v[1]), (0x204, 1));
+    }
+
+    #[test]
+    fn cycles_until_timer_tick() {
+        let mut state = state::State::new();
+
+        // 600Hz means a timer tick every 10 cycles
+        assert_eq!(state.cycles_until_timer_tick(600), 10);
+
+        state.cycles = 23;
+        assert_eq!(state.cycles_until_timer_tick(600), 7);
+
+        state.cycles = 29;
+        assert_eq!(state.cycles_until_timer_tick(600), 1);
+
+        // Clocks slower than the timers tick every cycle
+        assert_eq!(state.cycles_until_timer_tick(30), 1);
+    }
+
+    #[test]
+    fn instruction_random_is_reproducible() {
+        let run = |seed: u64| {
+            let mut state = state::State::with_seed(seed);
+            let mut values = Vec::new();
+            for _ in 0..8 {
+                // 0xCXNN: Set VX to a random number with a mask of NN
+                state.pc = 0x200;
+                state.memory[0x200] = 0xC0; // RND V0, 0xFF
+                state.memory[0x201] = 0xFF; // RND V0, 0xFF
+                decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+                values.push(state.v[0]);
+            }
+            values
+        };
+
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+    }
+
+    #[test]
+    fn rom_trailer_is_stripped() {
+        let program = [0x00, 0xE0, 0x12, 0x00];
+
+        // CRC-16/CCITT-FALSE of the program
+        let crc = [0x41, 0xE0];
+        let rom = [&program[..], &crc[..]].concat();
+        assert_eq!(state::strip_trailer(&rom), (&program[..], true));
+
+        // Length of the program
+        let rom = [&program[..], &[0x00, 0x04]].concat();
+        assert_eq!(state::strip_trailer(&rom), (&program[..], true));
+
+        // Neither, so a warning is logged, but the trailer is still stripped
+        let rom = [&program[..], &[0xBA, 0xD0]].concat();
+        assert_eq!(state::strip_trailer(&rom), (&program[..], false));
+    }
+
+    #[test]
+    fn input_sampling_point() {
+        for (sampling, skipped) in [
+            (input::InputSampling::FrameStart, false),
+            (input::InputSampling::Continuous, true),
+        ] {
+            let mut state = state::State::new();
+            state.v[0] = 0x5;
+            state.memory[0x208..0x20E].copy_from_slice(&[
+                0xE0, 0x9E, // SKP V0
+                0x61, 0x01, // LD V1, 1
+                0x62, 0x02, // LD V2, 2
+            ]);
+            let mut emulator = emulator::Emulator::new(state);
+            emulator.input_sampling = sampling;
+
+            // The key is pressed in the middle of the frame, after the first poll at frame start
+            let mut input = input::ScriptedInput::new(vec![(2..100, 0x5)]);
+            for _ in 0..6 {
+                emulator
+                    .step_with_input(&mut input)
+                    .expect("Failed to execute instruction");
+            }
+
+            if skipped {
+                assert_eq!((emulator.state.v[1], emulator.state.v[2]), (0, 2));
+            } else {
+                assert_eq!((emulator.state.v[1], emulator.state.v[2]), (1, 0));
+            }
+        }
+    }
+
+    #[test]
+    fn display_hook_sees_each_change() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let rom = [
+            0x00, 0xE0, // CLS
+            0xA2, 0x0C, // LD I, 0x20C
+            0xD0, 0x01, // DRW V0, V0, 1
+            0xA2, 0x0D, // LD I, 0x20D
+            0xD0, 0x01, // DRW V0, V0, 1
+            0x12, 0x0A, // JP 0x20A
+            0x80, 0x00, // Sprites
+        ];
+        let mut emulator = emulator::Emulator::new(state::State::try_from(&rom[..]).unwrap());
+        emulator.state.quirks.display_wait = false;
+
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        let hook_frames = Rc::clone(&frames);
+        emulator.set_display_hook(Box::new(move |screen| {
+            hook_frames.borrow_mut().push(screen.to_vec());
+        }));
+
+        for _ in 0..10 {
+            emulator.step().expect("Failed to execute instruction");
+        }
+
+        // The clear and the first sprite, while the empty sprite changes nothing
+        let frames = frames.borrow();
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].iter().all(|&on| !on));
+        assert_eq!(frames[1].iter().filter(|&&on| on).count(), 1);
+        assert!(frames[1][0]);
+    }
+
+    #[test]
+    fn trace_hook_sees_each_instruction() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut state = state::State::new();
+        state.memory[0x200..0x206].copy_from_slice(&[
+            0x60, 0x2A, // LD V0, 0x2A
+            0xF1, 0x0A, // LD V1, K
+            0x12, 0x00, // JP 0x200
+        ]);
+        let mut emulator = emulator::Emulator::new(state);
+
+        let trace = Rc::new(RefCell::new(Vec::new()));
+        let hook_trace = Rc::clone(&trace);
+        emulator.set_trace_hook(Box::new(move |pc, opcode, state| {
+            hook_trace.borrow_mut().push((pc, opcode, state.v[0]));
+        }));
+
+        // The third cycle waits for a key press, so no instruction is traced
+        for _ in 0..3 {
+            emulator.step().expect("Failed to execute instruction");
+        }
+
+        assert_eq!(
+            *trace.borrow(),
+            [(0x200, 0x602A, 0x00), (0x202, 0xF10A, 0x2A)]
+        );
+    }
+
+    #[test]
+    fn instruction_skip_if_key_held_across_timeout() {
+        let mut state = state::State::new();
+        let t0 = Duration::ZERO;
+
+        // 0xEX9E: Skip the following instruction if the key stored in VX is pressed
+        state.v[0] = 0x5;
+        for addr in (0x200..0x20C).step_by(2) {
+            state.memory[addr] = 0xE0; // SKP V0
+            state.memory[addr + 1] = 0x9E; // SKP V0
+        }
+
+        state.poll_key(1 << 0x5, false, t0);
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.pc, 0x204); // Key pressed
+
+        // The key is re-reported by auto-repeat before it times out, so it is still held after the original timeout
+        state.poll_key(0, false, t0 + Duration::from_millis(80));
+        state.poll_key(1 << 0x5, false, t0 + Duration::from_millis(90));
+        state.poll_key(0, false, t0 + Duration::from_millis(150));
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.pc, 0x208); // Key still held
+
+        // Checking the key doesn't consume it
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.pc, 0x20C); // Key still held
+
+        // No more reports, so the key times out
+        state.poll_key(0, false, t0 + Duration::from_millis(250));
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.pc, 0x20E); // Key released
+    }
+
+    #[test]
+    fn instruction_skip_if_key_held_until_released() {
+        let mut state = state::State::new();
+        let t0 = Duration::ZERO;
+
+        // 0xEX9E: Skip the following instruction if the key stored in VX is pressed
+        state.v[0] = 0x5;
+        for addr in (0x200..0x20C).step_by(2) {
+            state.memory[addr] = 0xE0; // SKP V0
+            state.memory[addr + 1] = 0x9E; // SKP V0
+        }
+
+        // With release events, a held key doesn't time out
+        state.poll_key(1 << 0x5, true, t0);
+        state.poll_key(1 << 0x5, true, t0 + Duration::from_secs(1));
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.pc, 0x204); // Key still held
+
+        // ... but is released right away
+        state.poll_key(0, true, t0 + Duration::from_millis(1010));
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.pc, 0x206); // Key released
+    }
+
+    #[test]
+    fn hires_mode_draws_bottom_half() {
+        let mut state = state::State::new();
+        state.set_hires(true);
+
+        assert_eq!(state.height(), 64);
+        assert_eq!(state.visible_screen().len(), 64 * 64);
+
+        state.v[0] = 8; // X
+        state.v[1] = 40; // Y
+        state.i = constants::SMALL_FONT_OFFSET; // Sprite for "0", top row 0xF0
+        state.memory[0x200] = 0xD0; // DRW V0, V1, 1
+        state.memory[0x201] = 0x11; // DRW V0, V1, 1
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+        // Below the 32 rows of the standard mode, so no wrap-around to row 8
+        assert!(state.screen[40 * constants::WIDTH + 8]);
+        assert!(!state.screen[8 * constants::WIDTH + 8]);
+    }
+
+    #[test]
+    fn hires_mode_detected_from_init_jump() {
+        let state = state::State::try_from(&[0x12, 0x60][..]).unwrap();
+
+        assert!(state.hires);
+        assert_eq!(state.pc, constants::HIRES_ENTRY_POINT);
+    }
+
+    #[test]
+    fn load_rom_at_entry_point() {
+        let mut state = state::State::new();
+        let font = state.memory[..0x200].to_vec();
+
+        state
+            .load_rom_at(&[0x60, 0x2A], 0x600)
+            .expect("Failed to load ROM");
+
+        assert_eq!(state.pc, 0x600);
+        assert_eq!(state.memory[0x600..0x602], [0x60, 0x2A]);
+        assert_eq!(state.memory[..0x200], font[..]);
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.v[0], 0x2A);
+    }
+
+    #[test]
+    fn load_rom_at_rejects_bad_entry_points() {
+        let mut state = state::State::new();
+
+        // Over the fonts
+        assert!(state.load_rom_at(&[0x60, 0x2A], 0x100).is_err());
+        // Past the end of memory
+        assert!(state.load_rom_at(&[0x60, 0x2A], 0xFFF).is_err());
+        assert!(state.load_rom_at(&[0x60, 0x2A], 0x1000).is_err());
+        assert!(state.load_rom_at(&[0x60, 0x2A], 0xFFE).is_ok());
+    }
+
+    #[test]
+    fn instruction_skip_with_multiple_keys_held() {
+        let mut state = state::State::new();
+        let t0 = Duration::ZERO;
+
+        state.v[0] = 0x4; // Left
+        state.v[1] = 0x6; // Fire
+        state.v[2] = 0x5;
+        state.memory[0x200] = 0xE0; // SKP V0
+        state.memory[0x201] = 0x9E; // SKP V0
+        state.memory[0x204] = 0xE1; // SKP V1
+        state.memory[0x205] = 0x9E; // SKP V1
+        state.memory[0x208] = 0xE2; // SKNP V2
+        state.memory[0x209] = 0xA1; // SKNP V2
+
+        state.poll_key(1 << 0x4 | 1 << 0x6, true, t0);
+
+        for _ in 0..3 {
+            decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        }
+        assert_eq!(state.pc, 0x20C); // Both keys held, and key 5 not held
+    }
+
+    #[test]
+    fn instruction_wait_for_key_latches_newly_pressed_key() {
+        let mut state = state::State::new();
+        let t0 = Duration::ZERO;
+
+        state.memory[0x200] = 0xF3; // LD V3, K
+        state.memory[0x201] = 0x0A; // LD V3, K
+
+        // Key 2 is already held when the wait starts, so it doesn't satisfy it
+        state.poll_key(1 << 0x2, true, t0);
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        state.poll_key(1 << 0x2, true, t0);
+        assert_eq!(state.waiting_for_keypress, Some(3));
+
+        state.poll_key(1 << 0x2 | 1 << 0x9, true, t0);
+        assert_eq!(state.waiting_for_keypress, None);
+        assert_eq!(state.v[3], 0x9);
+    }
+
+    #[test]
+    fn instruction_wait_for_key_latches_repeated_tap_without_releases() {
+        let mut state = state::State::new();
+        let t0 = Duration::ZERO;
+        state.key_timeout = Duration::from_millis(250);
+
+        state.memory[0x200] = 0xF3; // LD V3, K
+        state.memory[0x201] = 0x0A;
+
+        // Key 2 was tapped before the wait, and hasn't timed out when it's tapped again
+        state.poll_key(1 << 0x2, false, t0);
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        state.poll_key(0, false, t0 + Duration::from_millis(100));
+        assert_eq!(state.keys, 1 << 0x2);
+        assert_eq!(state.waiting_for_keypress, Some(3));
+
+        state.poll_key(1 << 0x2, false, t0 + Duration::from_millis(200));
+        assert_eq!(state.waiting_for_keypress, None);
+        assert_eq!(state.v[3], 0x2);
+    }
+
+    #[test]
+    fn wait_for_key_polls_every_cycle() {
+        let rom = [
+            0xF1, 0x0A, // LD V1, K
+            0x12, 0x02, // JP 0x202
+        ];
+        let mut emulator = emulator::Emulator::new(state::State::try_from(&rom[..]).unwrap());
+
+        // A short press in the middle of the frame, between the sampling points at frame start
+        let mut input = input::ScriptedInput::new(vec![(5..7, 0x5)]);
+        for _ in 0..10 {
+            emulator
+                .step_with_input(&mut input)
+                .expect("Failed to execute instruction");
+        }
+
+        assert_eq!(emulator.state.waiting_for_keypress, None);
+        assert_eq!(emulator.state.v[1], 0x5);
+    }
+
+    #[test]
+    fn stats_count_cycles_and_calls() {
+        let rom = [
+            0x22, 0x06, // CALL 0x206
+            0x22, 0x06, // CALL 0x206
+            0xF0, 0xFF, // HALT 0
+            0xD0, 0x01, // DRW V0, V0, 1
+            0x00, 0xEE, // RET
+        ];
+        let mut emulator = emulator::Emulator::new(state::State::try_from(&rom[..]).unwrap());
+        emulator.turbo = true;
+        emulator.state.quirks.display_wait = false;
+
+        while emulator
+            .step()
+            .expect("Failed to execute instruction")
+            .is_none()
+        {}
+
+        assert_eq!(emulator.stats.cycles, 7);
+        assert_eq!(emulator.stats.instructions, 7);
+        assert_eq!(emulator.stats.calls, 2);
+        assert_eq!(emulator.stats.draws, 2);
+        assert_eq!(emulator.stats.peak_stack_depth, 1);
+        assert_eq!(emulator.cycles(), 7);
+        assert_eq!(emulator.opcode_stats()[0x2], 2);
+        assert_eq!(emulator.opcode_stats()[0xD], 2);
+
+        emulator.reset_stats();
+
+        assert_eq!(emulator.cycles(), 0);
+        assert_eq!(emulator.opcode_stats(), [0; 16]);
+        assert_eq!(emulator.state.cycles, 7);
+    }
+
+    #[test]
+    fn paused_emulator_does_not_run() {
+        let rom = [
+            0x70, 0x01, // ADD V0, 0x01
+            0x12, 0x00, // JP 0x200
+        ];
+        let mut emulator = emulator::Emulator::new(state::State::try_from(&rom[..]).unwrap());
+        emulator.set_delay_timer(10);
+
+        emulator.pause();
+        assert!(emulator.is_paused());
+        for _ in 0..10 {
+            emulator.step().expect("Failed to execute instruction");
+            emulator.vblank();
+        }
+        assert_eq!(emulator.state.v[0], 0);
+        assert_eq!(emulator.state.pc, 0x200);
+        assert_eq!(emulator.delay_timer(), 10);
+
+        emulator.resume();
+        assert!(!emulator.is_paused());
+        emulator.step().expect("Failed to execute instruction");
+        emulator.vblank();
+        assert_eq!(emulator.state.v[0], 1);
+        assert_eq!(emulator.delay_timer(), 9);
+    }
+
+    #[test]
+    fn timer_accessors() {
+        let rom = [
+            0xF0, 0x07, // LD V0, DT
+            0xF1, 0x18, // LD ST, V1
+        ];
+        let mut emulator = emulator::Emulator::new(state::State::try_from(&rom[..]).unwrap());
+        emulator.state.v[1] = 3;
+
+        emulator.set_delay_timer(0x2A);
+        emulator.step().expect("Failed to execute instruction");
+        assert_eq!(emulator.state.v[0], 0x2A);
+
+        assert!(!emulator.is_beeping());
+        emulator.step().expect("Failed to execute instruction");
+        assert_eq!(emulator.sound_timer(), 3);
+        assert!(emulator.is_beeping());
+
+        emulator.set_sound_timer(0);
+        assert!(!emulator.is_beeping());
+    }
+
+    #[test]
+    fn single_step_while_paused() {
+        let rom = [
+            0x60, 0x2A, // LD V0, 0x2A
+            0xD0, 0x01, // DRW V0, V0, 1
+            0x70, 0x01, // ADD V0, 0x01
+        ];
+        let mut emulator = emulator::Emulator::new(state::State::try_from(&rom[..]).unwrap());
+        emulator.pause();
+
+        assert_eq!(emulator.next_instruction(), (0x200, 0x602A));
+        emulator
+            .single_step()
+            .expect("Failed to execute instruction");
+        assert_eq!(emulator.state.v[0], 0x2A);
+
+        // The display wait after drawing doesn't block single-stepping
+        emulator
+            .single_step()
+            .expect("Failed to execute instruction");
+        emulator
+            .single_step()
+            .expect("Failed to execute instruction");
+        assert_eq!(emulator.state.v[0], 0x2B);
+        assert!(emulator.is_paused());
+    }
+
+    #[test]
+    fn pixel_accessors() {
+        let mut state = state::State::new();
+
+        state.set_pixel(3, 1, true);
+
+        assert!(state.pixel(3, 1));
+        assert!(state.screen[constants::WIDTH + 3]);
+        let text = state.screen_to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), constants::HEIGHT);
+        assert_eq!(lines[1].chars().position(|c| c == '█'), Some(3));
+        assert_eq!(text.matches('█').count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "outside of the 64x32 screen")]
+    fn pixel_out_of_bounds() {
+        state::State::new().pixel(0, constants::HEIGHT);
+    }
+
+    #[test]
+    fn instruction_big_font_sprite() {
+        let mut state = state::State::new();
+        state.v[0] = 0x1;
+        state.memory[0x200] = 0xF0; // LD HF, V0
+        state.memory[0x201] = 0x30; // LD HF, V0
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+        assert_eq!(state.i, constants::LARGE_FONT_OFFSET + 10);
+        assert_eq!(state.memory[state.i..state.i + 10], font::BIG_FONT[10..20]);
+    }
+
+    #[test]
+    fn custom_font_replaces_small_glyphs() {
+        let mut state = state::State::new();
+        let font = [0xAA; 80];
+
+        state.set_font(&font).expect("Failed to set font");
+
+        assert_eq!(state.memory[..80], font);
+        assert_eq!(state.memory[80..90], font::BIG_FONT[..10]);
+        assert_eq!(
+            state.set_font(&font[..79]),
+            Err(error::Chip8Error::InvalidFont {
+                expected: 80,
+                actual: 79
+            })
+        );
+    }
+
+    #[test]
+    fn long_addressing_loads_16_bit_address() {
+        let mut state = state::State::with_memory_size(constants::XO_CHIP_MEMORY_SIZE);
+        state.quirks = quirks::Quirks::xochip();
+        state.memory[0x200..0x20A].copy_from_slice(&[
+            0xF0, 0x00, 0x12, 0x34, // LD I, LONG 0x1234
+            0x30, 0x00, // SE V0, 0x00
+            0xF0, 0x00, 0xAB, 0xCD, // LD I, LONG 0xABCD
+        ]);
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!((state.i, state.pc), (0x1234, 0x204));
+
+        // Skips the whole four bytes
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!((state.i, state.pc), (0x1234, 0x20A));
+
+        // Without the quirk, F000 is an unknown opcode and the address runs as an instruction
+        let mut state = state::State::new();
+        state.memory[0x200..0x204].copy_from_slice(&[0xF0, 0x00, 0x12, 0x34]);
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!((state.i, state.pc), (0x000, 0x202));
+        state.strict = true;
+        state.pc = 0x200;
+        assert!(decoder::decode_and_execute(&mut state).is_err());
+    }
+
+    #[test]
+    fn long_addressing_of_0xf000_steps_over_the_address_only() {
+        let mut state = state::State::with_memory_size(constants::XO_CHIP_MEMORY_SIZE);
+        state.quirks = quirks::Quirks::xochip();
+        state.memory[0x200..0x206].copy_from_slice(&[
+            0xF0, 0x00, 0xF0, 0x00, // LD I, LONG 0xF000
+            0x60, 0x42, // LD V0, 0x42
+        ]);
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!((state.i, state.pc), (0xF000, 0x204));
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.v[0], 0x42);
+    }
+
+    #[test]
+    fn large_memory_map_uses_16_bit_addresses() {
+        let mut state = state::State::with_memory_size(constants::XO_CHIP_MEMORY_SIZE);
+        state.i = 0xFFF;
+        state.v[0] = 0x01;
+
+        state.memory[0x200] = 0xF0; // ADD I, V0
+        state.memory[0x201] = 0x1E;
+        state.memory[0x202] = 0xF0; // LD [I], V0
+        state.memory[0x203] = 0x55;
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.i, 0x1000);
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.memory[0x1000], 0x01);
+        assert_eq!(state.i, 0x1001);
+    }
+
+    #[test]
+    fn instruction_draw_xo_chip_planes() {
+        let mut state = state::State::new();
+        state.quirks = quirks::Quirks::xochip();
+        state.i = 0x300;
+        state.memory[0x300] = 0x80; // Sprite for the first plane
+        state.memory[0x301] = 0xC0; // Sprite for the second plane
+
+        state.memory[0x200] = 0xF2; // PLANE 2
+        state.memory[0x201] = 0x01;
+        state.memory[0x202] = 0xD0; // DRW V0, V0, 1
+        state.memory[0x203] = 0x01;
+        state.memory[0x204] = 0xF3; // PLANE 3
+        state.memory[0x205] = 0x01;
+        state.memory[0x206] = 0xD0; // DRW V0, V0, 1
+        state.memory[0x207] = 0x01;
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.planes, 2);
+        assert!(!state.screen[0]);
+        assert!(state.plane2[0]);
+        assert_eq!(state.v[0xF], 0);
+
+        // Both planes: the first byte goes to the first plane, the second to the second plane, colliding there
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.visible_planes()[..2], [1, 2]);
+        assert_eq!(state.v[0xF], 1);
+    }
+
+    #[test]
+    fn instruction_plane_is_unknown_without_xo_chip_quirk() {
+        let mut state = state::State::new();
+        state.strict = true;
+
+        state.memory[0x200] = 0xF2; // PLANE 2
+        state.memory[0x201] = 0x01;
+
+        assert!(decoder::decode_and_execute(&mut state).is_err());
+        assert_eq!(state.planes, 1);
+    }
+
+    #[test]
+    fn fetch_wraps_at_top_of_memory() {
+        let mut state = state::State::new();
+        state.pc = 0xFFF;
+
+        // LD V0, 0x2A, split across the top of memory
+        state.memory[0xFFF] = 0x60;
+        state.memory[0x000] = 0x2A;
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+        assert_eq!(state.v[0], 0x2A);
+        assert_eq!(state.pc, 0x001);
+    }
+
+    #[test]
+    fn skip_wraps_at_top_of_memory() {
+        let mut state = state::State::new();
+        state.pc = 0xFFC;
+
+        state.memory[0xFFC] = 0x30; // SE V0, 0x00
+        state.memory[0xFFD] = 0x00;
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+        assert_eq!(state.pc, 0x000);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn flags_round_trip_and_reject_corrupt_file() {
+        let path = std::env::temp_dir().join(format!("chip8-flags-{}.sav", std::process::id()));
+        let mut state = state::State::new();
+        state.flags = [1, 2, 3, 4, 5, 6, 7, 8];
+        state.save_flags(&path).expect("Failed to save flags");
+
+        let mut loaded = state::State::new();
+        loaded.load_flags(&path).expect("Failed to load flags");
+        assert_eq!(loaded.flags, state.flags);
+
+        std::fs::write(&path, [0xFF; 3]).expect("Failed to corrupt flags");
+        let mut corrupt = state::State::new();
+        assert!(corrupt.load_flags(&path).is_err());
+        assert_eq!(corrupt.flags, [0; 8]);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn rewind_restores_earlier_frame() {
+        let rom = [
+            0x70, 0x01, // ADD V0, 0x01
+            0x12, 0x00, // JP 0x200
+        ];
+        let mut emulator = emulator::Emulator::new(state::State::try_from(&rom[..]).unwrap());
+        emulator.rewind_depth = 2;
+
+        for expected in 1..=3 {
+            emulator.step().expect("Failed to execute instruction");
+            emulator.step().expect("Failed to execute instruction");
+            emulator.vblank();
+            assert_eq!(emulator.state.v[0], expected);
+        }
+        emulator.step().expect("Failed to execute instruction");
+        assert_eq!(emulator.state.v[0], 4);
+
+        // Only the last 2 frames are kept
+        assert_eq!(emulator.rewind(5), 2);
+        assert_eq!(emulator.state.v[0], 2);
+        assert_eq!(emulator.rewind(1), 0);
+    }
+
+    #[test]
+    fn reset_restarts_program() {
+        let rom = [
+            0x60, 0x2A, // LD V0, 0x2A
+            0xA2, 0x00, // LD I, 0x200
+            0xC1, 0xFF, // RND V1, 0xFF
+            0xF0, 0x15, // LD DT, V0
+            0x22, 0x0C, // CALL 0x20C
+            0x00, 0x00, // NOP
+            0xD0, 0x05, // DRW V0, V0, 5
+        ];
+        let mut state = state::State::try_from(&rom[..]).unwrap();
+        state.set_seed(42);
+        let mut emulator = emulator::Emulator::new(state);
+
+        for _ in 0..6 {
+            emulator.step().expect("Failed to execute instruction");
+        }
+        let random = emulator.state.v[1];
+        assert_eq!(emulator.state.v[0], 0x2A);
+        assert_eq!(emulator.state.delay_timer, 0x2A);
+        assert_eq!(emulator.state.stack.len(), 1);
+        assert!(emulator.state.screen.contains(&true));
+
+        emulator.reset();
+
+        assert_eq!(emulator.state.v, [0; 16]);
+        assert_eq!(emulator.state.i, 0);
+        assert_eq!(emulator.state.pc, 0x200);
+        assert_eq!(emulator.state.delay_timer, 0);
+        assert!(emulator.state.stack.is_empty());
+        assert!(!emulator.state.screen.contains(&true));
+        assert_eq!(emulator.state.memory[0x200..0x200 + rom.len()], rom);
+        assert_eq!(emulator.state.memory[..5], font::SMALL_FONT[..5]);
+
+        // The same seed gives the same random numbers
+        for _ in 0..3 {
+            emulator.step().expect("Failed to execute instruction");
+        }
+        assert_eq!(emulator.state.v[1], random);
+    }
+
+    #[test]
+    fn format_state() {
+        let mut state = state::State::new();
+        state.pc = 0x21A;
+        state.i = 0x50;
+        state.v[0] = 0x2A;
+        state.v[0xF] = 1;
+        state.delay_timer = 60;
+        state.stack.push_back(0x204);
+        state.screen[1] = true;
+
+        assert_eq!(
+            format!("{state:?}"),
+            "State { pc: 21A, i: 050, v: [2A, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 01], \
+             delay_timer: 60, sound_timer: 0, stack: [204], .. }"
+        );
+        assert_eq!(
+            state.to_string(),
+            screen::screen_to_string(state.visible_screen())
+        );
+        assert!(state.to_string().starts_with(" █ "));
+    }
+
+    #[test]
+    fn build_emulator() {
+        let emulator = emulator::Emulator::builder().build();
+        assert_eq!(emulator.state.quirks, quirks::Quirks::chip8());
+        assert_eq!(emulator.cycles_per_second, constants::CLOCK_FREQ);
+        assert!(!emulator.turbo && !emulator.state.strict);
+
+        let mut emulator = emulator::Emulator::builder()
+            .quirks(quirks::Quirks::schip())
+            .clock_hz(1000)
+            .seed(42)
+            .strict(true)
+            .max_stack_depth(None)
+            .build();
+        assert_eq!(emulator.state.quirks, quirks::Quirks::schip());
+        assert_eq!(emulator.cycles_per_second, 1000);
+        assert_eq!(emulator.state.seed, 42);
+        assert_eq!(emulator.state.max_stack_depth, None);
+
+        // Strict mode fails on the unknown opcode
+        emulator
+            .state
+            .load_rom(&[0x80, 0x08]) // DW 0x8008
+            .expect("Failed to load ROM");
+        assert!(emulator.step().is_err());
+    }
+
+    #[test]
+    fn press_and_release_keys() {
+        let rom = [
+            0xF3, 0x0A, // LD V3, K
+            0xE3, 0x9E, // SKP V3
+            0x12, 0x04, // JP 0x204
+            0xE3, 0xA1, // SKNP V3
+            0x12, 0x06, // JP 0x206
+        ];
+        let mut emulator = emulator::Emulator::new(state::State::try_from(&rom[..]).unwrap());
+
+        emulator.step().expect("Failed to execute instruction");
+        emulator.step().expect("Failed to execute instruction");
+        assert_eq!(emulator.state.waiting_for_keypress, Some(3));
+
+        emulator.press_key(5).expect("Failed to press key");
+        assert!(emulator.is_key_down(5));
+        assert_eq!(emulator.state.v[3], 5);
+        emulator.step().expect("Failed to execute instruction"); // SKP V3
+        assert_eq!(emulator.state.pc, 0x206);
+
+        emulator.step().expect("Failed to execute instruction"); // SKNP V3
+        assert_eq!(emulator.state.pc, 0x208);
+        emulator.release_key(5).expect("Failed to release key");
+        assert!(!emulator.is_key_down(5));
+        emulator.step().expect("Failed to execute instruction"); // JP 0x206
+        emulator.step().expect("Failed to execute instruction"); // SKNP V3
+        assert_eq!(emulator.state.pc, 0x20A);
+
+        assert_eq!(
+            emulator.press_key(0x10),
+            Err(error::Chip8Error::InvalidKey(0x10))
+        );
+        assert!(!emulator.is_key_down(0x10));
+    }
+
+    #[test]
+    fn reload_rom_keeps_registers() {
+        let rom = [
+            0x60, 0x2A, // LD V0, 0x2A
+            0x12, 0x02, // JP 0x202
+        ];
+        let mut state = state::State::new();
+        assert_eq!(state.rom_len(), 0);
+        state.load_rom_at(&rom, 0x300).expect("Failed to load ROM");
+        assert_eq!(state.rom_len(), 4);
+
+        let mut emulator = emulator::Emulator::new(state);
+        emulator.step().expect("Failed to execute instruction");
+
+        emulator
+            .state
+            .reload_rom(&[0x61, 0x01]) // LD V1, 0x01
+            .expect("Failed to reload ROM");
+        assert_eq!(emulator.state.rom_len(), 2);
+        assert_eq!(
+            emulator.state.memory[0x300..0x304],
+            [0x61, 0x01, 0x12, 0x02]
+        );
+        assert_eq!((emulator.state.pc, emulator.state.v[0]), (0x302, 0x2A));
+
+        assert!(emulator.state.reload_rom(&[0; 0xD01]).is_err());
+    }
+
+    #[test]
+    fn pc_overflow_policies() {
+        let run = |pc_overflow| {
+            let mut state = state::State::with_seed(0);
+            state.pc_overflow = pc_overflow;
+            state.pc = 0xFFE;
+            state.memory[0xFFE] = 0x60; // LD V0, 0x2A
+            state.memory[0xFFF] = 0x2A;
+            state.memory[0x000] = 0x61; // LD V1, 0x2B, over the font
+            state.memory[0x001] = 0x2B;
+
+            let first = decoder::decode_and_execute(&mut state);
+            assert_eq!(first, Ok(None));
+            assert_eq!(state.v[0], 0x2A);
+            (decoder::decode_and_execute(&mut state), state)
+        };
+
+        let (result, state) = run(state::PcOverflow::Wrap);
+        assert_eq!(result, Ok(None));
+        assert_eq!((state.pc, state.v[1]), (0x002, 0x2B));
+
+        let (result, state) = run(state::PcOverflow::Halt);
+        assert_eq!(result, Ok(Some(0)));
+        assert_eq!((state.pc, state.v[1]), (0x1000, 0));
+
+        let (result, _) = run(state::PcOverflow::Error);
+        assert_eq!(result, Err(error::Chip8Error::PcOverflow(0x1000)));
+    }
+
+    #[test]
+    fn pc_overflow_stop_executes_nothing() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut state = state::State::with_seed(0);
+        state.pc_overflow = "HALT".parse().expect("Failed to parse policy");
+        state.pc = 0xFFF;
+        let mut emulator = emulator::Emulator::new(state);
+        emulator.coverage = Some(stats::Coverage::default());
+        let traced = Rc::new(RefCell::new(0));
+        let hook_traced = Rc::clone(&traced);
+        emulator.set_trace_hook(Box::new(move |_, _, _| *hook_traced.borrow_mut() += 1));
+
+        assert_eq!(emulator.step().expect("Failed to step"), Some(0));
+        assert_eq!(emulator.stats.instructions, 0);
+        assert_eq!(emulator.opcode_stats(), [0; 16]);
+        assert_eq!(*traced.borrow(), 0);
+        let all_forms = stats::Coverage::default().missed().len();
+        assert!(
+            emulator
+                .coverage
+                .as_ref()
+                .is_some_and(|coverage| coverage.missed().len() == all_forms)
+        );
+
+        emulator.state.pc_overflow = "Error".parse().expect("Failed to parse policy");
+        assert!(emulator.step().is_err());
+        assert_eq!(emulator.stats.instructions, 0);
+    }
+
+    #[test]
+    fn pc_overflow_on_skip() {
+        let mut state = state::State::with_seed(0);
+        state.pc_overflow = state::PcOverflow::Error;
+        state.pc = 0xFFE;
+        state.memory[0xFFE] = 0x30; // SE V0, 0x00
+        state.memory[0xFFF] = 0x00;
+
+        assert_eq!(decoder::decode_and_execute(&mut state), Ok(None));
+        assert_eq!(
+            decoder::decode_and_execute(&mut state),
+            Err(error::Chip8Error::PcOverflow(0x1002))
+        );
+    }
+
+    #[test]
+    fn cycle_accurate_clock() {
+        let mut emulator = emulator::Emulator::builder()
+            .turbo(true)
+            .cycle_accurate(true)
+            .seed(0)
+            .build();
+        let program = [
+            0x60, 0x2A, // LD V0, 0x2A
+            0x12, 0x00, // JP 0x200
+        ];
+        emulator
+            .state
+            .load_rom(&program)
+            .expect("Failed to load ROM");
+        emulator.set_delay_timer(10);
+
+        emulator.step().expect("Failed to execute instruction");
+        emulator.step().expect("Failed to execute instruction");
+        assert_eq!(emulator.cycles(), 46 + 52);
+
+        // A frame of the VIP's clock is 3668 machine cycles, which the 38th iteration of the loop crosses
+        for _ in 0..36 {
+            emulator.step().expect("Failed to execute instruction");
+            emulator.step().expect("Failed to execute instruction");
+        }
+        assert_eq!(emulator.state.cycles, 37 * 98);
+        assert_eq!(emulator.delay_timer(), 10);
+        emulator.step().expect("Failed to execute instruction");
+        assert_eq!(emulator.delay_timer(), 9);
+    }
+
+    #[test]
+    fn breakpoint_after_display_wait_resumes() {
+        let mut emulator = emulator::Emulator::new(state::State::with_seed(0));
+        assert!(emulator.state.quirks.display_wait);
+        let program = [
+            0xD0, 0x01, // DRW V0, V0, 1
+            0x60, 0x01, // LD V0, 0x01
+            0x12, 0x04, // JP 0x204
+        ];
+        emulator
+            .state
+            .load_rom(&program)
+            .expect("Failed to load ROM");
+        emulator.add_breakpoint(0x202);
+
+        // Stops while waiting for the vertical blank after the draw, and resumes through it
+        let mut run = || emulator.run_until_break(100).expect("Failed to run");
+        assert_eq!(run(), emulator::BreakReason::Breakpoint(0x202));
+        assert_eq!(run(), emulator::BreakReason::CycleLimit);
+        assert_eq!(emulator.state.v[0], 0x01);
+    }
+
+    #[test]
+    fn breakpoints_and_watchpoints() {
+        let mut emulator = emulator::Emulator::new(state::State::with_seed(0));
+        let program = [
+            0x60, 0x2A, // LD V0, 0x2A
+            0xA3, 0x00, // LD I, 0x300
+            0xF0, 0x55, // LD [I], V0
+            0x70, 0x01, // ADD V0, 0x01
+            0x12, 0x08, // JP 0x208
+        ];
+        emulator
+            .state
+            .load_rom(&program)
+            .expect("Failed to load ROM");
+        emulator.add_breakpoint(0x204);
+        emulator.watch_memory(0x300);
+        emulator
+            .watch_register(0)
+            .expect("Failed to watch register");
+
+        let mut run = || emulator.run_until_break(100).expect("Failed to run");
+        assert_eq!(
+            run(),
+            emulator::BreakReason::Watchpoint {
+                watch: emulator::Watch::Register(0),
+                pc: 0x200,
+                old: 0,
+                new: 0x2A
+            }
+        );
+        assert_eq!(run(), emulator::BreakReason::Breakpoint(0x204));
+        assert_eq!(
+            run(),
+            emulator::BreakReason::Watchpoint {
+                watch: emulator::Watch::Memory(0x300),
+                pc: 0x204,
+                old: 0,
+                new: 0x2A
+            }
+        );
+        assert_eq!(
+            run(),
+            emulator::BreakReason::Watchpoint {
+                watch: emulator::Watch::Register(0),
+                pc: 0x206,
+                old: 0x2A,
+                new: 0x2B
+            }
+        );
+        assert_eq!(run(), emulator::BreakReason::CycleLimit);
+
+        assert_eq!(
+            emulator.watch_register(0x10),
+            Err(error::Chip8Error::InvalidRegister(0x10))
+        );
+    }
+
+    #[test]
+    fn memory_stack_holds_return_addresses() {
+        let mut state = state::State::with_seed(0);
+        state.memory_stack = true;
+        let program = [
+            0x22, 0x06, // CALL 0x206
+            0x00, 0x00, // NOP
+            0x00, 0x00, // NOP
+            0x22, 0x0A, // CALL 0x20A
+            0x00, 0xEE, // RET
+            0x00, 0xEE, // RET
+        ];
+        state.load_rom(&program).expect("Failed to load ROM");
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(
+            state.memory[constants::STACK_OFFSET..][..4],
+            [0x02, 0x02, 0x02, 0x08]
+        );
+
+        // The program rewrites the outer return address, to skip the first NOP
+        state.memory[constants::STACK_OFFSET + 1] = 0x04;
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.pc, 0x208);
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        assert_eq!(state.pc, 0x204);
+        assert!(state.stack.is_empty());
+    }
+
+    #[test]
+    fn memory_stack_overflows_at_its_region() {
+        let mut state = state::State::with_seed(0);
+        state.memory_stack = true;
+        state.max_stack_depth = None;
+        state.load_rom(&[0x22, 0x00]).expect("Failed to load ROM"); // CALL 0x200
+
+        for _ in 0..constants::MEMORY_STACK_DEPTH {
+            decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+        }
+        assert_eq!(
+            decoder::decode_and_execute(&mut state),
+            Err(error::Chip8Error::StackOverflow)
+        );
+        assert_eq!(state.memory[0xF00], 0, "The display area must be untouched");
+    }
+
+    #[test]
+    fn reject_empty_roms() {
+        assert_eq!(
+            state::State::try_from(&[][..]).map(|_| ()),
+            Err(error::Chip8Error::RomTooSmall(0))
+        );
+        assert_eq!(
+            state::State::try_from(&[0x12][..]).map(|_| ()),
+            Err(error::Chip8Error::RomTooSmall(1))
+        );
+        assert_eq!(
+            error::Chip8Error::RomTooSmall(0).to_string(),
+            "ROM is empty"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn reject_empty_rom_files_and_directories() {
+        let path = std::env::temp_dir().join(format!("chip8-empty-{}.ch8", std::process::id()));
+        std::fs::write(&path, []).expect("Failed to write ROM");
+        let empty = state::read_rom(&path).expect_err("Empty ROM should fail");
+        std::fs::remove_file(&path).expect("Failed to remove ROM");
+        assert_eq!(empty.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(empty.to_string(), "ROM is empty");
+
+        let directory = state::State::try_from(&std::env::temp_dir())
+            .map(|_| ())
+            .expect_err("Directory should fail");
+        assert_eq!(directory.kind(), std::io::ErrorKind::IsADirectory);
+    }
+
+    #[test]
+    fn key_wait_status() {
+        let mut emulator = emulator::Emulator::new(state::State::with_seed(0));
+        emulator
+            .state
+            .load_rom(&[0xF3, 0x0A]) // LD V3, K
+            .expect("Failed to load ROM");
+        assert!(!emulator.is_waiting_for_key());
+
+        emulator.step().expect("Failed to execute instruction");
+        assert!(emulator.is_waiting_for_key());
+        assert_eq!(emulator.pending_key_register(), Some(3));
+
+        emulator.press_key(0xA).expect("Failed to press key");
+        emulator.press_key(0x1).expect("Failed to press key");
+        assert_eq!(emulator.keys_pressed(), 0b0000_0100_0000_0010);
+        assert!(!emulator.is_waiting_for_key());
+        assert_eq!(emulator.pending_key_register(), None);
+        assert_eq!(emulator.state.v[3], 0xA);
+    }
+
+    #[test]
+    fn step_frame_runs_a_frame() {
+        let mut emulator = emulator::Emulator::builder().clock_hz(600).seed(0).build();
+        let program = [
+            0x60, 0x05, // LD V0, 0x05
+            0xF0, 0x18, // LD ST, V0
+            0xD0, 0x05, // DRW V0, V0, 5
+            0xF1, 0x0A, // LD V1, K
+            0x12, 0x08, // JP 0x208
+        ];
+        emulator
+            .state
+            .load_rom(&program)
+            .expect("Failed to load ROM");
+
+        // 10 cycles per frame: the first frame draws, and then waits for the vertical blank
+        let frame = emulator.step_frame().expect("Failed to run frame");
+        assert_eq!(
+            frame,
+            emulator::FrameResult {
+                screen_changed: true,
+                beeping: true,
+                exit_code: None
+            }
+        );
+        assert_eq!(emulator.cycles(), 10);
+        assert_eq!(emulator.sound_timer(), 4);
+        assert!(!emulator.is_waiting_for_key());
+
+        let frame = emulator.step_frame().expect("Failed to run frame");
+        assert!(!frame.screen_changed);
+        assert!(emulator.is_waiting_for_key());
+
+        emulator.press_key(0x7).expect("Failed to press key");
+        emulator.step_frame().expect("Failed to run frame");
+        assert_eq!(emulator.state.v[1], 0x7);
+        assert_eq!(emulator.cycles(), 30);
+    }
+
+    #[test]
+    fn load_at_places_blobs() {
+        let mut state = state::State::new();
+        state
+            .load_rom(&[0x22, 0x00]) // CALL 0x200
+            .expect("Failed to load ROM");
+
+        state
+            .load_at(&[0xF0, 0x90], 0x800)
+            .expect("Failed to load sprite");
+        state
+            .load_at(&[0x00, 0xEE], 0xA00)
+            .expect("Failed to load subroutine");
+
+        assert_eq!(state.memory[0x800..0x802], [0xF0, 0x90]);
+        assert_eq!(state.memory[0xA00..0xA02], [0x00, 0xEE]);
+        assert_eq!(state.pc, 0x200);
+        assert_eq!(state.rom_len(), 2);
+    }
+
+    #[test]
+    fn load_at_checks_bounds() {
+        let mut state = state::State::new();
+
+        assert!(state.load_at(&[1, 2], 0xFFE).is_ok());
+        assert_eq!(
+            state.load_at(&[1, 2], 0xFFF),
+            Err(error::Chip8Error::LoadOutOfBounds {
+                size: 2,
+                address: 0xFFF
+            })
+        );
+        assert!(state.load_at(&[1], 0x1001).is_err());
+
+        // The fonts can be replaced, unless they are protected
+        assert!(state.load_at(&[0xFF], 0x10).is_ok());
+        state.protect_fonts = true;
+        assert_eq!(
+            state.load_at(&[0xFF; 4], 0x9E),
+            Err(error::Chip8Error::FontOverwrite {
+                size: 4,
+                address: 0x9E
+            })
+        );
+        assert!(state.load_at(&[0xFF; 4], 0xF0).is_ok());
+    }
+
+    #[test]
+    fn state_setup_in_one_expression() {
+        let mut v = [0; 16];
+        v[0] = 0x12;
+        v[1] = 0x34;
+        let mut state = state::State::with_seed(0)
+            .with_registers(v)
+            .with_i(0x300)
+            .with_pc(0x400)
+            .with_memory_at(0x400, &[0xF1, 0x55]); // LD [I], V1
+
+        decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+        assert_eq!(state.memory[0x300..0x302], [0x12, 0x34]);
+        assert_eq!(state.pc, 0x402);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn replay_reproduces_a_run() {
+        let path = std::env::temp_dir().join(format!("chip8-replay-{}.rpl", std::process::id()));
+        let program = [
+            0xF0, 0x0A, // LD V0, K
+            0xC1, 0xFF, // RND V1, 0xFF
+            0xE0, 0x9E, // SKP V0
+            0x72, 0x01, // ADD V2, 0x01
+            0xF3, 0x07, // LD V3, DT
+            0x12, 0x02, // JP 0x202
+        ];
+        let run = |emulator: &mut emulator::Emulator, input: &mut dyn input::Input| {
+            for _ in 0..2000 {
+                emulator
+                    .step_with_input(input)
+                    .expect("Failed to execute instruction");
+            }
+            (emulator.state.v, emulator.state.cycles)
+        };
+
+        let mut recorded = emulator::Emulator::builder().seed(1).build();
+        recorded
+            .state
+            .load_rom(&program)
+            .expect("Failed to load ROM");
+        recorded
+            .start_recording(&path, true)
+            .expect("Failed to start recording");
+        let expected = run(
+            &mut recorded,
+            &mut input::ScriptedInput::new(vec![(30..40, 0x5), (900..1500, 0xA)]),
+        );
+
+        // Another seed and no input, both replaced by the recording
+        let mut replayed = emulator::Emulator::builder().seed(2).build();
+        replayed
+            .state
+            .load_rom(&program)
+            .expect("Failed to load ROM");
+        replayed
+            .play_recording(&path)
+            .expect("Failed to play recording");
+        assert!(replayed.is_playing_back());
+        let actual = run(&mut replayed, &mut input::ScriptedInput::new(vec![]));
+
+        assert_eq!(actual, expected);
+        assert_eq!(expected.0[0], 0x5);
+        assert!(!replayed.is_playing_back());
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn font_at_custom_offset() {
+        for offset in [0x000, 0x050] {
+            let mut state = state::State::with_seed(0)
+                .with_font_offset(offset)
+                .with_registers([0xA; 16])
+                .with_memory_at(0x200, &[0xF0, 0x29, 0xF1, 0x30]); // LD F, V0; LD HF, V1
+
+            decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+            assert_eq!(state.i, offset + 0xA * font::SMALL_GLYPH_SIZE);
+            assert_eq!(state.memory[state.i..state.i + 5], font::SMALL_FONT[50..55]);
+
+            decoder::decode_and_execute(&mut state).expect("Failed to execute instruction");
+            assert_eq!(state.i, offset + 80 + 0xA * font::BIG_GLYPH_SIZE);
+            assert_eq!(
+                state.memory[state.i..state.i + 10],
+                font::BIG_FONT[100..110]
+            );
+        }
+
+        // The old location is cleared
+        let state = state::State::with_seed(0).with_font_offset(0x100);
+        assert!(state.memory[..0x100].iter().all(|&byte| byte == 0));
+        assert_eq!(state.fonts(), 0x100..0x1F0);
+    }
+
+    #[test]
+    fn memory_access_is_bounds_checked() {
+        let mut state = state::State::new();
+        state.protect_fonts = true;
+        let mut emulator = emulator::Emulator::new(state);
+
+        emulator
+            .write_memory(0xFFF, 0xAB)
+            .expect("Failed to write the last byte");
+        assert_eq!(emulator.read_memory(0xFFF), Some(0xAB));
+        assert_eq!(emulator.read_memory(0x000), Some(0xF0));
+        assert_eq!(emulator.read_memory(0x1000), None);
+        assert!(matches!(
+            emulator.write_memory(0x1000, 0),
+            Err(error::Chip8Error::LoadOutOfBounds {
+                address: 0x1000,
+                ..
+            })
+        ));
+        assert!(matches!(
+            emulator.write_memory(0x000, 0),
+            Err(error::Chip8Error::FontOverwrite { address: 0x000, .. })
+        ));
+
+        assert_eq!(emulator.memory_slice(0xFFE..0x1000), Some(&[0, 0xAB][..]));
+        assert_eq!(emulator.memory_slice(0x000..0x002), Some(&[0xF0, 0x90][..]));
+        assert_eq!(emulator.memory_slice(0xFFF..0x1001), None);
+
+        emulator.state.protect_fonts = false;
+        emulator
+            .write_memory(0x000, 0)
+            .expect("Failed to write the fonts");
+        assert_eq!(emulator.read_memory(0x000), Some(0));
+    }
 }
@@ -0,0 +1,155 @@
+//! Pacing of the run loop against the wall clock.
+//!
+//! The run loop measures the wall time elapsed since its previous iteration, and the `Pacer` converts it into the
+//! number of cycles and vertical blanks owed. Time that isn't a whole cycle or frame yet carries over to the next
+//! iteration, so the emulator neither drifts nor slows down when an iteration takes longer than planned. After a long
+//! stall (e.g. the host was suspended) only `MAX_CATCH_UP` is made up for, so the program doesn't freeze while
+//! executing thousands of instructions at once, only to race through them.
+//!
+//! Alternatively, `Pacer::per_frame` runs a fixed budget of instructions at the start of each frame, the way many
+//! ROMs were tuned, so the run loop only needs to sleep once per frame.
+
+use crate::constants;
+use core::time::Duration;
+
+/// Converts the elapsed wall time into the cycles and vertical blanks owed, carrying over the remainders.
+pub struct Pacer {
+    /// Wall time of a cycle.
+    tick_length: Duration,
+
+    /// Wall time of a frame.
+    frame_length: Duration,
+
+    /// Elapsed time not yet converted to cycles.
+    cycle_debt: Duration,
+
+    /// Elapsed time not yet converted to frames.
+    frame_debt: Duration,
+
+    /// Instructions executed per frame, rather than per tick of the clock, if set.
+    instructions_per_frame: Option<u32>,
+}
+
+impl Pacer {
+    /// Create a pacer.
+    ///
+    /// # Arguments
+    /// * `cycles_per_second` - The clock frequency, at least 1.
+    pub fn new(cycles_per_second: u32) -> Self {
+        Self {
+            tick_length: Duration::from_secs(1) / cycles_per_second.max(1),
+            frame_length: Duration::from_secs(1) / constants::TIMER_FREQ,
+            cycle_debt: Duration::ZERO,
+            frame_debt: Duration::ZERO,
+            instructions_per_frame: None,
+        }
+    }
+
+    /// Create a pacer that owes a budget of cycles for each frame, rather than following a clock.
+    ///
+    /// # Arguments
+    /// * `instructions_per_frame` - The number of cycles per frame.
+    pub fn per_frame(instructions_per_frame: u32) -> Self {
+        Self {
+            instructions_per_frame: Some(instructions_per_frame),
+            ..Self::new(instructions_per_frame.saturating_mul(constants::TIMER_FREQ))
+        }
+    }
+
+    /// Account for elapsed wall time.
+    ///
+    /// # Arguments
+    /// * `elapsed` - The wall time since the previous call.
+    ///
+    /// # Returns
+    /// A tuple containing the number of cycles to execute, and the number of vertical blanks to signal.
+    pub fn advance(&mut self, elapsed: Duration) -> (u32, u32) {
+        self.frame_debt = (self.frame_debt + elapsed).min(constants::MAX_CATCH_UP);
+        let frames = Self::take_whole(&mut self.frame_debt, self.frame_length);
+        if let Some(instructions_per_frame) = self.instructions_per_frame {
+            return (frames.saturating_mul(instructions_per_frame), frames);
+        }
+
+        self.cycle_debt = (self.cycle_debt + elapsed).min(constants::MAX_CATCH_UP);
+        let cycles = Self::take_whole(&mut self.cycle_debt, self.tick_length);
+        (cycles, frames)
+    }
+
+    /// Forget any owed time, e.g. after being paused.
+    pub fn reset(&mut self) {
+        self.cycle_debt = Duration::ZERO;
+        self.frame_debt = Duration::ZERO;
+    }
+
+    /// Wall time until the next cycle is owed. With a budget per frame, that is the start of the next frame.
+    pub fn until_next_cycle(&self) -> Duration {
+        match self.instructions_per_frame {
+            Some(_) => self.frame_length.saturating_sub(self.frame_debt),
+            None => self.tick_length.saturating_sub(self.cycle_debt),
+        }
+    }
+
+    /// Take as many whole periods as possible out of `debt`, and return how many were taken. A period that rounded
+    /// down to nothing, from a clock faster than `MAX_CLOCK_FREQ`, counts as a nanosecond.
+    fn take_whole(debt: &mut Duration, period: Duration) -> u32 {
+        let period = period.max(Duration::from_nanos(1));
+        let count = (debt.as_nanos() / period.as_nanos()) as u32;
+        *debt -= period * count;
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carries_over_partial_cycles() {
+        let mut pacer = Pacer::new(1000);
+
+        assert_eq!(pacer.advance(Duration::from_micros(1500)), (1, 0));
+        assert_eq!(pacer.until_next_cycle(), Duration::from_micros(500));
+        assert_eq!(pacer.advance(Duration::from_micros(1500)), (2, 0));
+        assert_eq!(pacer.advance(Duration::from_millis(14)), (14, 1));
+    }
+
+    #[test]
+    fn caps_catch_up_after_stall() {
+        let mut pacer = Pacer::new(700);
+
+        let (cycles, frames) = pacer.advance(Duration::from_secs(5));
+
+        assert_eq!(cycles, 70); // 100 ms worth
+        assert_eq!(frames, 6);
+    }
+
+    #[test]
+    fn caps_catch_up_with_huge_clock() {
+        let mut pacer = Pacer::new(u32::MAX);
+
+        let (cycles, frames) = pacer.advance(Duration::from_secs(5));
+
+        assert_eq!(cycles, 100_000_000); // 100 ms worth, at a cycle per nanosecond
+        assert_eq!(frames, 6);
+    }
+
+    #[test]
+    fn runs_budget_per_frame() {
+        let mut pacer = Pacer::per_frame(10);
+
+        assert_eq!(pacer.advance(Duration::from_millis(10)), (0, 0));
+        assert_eq!(
+            pacer.until_next_cycle(),
+            Duration::from_secs(1) / 60 - Duration::from_millis(10)
+        );
+        assert_eq!(pacer.advance(Duration::from_millis(10)), (10, 1));
+        assert_eq!(pacer.advance(Duration::from_millis(40)), (20, 2));
+    }
+
+    #[test]
+    fn large_budget_per_frame_saturates() {
+        let mut pacer = Pacer::per_frame(u32::MAX);
+
+        assert_eq!(pacer.advance(Duration::from_millis(40)), (u32::MAX, 2));
+    }
+}
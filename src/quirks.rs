@@ -0,0 +1,216 @@
+//! Behavioral differences between CHIP-8 interpreters.
+//!
+//! The original COSMAC VIP interpreter and its successors (CHIP-48, SUPER-CHIP, ...) disagree on the exact semantics of
+//! a handful of instructions, and ROMs are usually written against one of them. `Quirks` selects which behavior the
+//! interpreter emulates. The presets `Quirks::chip8()`, `Quirks::schip()` and `Quirks::xochip()` cover the common
+//! cases, and individual quirks can be listed and toggled by name with `Quirks::all_settings` and `Quirks::set_by_name`.
+
+use crate::error::Chip8Error;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A quirk as listed by `Quirks::all_settings`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuirkSetting {
+    /// Name of the quirk, as accepted by `Quirks::set_by_name`.
+    pub name: &'static str,
+
+    /// Whether the quirk is currently enabled.
+    pub value: bool,
+
+    /// Human readable description of the quirk.
+    pub description: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8XY6`/`8XYE` shift VY and store the result in VX (COSMAC VIP), rather than shifting VX in place (SUPER-CHIP).
+    pub shift_uses_vy: bool,
+
+    /// `DXYN` waits for the next vertical blank (COSMAC VIP), so at most one sprite is drawn per frame.
+    pub display_wait: bool,
+
+    /// `BXNN` jumps to XNN plus VX (CHIP-48, SUPER-CHIP), rather than `BNNN` jumping to NNN plus V0 (COSMAC VIP).
+    pub jump_uses_vx: bool,
+
+    /// `8XY1`/`8XY2`/`8XY3` reset VF to 0 as a side effect (COSMAC VIP).
+    pub vf_reset_on_logic: bool,
+
+    /// `FN01` selects among two display planes, which `DXYN` draws to and `00E0` clears (XO-CHIP).
+    pub xo_chip_planes: bool,
+
+    /// `F000 NNNN` loads I with the 16-bit address in the next two bytes, and skips step over all four bytes (XO-CHIP).
+    pub xo_chip_long_addressing: bool,
+
+    /// `FX1E` sets VF to 1 when I + VX exceeds 0xFFF, and to 0 otherwise (Amiga interpreter). Spacefight 2091! relies
+    /// on this.
+    pub fx1e_overflow_vf: bool,
+
+    /// `DXYN` drops the pixels of a sprite past the right and bottom edges (COSMAC VIP, SUPER-CHIP), rather than
+    /// wrapping them around to the opposite edge (XO-CHIP). The starting position wraps either way.
+    pub clip_sprites: bool,
+}
+
+impl Quirks {
+    /// Behavior of the original COSMAC VIP interpreter.
+    pub const fn chip8() -> Self {
+        Self {
+            shift_uses_vy: true,
+            display_wait: true,
+            jump_uses_vx: false,
+            vf_reset_on_logic: true,
+            xo_chip_planes: false,
+            xo_chip_long_addressing: false,
+            fx1e_overflow_vf: false,
+            clip_sprites: true,
+        }
+    }
+
+    /// Behavior of the SUPER-CHIP interpreter.
+    pub const fn schip() -> Self {
+        Self {
+            shift_uses_vy: false,
+            display_wait: false,
+            jump_uses_vx: true,
+            vf_reset_on_logic: false,
+            xo_chip_planes: false,
+            xo_chip_long_addressing: false,
+            fx1e_overflow_vf: false,
+            clip_sprites: true,
+        }
+    }
+
+    /// Behavior of XO-CHIP, as implemented by Octo.
+    pub const fn xochip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            display_wait: false,
+            jump_uses_vx: false,
+            vf_reset_on_logic: false,
+            xo_chip_planes: true,
+            xo_chip_long_addressing: true,
+            fx1e_overflow_vf: false,
+            clip_sprites: false,
+        }
+    }
+
+    /// List all quirks with their names, current values and descriptions.
+    pub fn all_settings(&self) -> Vec<QuirkSetting> {
+        let mut quirks = *self;
+        quirks
+            .fields_mut()
+            .into_iter()
+            .map(|(name, description, value)| QuirkSetting {
+                name,
+                value: *value,
+                description,
+            })
+            .collect()
+    }
+
+    /// Enable or disable a quirk by name.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the quirk, as listed by `all_settings`.
+    /// * `value` - Whether the quirk should be enabled.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the quirk was set.
+    /// * `Err(Chip8Error::UnknownQuirk)` if there is no quirk with that name.
+    pub fn set_by_name(&mut self, name: &str, value: bool) -> Result<(), Chip8Error> {
+        let field = self
+            .fields_mut()
+            .into_iter()
+            .find(|(field_name, _, _)| *field_name == name)
+            .map(|(_, _, field)| field)
+            .ok_or_else(|| Chip8Error::UnknownQuirk(name.to_string()))?;
+        *field = value;
+        Ok(())
+    }
+
+    /// Names, descriptions and values of all quirks. This is the single place a new quirk must be registered.
+    fn fields_mut(&mut self) -> Vec<(&'static str, &'static str, &mut bool)> {
+        vec![
+            (
+                "shift_uses_vy",
+                "8XY6/8XYE shift VY into VX, rather than shifting VX in place",
+                &mut self.shift_uses_vy,
+            ),
+            (
+                "display_wait",
+                "DXYN waits for the next vertical blank, drawing at most one sprite per frame",
+                &mut self.display_wait,
+            ),
+            (
+                "jump_uses_vx",
+                "BXNN jumps to XNN plus VX, rather than BNNN jumping to NNN plus V0",
+                &mut self.jump_uses_vx,
+            ),
+            (
+                "vf_reset_on_logic",
+                "8XY1/8XY2/8XY3 reset VF to 0",
+                &mut self.vf_reset_on_logic,
+            ),
+            (
+                "xo_chip_planes",
+                "FN01 selects among two display planes for DXYN and 00E0",
+                &mut self.xo_chip_planes,
+            ),
+            (
+                "xo_chip_long_addressing",
+                "F000 NNNN loads I with a 16-bit address, and is skipped as a whole",
+                &mut self.xo_chip_long_addressing,
+            ),
+            (
+                "fx1e_overflow_vf",
+                "FX1E sets VF to 1 when I + VX overflows past 0xFFF, and to 0 otherwise",
+                &mut self.fx1e_overflow_vf,
+            ),
+            (
+                "clip_sprites",
+                "DXYN drops pixels past the edges of the screen, rather than wrapping them around",
+                &mut self.clip_sprites,
+            ),
+        ]
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::chip8()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_quirk_by_name() {
+        let mut quirks = Quirks::chip8();
+        assert!(quirks.shift_uses_vy);
+
+        quirks
+            .set_by_name("shift_uses_vy", false)
+            .expect("Failed to set quirk");
+
+        assert!(!quirks.shift_uses_vy);
+        assert!(
+            quirks
+                .all_settings()
+                .iter()
+                .any(|setting| setting.name == "shift_uses_vy" && !setting.value)
+        );
+    }
+
+    #[test]
+    fn set_unknown_quirk_by_name() {
+        let mut quirks = Quirks::chip8();
+
+        let err = quirks.set_by_name("no_such_quirk", true).unwrap_err();
+
+        assert_eq!(err, Chip8Error::UnknownQuirk("no_such_quirk".to_string()));
+        assert_eq!(quirks, Quirks::chip8());
+    }
+}
@@ -0,0 +1,204 @@
+//! Recording of the screen to an animated GIF, e.g. to attach a run to a bug report.
+//!
+//! Only available with the `gif` feature.
+
+use crate::constants::{HEIGHT, TIMER_FREQ, WIDTH};
+use crate::display::Display;
+use log::*;
+use std::borrow::Cow;
+use std::fs::File;
+use std::path::Path;
+
+/// Palette of the recording: unlit pixels are black, lit pixels are white.
+const PALETTE: [u8; 6] = [0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF];
+
+/// A `Display` that records each rendered frame, to be written as an animated GIF with `finish`.
+///
+/// `render` is expected to be called once per frame, at 60Hz. Identical consecutive frames are stored once, with a
+/// longer delay. Once `max_frames` distinct frames have been recorded, further frames are dropped.
+///
+/// Screens can be `HEIGHT` or `HIRES_HEIGHT` rows high. The recording is as high as the highest screen, and lower
+/// screens are drawn at the top, with unlit pixels below.
+pub struct GifRecorder {
+    /// Distinct frames, and the number of 60Hz frames each was shown for.
+    frames: Vec<(Vec<bool>, u32)>,
+
+    /// Maximum number of distinct frames to record.
+    max_frames: usize,
+
+    /// Integer scale factor of the recording.
+    scale: u16,
+
+    /// Whether frames have been dropped because `max_frames` was reached.
+    truncated: bool,
+}
+
+impl GifRecorder {
+    /// Create a recorder.
+    ///
+    /// # Arguments
+    /// * `max_frames` - The maximum number of distinct frames to record.
+    /// * `scale` - The integer scale factor, at least 1. Each CHIP-8 pixel becomes a `scale` × `scale` block.
+    pub fn new(max_frames: usize, scale: u16) -> Self {
+        Self {
+            frames: Vec::new(),
+            max_frames,
+            scale: scale.max(1),
+            truncated: false,
+        }
+    }
+
+    /// Write the recorded frames to an animated GIF, looping forever.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the GIF file to write.
+    ///
+    /// # Return
+    /// * `Ok(())` if the file was written.
+    /// * `Err` if the file couldn't be written.
+    pub fn finish(self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let rows = self
+            .frames
+            .iter()
+            .map(|(screen, _)| screen.len() / WIDTH)
+            .max()
+            .unwrap_or(HEIGHT);
+        let width = WIDTH as u16 * self.scale;
+        let height = rows as u16 * self.scale;
+
+        let mut encoder = gif::Encoder::new(File::create(path)?, width, height, &PALETTE)?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        let mut elapsed_frames = 0;
+        for (screen, shown_for) in &self.frames {
+            let frame = gif::Frame {
+                width,
+                height,
+                delay: delay(elapsed_frames, *shown_for),
+                buffer: Cow::Owned(self.scale_screen(screen, rows)),
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&frame)?;
+            elapsed_frames += shown_for;
+        }
+
+        Ok(())
+    }
+
+    /// Convert a screen buffer to scaled palette indices, padded with unlit pixels to `rows` rows.
+    fn scale_screen(&self, screen: &[bool], rows: usize) -> Vec<u8> {
+        let scale = self.scale as usize;
+        let mut buffer = Vec::with_capacity(WIDTH * rows * scale * scale);
+        for row in 0..rows * scale {
+            for column in 0..WIDTH * scale {
+                let pixel = screen.get((row / scale) * WIDTH + column / scale);
+                buffer.push(pixel.copied().unwrap_or(false) as u8);
+            }
+        }
+        buffer
+    }
+}
+
+impl Display for GifRecorder {
+    fn render(&mut self, screen: &[bool]) -> Result<(), Box<dyn std::error::Error>> {
+        if self.truncated {
+            return Ok(());
+        }
+
+        if let Some((last, shown_for)) = self.frames.last_mut()
+            && last.as_slice() == screen
+        {
+            *shown_for += 1;
+        } else if self.frames.len() < self.max_frames {
+            self.frames.push((screen.to_vec(), 1));
+        } else if !self.truncated {
+            warn!("GIF recording stopped after {} frames", self.max_frames);
+            self.truncated = true;
+        }
+
+        Ok(())
+    }
+}
+
+/// The GIF delay, in 1/100s, of a frame shown for `shown_for` 60Hz frames, starting `elapsed_frames` frames into the
+/// recording. The delay is computed from the rounded start and end times, so rounding errors don't accumulate.
+fn delay(elapsed_frames: u32, shown_for: u32) -> u16 {
+    let centiseconds =
+        |frames: u32| (frames as u64 * 100 + TIMER_FREQ as u64 / 2) / TIMER_FREQ as u64;
+    let delay = centiseconds(elapsed_frames + shown_for) - centiseconds(elapsed_frames);
+    delay.min(u16::MAX as u64) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::HIRES_HEIGHT;
+
+    #[test]
+    fn identical_frames_are_merged() {
+        let blank = [false; WIDTH * HEIGHT];
+        let mut lit = [false; WIDTH * HEIGHT];
+        lit[0] = true;
+
+        let mut recorder = GifRecorder::new(2, 1);
+        for screen in [&blank, &blank, &blank, &lit, &blank, &lit] {
+            recorder.render(screen).expect("Failed to render");
+        }
+
+        // The third distinct frame and everything after it is dropped
+        let shown_for: Vec<u32> = recorder.frames.iter().map(|(_, n)| *n).collect();
+        assert_eq!(shown_for, [3, 1]);
+        assert!(recorder.truncated);
+    }
+
+    #[test]
+    fn delays_do_not_drift() {
+        // 60 frames of 1/60s each must add up to exactly one second
+        let total: u32 = (0..60).map(|frame| delay(frame, 1) as u32).sum();
+        assert_eq!(total, 100);
+        assert_eq!(delay(0, 6), 10);
+    }
+
+    #[test]
+    fn finish_writes_gif() {
+        let path = std::env::temp_dir().join("chip8-rs-recorder-test.gif");
+        let mut recorder = GifRecorder::new(10, 2);
+        recorder
+            .render(&[false; WIDTH * HEIGHT])
+            .expect("Failed to render");
+
+        recorder.finish(&path).expect("Failed to write GIF");
+
+        let bytes = std::fs::read(&path).expect("Failed to read GIF");
+        std::fs::remove_file(&path).ok();
+        assert!(bytes.starts_with(b"GIF89a"));
+    }
+
+    #[test]
+    fn hires_frames_are_recorded_whole() {
+        let mut hires = [false; WIDTH * HIRES_HEIGHT];
+        hires[WIDTH * HIRES_HEIGHT - 1] = true;
+        let mut recorder = GifRecorder::new(10, 1);
+        recorder
+            .render(&[false; WIDTH * HEIGHT])
+            .expect("Failed to render");
+        recorder.render(&hires).expect("Failed to render");
+
+        // The lowres frame is padded, and the bottom right pixel of the hires frame is kept
+        assert_eq!(
+            recorder.scale_screen(&recorder.frames[0].0, HIRES_HEIGHT),
+            [0; WIDTH * HIRES_HEIGHT]
+        );
+        assert_eq!(
+            recorder.scale_screen(&recorder.frames[1].0, HIRES_HEIGHT)[WIDTH * HIRES_HEIGHT - 1],
+            1
+        );
+
+        let path = std::env::temp_dir().join("chip8-rs-recorder-hires-test.gif");
+        recorder.finish(&path).expect("Failed to write GIF");
+        let bytes = std::fs::read(&path).expect("Failed to read GIF");
+        std::fs::remove_file(&path).ok();
+        // The logical screen size follows the signature, as little-endian width and height
+        assert_eq!(bytes[6..10], [64, 0, 64, 0]);
+    }
+}
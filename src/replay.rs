@@ -0,0 +1,238 @@
+//! Recordings of the keypad input, to reproduce a run frame for frame, e.g. to attach to a bug report.
+//!
+//! A recording (`.rpl`) is a text file, starting with the seed of the random number generator, the clock speed, and
+//! whether the input reported key releases. It is followed by a line for every poll that changed the keys: the frame,
+//! the cycle, and the keys held from then on, or `-` for none. Lines starting with `#` are comments.
+//!
+//! ```text
+//! # chip8 replay
+//! seed 1234
+//! clock 700
+//! releases true
+//! 35 408 5
+//! 40 466 5 A
+//! 52 606 -
+//! ```
+//!
+//! While recording or playing back, the emulator signals vertical blanks at frame boundaries counted in cycles, like
+//! in turbo mode, rather than following the wall clock, so the timers run the same on every run. A run is only
+//! reproduced with the same ROM and options, e.g. the quirks, and without rewinding. Only available with the `std`
+//! feature.
+
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Writes the input of a run to a recording, see `Emulator::start_recording`. Each line is written as soon as it is
+/// recorded, so the recording is complete up to a crash.
+pub struct Recorder {
+    writer: LineWriter<File>,
+
+    /// The keys of the last poll.
+    keys: u16,
+}
+
+impl Recorder {
+    /// Create a recording, and write its header.
+    ///
+    /// # Arguments
+    /// * `path` - The file to write, replaced if it exists.
+    /// * `seed` - The seed of the random number generator.
+    /// * `clock_hz` - The clock speed, which determines the frame boundaries.
+    /// * `reports_releases` - Whether the input reports key releases.
+    pub fn create(
+        path: &Path,
+        seed: u64,
+        clock_hz: u32,
+        reports_releases: bool,
+    ) -> std::io::Result<Self> {
+        let mut writer = LineWriter::new(File::create(path)?);
+        writeln!(writer, "# chip8 replay")?;
+        writeln!(writer, "seed {seed}")?;
+        writeln!(writer, "clock {clock_hz}")?;
+        writeln!(writer, "releases {reports_releases}")?;
+        Ok(Self { writer, keys: 0 })
+    }
+
+    /// Record a poll of the input, if the keys changed since the previous one.
+    ///
+    /// # Arguments
+    /// * `frame` - The frame of the poll.
+    /// * `cycle` - The cycle of the poll.
+    /// * `keys` - The polled keys, with bit `n` set if key `n` is held.
+    pub fn record(&mut self, frame: u64, cycle: u64, keys: u16) -> std::io::Result<()> {
+        if keys == self.keys {
+            return Ok(());
+        }
+        self.keys = keys;
+        writeln!(self.writer, "{frame} {cycle} {}", format_keys(keys))
+    }
+}
+
+/// A recording being played back, see `Emulator::play_recording`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Playback {
+    /// The seed of the random number generator.
+    pub seed: u64,
+
+    /// The clock speed the recording was made at.
+    pub clock_hz: u32,
+
+    /// Whether the recorded input reported key releases.
+    pub reports_releases: bool,
+
+    /// The cycles of the polls that changed the keys, and the keys from then on.
+    polls: Vec<(u64, u16)>,
+
+    /// Index of the next poll to replay.
+    next: usize,
+
+    /// The keys of the last replayed poll.
+    keys: u16,
+}
+
+impl Playback {
+    /// Load a recording from a file.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        text.parse()
+            .map_err(|e| format!("Invalid recording '{}': {}", path.display(), e).into())
+    }
+
+    /// The keys to report for a poll at `cycle`. Each poll replays at most one recorded poll, so keys that changed
+    /// several times in the same cycle change as often in the playback.
+    pub fn poll(&mut self, cycle: u64) -> u16 {
+        if let Some(&(at, keys)) = self.polls.get(self.next)
+            && at <= cycle
+        {
+            self.keys = keys;
+            self.next += 1;
+        }
+        self.keys
+    }
+
+    /// Whether all recorded polls were replayed.
+    pub fn is_finished(&self) -> bool {
+        self.next == self.polls.len()
+    }
+}
+
+impl FromStr for Playback {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut seed = None;
+        let mut clock_hz = None;
+        let mut reports_releases = true;
+        let mut polls = Vec::new();
+
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |what: &str| format!("Line {}: invalid {what} '{line}'", number + 1);
+
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("seed") => {
+                    seed = Some(
+                        fields
+                            .next()
+                            .and_then(|s| s.parse().ok())
+                            .ok_or_else(|| invalid("seed"))?,
+                    )
+                }
+                Some("clock") => {
+                    clock_hz = Some(
+                        fields
+                            .next()
+                            .and_then(|s| s.parse().ok())
+                            .ok_or_else(|| invalid("clock"))?,
+                    )
+                }
+                Some("releases") => {
+                    reports_releases = fields
+                        .next()
+                        .and_then(|s| s.parse().ok())
+                        .ok_or_else(|| invalid("releases"))?
+                }
+                Some(frame) if frame.parse::<u64>().is_ok() => {
+                    // The frame is only there for people reading the recording
+                    let cycle = fields
+                        .next()
+                        .and_then(|s| s.parse().ok())
+                        .ok_or_else(|| invalid("poll"))?;
+                    let keys = parse_keys(fields).ok_or_else(|| invalid("keys"))?;
+                    polls.push((cycle, keys));
+                }
+                Some(_) => return Err(invalid("line")),
+                None => unreachable!("Empty lines are skipped"),
+            }
+        }
+
+        Ok(Self {
+            seed: seed.ok_or("Missing seed")?,
+            clock_hz: clock_hz.ok_or("Missing clock")?,
+            reports_releases,
+            polls,
+            next: 0,
+            keys: 0,
+        })
+    }
+}
+
+/// The held keys as hex digits separated by spaces, or `-` for none.
+fn format_keys(keys: u16) -> String {
+    if keys == 0 {
+        return "-".to_string();
+    }
+    (0..16)
+        .filter(|key| keys & (1 << key) != 0)
+        .map(|key| format!("{key:X}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parse the keys written by `format_keys`.
+fn parse_keys<'a>(fields: impl Iterator<Item = &'a str>) -> Option<u16> {
+    let mut keys = 0;
+    for field in fields {
+        if field == "-" {
+            continue;
+        }
+        let key = u8::from_str_radix(field, 16).ok().filter(|&key| key < 16)?;
+        keys |= 1 << key;
+    }
+    Some(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_recording() {
+        let text =
+            "# chip8 replay\nseed 42\nclock 700\nreleases false\n35 408 5\n40 466 5 A\n52 606 -\n";
+        let mut playback: Playback = text.parse().expect("Failed to parse recording");
+
+        assert_eq!(playback.seed, 42);
+        assert_eq!(playback.clock_hz, 700);
+        assert!(!playback.reports_releases);
+        assert_eq!(playback.poll(400), 0);
+        assert_eq!(playback.poll(408), 1 << 5);
+        assert_eq!(playback.poll(500), 1 << 5 | 1 << 0xA);
+        assert!(!playback.is_finished());
+        assert_eq!(playback.poll(606), 0);
+        assert!(playback.is_finished());
+
+        assert!("clock 700\n".parse::<Playback>().is_err());
+        assert!("seed 1\nclock 700\n1 2 G\n".parse::<Playback>().is_err());
+        assert_eq!(
+            "seed 1\nclock 700\nfoo 12 5\n".parse::<Playback>(),
+            Err("Line 3: invalid line 'foo 12 5'".to_string())
+        );
+    }
+}
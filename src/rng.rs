@@ -0,0 +1,49 @@
+//! Pseudo-random number generator for the `CXNN` instruction.
+//!
+//! A small SplitMix64 generator, so a run can be reproduced from its seed without depending on an external crate.
+
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, Hasher};
+
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Create a generator from a seed. The same seed always produces the same sequence.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Return the next random byte.
+    pub fn next_u8(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    /// Return the next random 64 bit value.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE5_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// Generate a seed from OS entropy.
+///
+/// The standard library seeds the keys of `RandomState` from the OS, so hashing with a fresh one yields a random value.
+#[cfg(feature = "std")]
+pub fn entropy_seed() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// Without the standard library there is no OS entropy, so this is a fixed seed. Seed the generator from a hardware
+/// source with `State::set_seed` instead.
+#[cfg(not(feature = "std"))]
+pub fn entropy_seed() -> u64 {
+    0x5EED
+}
@@ -0,0 +1,146 @@
+//! Helpers for comparing, fingerprinting and printing screen buffers, e.g. in rendering regression tests.
+//!
+//! Screen buffers are `WIDTH` × `HEIGHT` pixels, left to right from the upper-left corner, where true means on.
+
+use crate::constants::WIDTH;
+use crate::hash::fnv1a;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Compute the indices of the pixels that differ between two screen buffers.
+///
+/// If the buffers have different lengths, the missing pixels of the shorter one count as off.
+///
+/// # Arguments
+/// * `a` - The first screen buffer.
+/// * `b` - The second screen buffer.
+///
+/// # Returns
+/// The indices of the differing pixels, in ascending order.
+pub fn framebuffer_diff(a: &[bool], b: &[bool]) -> Vec<usize> {
+    (0..a.len().max(b.len()))
+        .filter(|&i| pixel(a, i) != pixel(b, i))
+        .collect()
+}
+
+/// Render the difference between two screen buffers as text, one line per row.
+///
+/// Matching pixels are shown as a space, pixels only on in `a` as `-`, and pixels only on in `b` as `+`.
+///
+/// # Arguments
+/// * `a` - The first screen buffer.
+/// * `b` - The second screen buffer.
+pub fn render_diff_ascii(a: &[bool], b: &[bool]) -> String {
+    let len = a.len().max(b.len());
+    let mut diff = String::with_capacity(len + len / WIDTH);
+
+    for i in 0..len {
+        diff.push(match (pixel(a, i), pixel(b, i)) {
+            (true, false) => '-',
+            (false, true) => '+',
+            _ => ' ',
+        });
+        if (i + 1) % WIDTH == 0 {
+            diff.push('\n');
+        }
+    }
+
+    diff
+}
+
+/// Render a screen buffer as text, with `█` for pixels that are on, and a line per row.
+///
+/// # Arguments
+/// * `screen` - The screen buffer.
+pub fn screen_to_string(screen: &[bool]) -> String {
+    screen
+        .chunks(WIDTH)
+        .map(|row| {
+            let mut line: String = row.iter().map(|&on| if on { '█' } else { ' ' }).collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+/// Pack a screen buffer into bytes, 8 pixels per byte with the leftmost in the most significant bit, like sprite data.
+/// A 64x32 screen packs into 256 bytes.
+///
+/// # Arguments
+/// * `screen` - The screen buffer.
+pub fn screen_bytes(screen: &[bool]) -> Vec<u8> {
+    screen
+        .chunks(8)
+        .map(|pixels| {
+            pixels
+                .iter()
+                .enumerate()
+                .fold(0, |byte, (i, &on)| byte | (on as u8) << (7 - i))
+        })
+        .collect()
+}
+
+/// Compute a fingerprint of a screen buffer, to compare against an expected value in tests. The hash is the 64 bit
+/// FNV-1a of the packed `screen_bytes`, so it is stable across platforms and Rust versions.
+///
+/// # Arguments
+/// * `screen` - The screen buffer.
+pub fn screen_hash(screen: &[bool]) -> u64 {
+    fnv1a(&screen_bytes(screen))
+}
+
+/// The pixel at `index`, or off if it is out of bounds.
+fn pixel(screen: &[bool], index: usize) -> bool {
+    screen.get(index).copied().unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::HEIGHT;
+
+    #[test]
+    fn diff_two_patterns() {
+        let mut a = [false; WIDTH * HEIGHT];
+        let mut b = [false; WIDTH * HEIGHT];
+        a[0] = true; // Only in a
+        a[1] = true; // In both
+        b[1] = true;
+        b[WIDTH + 2] = true; // Only in b
+
+        assert_eq!(framebuffer_diff(&a, &b), [0, WIDTH + 2]);
+
+        let diff = render_diff_ascii(&a, &b);
+        let lines: Vec<&str> = diff.lines().collect();
+        assert_eq!(lines.len(), HEIGHT);
+        assert_eq!(lines[0], format!("-{}", " ".repeat(WIDTH - 1)));
+        assert_eq!(lines[1], format!("  +{}", " ".repeat(WIDTH - 3)));
+        assert!(lines[2..].iter().all(|line| line.trim().is_empty()));
+    }
+
+    #[test]
+    fn identical_buffers_have_no_diff() {
+        let a = [true; WIDTH * HEIGHT];
+
+        assert!(framebuffer_diff(&a, &a).is_empty());
+        assert!(render_diff_ascii(&a, &a).trim().is_empty());
+    }
+
+    #[test]
+    fn pack_and_hash() {
+        let mut screen = [false; WIDTH * HEIGHT];
+        screen[0] = true;
+        screen[7] = true;
+        screen[WIDTH + 9] = true;
+
+        let bytes = screen_bytes(&screen);
+        assert_eq!(bytes.len(), 256);
+        assert_eq!(bytes[..2], [0x81, 0x00]);
+        assert_eq!(bytes[WIDTH / 8 + 1], 0x40);
+        assert_eq!(bytes.iter().filter(|&&byte| byte != 0).count(), 2);
+
+        // FNV-1a of 256 zero bytes
+        assert_eq!(screen_hash(&[false; WIDTH * HEIGHT]), 0xD80A_C658_736B_B725);
+        assert_ne!(screen_hash(&screen), screen_hash(&[false; WIDTH * HEIGHT]));
+    }
+}
@@ -0,0 +1,97 @@
+//! Export of the screen as PNG images, e.g. for golden-image regression tests and screenshots.
+//!
+//! Only available with the `png` feature.
+
+use crate::constants::{HEIGHT, HIRES_HEIGHT, WIDTH};
+use image::{Rgb, RgbImage};
+use std::path::Path;
+
+/// Color of lit pixels.
+pub const FOREGROUND: Rgb<u8> = Rgb([0xFF, 0xFF, 0xFF]);
+
+/// Color of unlit pixels.
+pub const BACKGROUND: Rgb<u8> = Rgb([0x00, 0x00, 0x00]);
+
+/// Render a screen buffer to an image, upscaling each CHIP-8 pixel to a `scale` × `scale` block.
+///
+/// # Arguments
+/// * `screen` - The screen buffer, `WIDTH` × `HEIGHT` (or `HIRES_HEIGHT`) pixels, left to right from the upper-left corner.
+/// * `scale` - The integer scale factor, at least 1.
+///
+/// # Returns
+/// * `Ok(RgbImage)` with the rendered screen.
+/// * `Err` if the screen buffer has the wrong size, or the scale is 0.
+pub fn screen_to_image(
+    screen: &[bool],
+    scale: u32,
+) -> Result<RgbImage, Box<dyn std::error::Error>> {
+    let height = screen.len() / WIDTH;
+    if screen.len() != WIDTH * HEIGHT && screen.len() != WIDTH * HIRES_HEIGHT {
+        return Err(format!(
+            "Screen buffer has {} pixels, expected {} or {}",
+            screen.len(),
+            WIDTH * HEIGHT,
+            WIDTH * HIRES_HEIGHT
+        )
+        .into());
+    }
+    if scale == 0 {
+        return Err("Scale must be at least 1".into());
+    }
+
+    let image = RgbImage::from_fn(WIDTH as u32 * scale, height as u32 * scale, |x, y| {
+        let column = (x / scale) as usize;
+        let row = (y / scale) as usize;
+        if screen[row * WIDTH + column] {
+            FOREGROUND
+        } else {
+            BACKGROUND
+        }
+    });
+
+    Ok(image)
+}
+
+/// Render a screen buffer to a PNG file, upscaling each CHIP-8 pixel to a `scale` × `scale` block.
+///
+/// # Arguments
+/// * `screen` - The screen buffer, `WIDTH` × `HEIGHT` (or `HIRES_HEIGHT`) pixels, left to right from the upper-left corner.
+/// * `path` - The path of the PNG file to write.
+/// * `scale` - The integer scale factor, at least 1.
+///
+/// # Returns
+/// * `Ok(())` if the file was written.
+/// * `Err` if the arguments are invalid, or the file couldn't be written.
+pub fn screen_to_png(
+    screen: &[bool],
+    path: &Path,
+    scale: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    screen_to_image(screen, scale)?.save_with_format(path, image::ImageFormat::Png)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screen_is_scaled_to_blocks() {
+        let mut screen = [false; WIDTH * HEIGHT];
+        screen[WIDTH + 2] = true; // (2, 1)
+
+        let image = screen_to_image(&screen, 3).expect("Failed to render screen");
+
+        assert_eq!(image.dimensions(), (WIDTH as u32 * 3, HEIGHT as u32 * 3));
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let lit = (6..9).contains(&x) && (3..6).contains(&y);
+            assert_eq!(*pixel, if lit { FOREGROUND } else { BACKGROUND });
+        }
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        assert!(screen_to_image(&[false; WIDTH * HEIGHT], 0).is_err());
+        assert!(screen_to_image(&[false; 10], 1).is_err());
+    }
+}
@@ -3,128 +3,802 @@
 //! including memory, registers, timers, stack, and display.
 //!
 //! The memory layout is as follows:
-//! - 0x000 to 0x1FF: Reserved for the interpreter (including font set)
+//! - 0x000 to 0x1FF: Reserved for the interpreter
+//!   - 0x000 to 0x04F: Small font (`SMALL_FONT_OFFSET`)
+//!   - 0x050 to 0x0EF: Big font (`LARGE_FONT_OFFSET`)
+//!
+//!   `State::with_font_offset` moves both fonts, e.g. to 0x050 like most interpreters, with the big font right after
+//!   the small one.
 //! - 0x200 to 0xFFF: Program memory and data
-//! - 0xEA0 to 0xEFF: Call stack (not explicitly modeled in this implementation)
-//! - 0xF00 to 0xFFF: Display refresh area (not explicitly modeled in this implementation)
+//! - 0xEA0 to 0xEFF: Call stack on the COSMAC VIP (only used with `State::memory_stack`, otherwise free for programs)
+//! - 0xF00 to 0xFFF: Display refresh area on the COSMAC VIP (not modeled in this implementation, so free for programs)
+//!
+//! Memory outside the fonts starts zeroed, unless `State::trap_uninitialized_memory` fills it with HALT instructions.
+//!
+//! Larger memory maps, like XO-CHIP's 64KB (see `State::with_memory_size`), extend the program memory up to the end.
 //!
-//! We don't actually model the stack, to keep things simple. In reality, the stack is an area of memory used to store up
-//! to 8 12 bit addresses, but we just keep those addresses in an array growing from index 0. The area of memory is unused.
+//! By default we don't model the stack in memory, to keep things simple. In reality, the stack is an area of memory used
+//! to store 12 bit return addresses, but we just keep those addresses in an array growing from index 0, and the area of
+//! memory is unused. With `memory_stack`, `2NNN` also writes each return address to memory at `STACK_OFFSET`, two bytes
+//! big-endian per call, with the length of the array as the stack pointer, and `00EE` returns to the address it reads
+//! back from there. A program that inspects (or overwrites) the stack then sees real return addresses.
 //!
 //! The `State` struct provides methods to initialize the state, load a ROM into memory,
-//! and bootstrap the built-in character set.
+//! and bootstrap the built-in character sets.
 use crate::constants;
-use std::collections::VecDeque;
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::PathBuf;
+use crate::error::Chip8Error;
+use crate::font;
+use crate::quirks::Quirks;
+use crate::rng::{self, Rng};
+use crate::screen;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+use core::str::FromStr;
+use core::time::Duration;
+use log::*;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+
+/// Size of both fonts, which are kept together.
+const FONTS_SIZE: usize = font::SMALL_FONT.len() + font::BIG_FONT.len();
+
+/// What happens when the program counter runs past the end of memory, e.g. into a program that doesn't end in a jump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PcOverflow {
+    /// Wrap around to address 0 and keep executing whatever is there, like the original interpreters.
+    #[default]
+    Wrap,
+
+    /// Stop the program, as if it executed `HALT 0`.
+    Halt,
 
+    /// Fail with `Chip8Error::PcOverflow`.
+    Error,
+}
+
+impl FromStr for PcOverflow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "wrap" => Ok(PcOverflow::Wrap),
+            "halt" => Ok(PcOverflow::Halt),
+            "error" => Ok(PcOverflow::Error),
+            _ => Err(format!(
+                "Unknown PC overflow policy '{s}', expected 'wrap', 'halt' or 'error'"
+            )),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct State {
-    pub screen: [bool; constants::WIDTH * constants::HEIGHT],
+    /// Screen buffer, large enough for the hires mode. Only the upper `height()` rows are visible.
+    pub screen: [bool; constants::WIDTH * constants::HIRES_HEIGHT],
+
+    /// Second display plane (XO-CHIP), laid out like `screen`, which is the first plane.
+    pub plane2: [bool; constants::WIDTH * constants::HIRES_HEIGHT],
+
+    /// Planes that `DXYN` draws to and `00E0` clears, as selected by `FN01`: bit 0 for `screen`, and bit 1 for `plane2`.
+    /// Only used with the `xo_chip_planes` quirk.
+    pub planes: u8,
+
+    /// Whether the 64×64 hires mode of the COSMAC VIP is active (see `set_hires`).
+    pub hires: bool,
 
-    pub delay_timer: u8,
-    pub sound_timer: u8,
+    /// Whether the screen changed since `Emulator::step` last checked, to invoke the display hook (see
+    /// `Emulator::set_display_hook`). Set when `DXYN` flips a pixel, and when the screen is cleared.
+    pub screen_dirty: bool,
 
-    /// Address register, only lower 12 bits used
+    /// Delay timer, counting down at 60Hz. Read and set through `Emulator::delay_timer`/`set_delay_timer` outside
+    /// the crate.
+    pub(crate) delay_timer: u8,
+
+    /// Sound timer, counting down at 60Hz while the buzzer sounds, see `Emulator::is_beeping`.
+    pub(crate) sound_timer: u8,
+
+    /// Address register, only the bits in `address_mask` used
     pub i: usize,
 
-    /// Pixels are stored in order, left to right from the upper-left corner. True means on, false means off.
-    pub memory: [u8; constants::MEMORY_SIZE],
+    /// Main memory, `MEMORY_SIZE` bytes unless created with `with_memory_size`.
+    pub memory: Vec<u8>,
 
-    /// Program counter, only lower 12 bits used
+    /// Program counter, only the bits in `address_mask` used, unless it ran past the end of memory and `pc_overflow`
+    /// stopped the program there.
     pub pc: usize,
 
-    /// Up to 12 levels of nested return addresses
+    /// What happens when the program counter runs past the end of memory.
+    pub pc_overflow: PcOverflow,
+
+    /// Address the program started from, where `reset` restarts it.
+    pub entry_point: usize,
+
+    /// Memory the ROM was loaded into, see `rom_len` and `reload_rom`. Empty until a ROM is loaded.
+    rom: Range<usize>,
+
+    /// Address of the small font, followed by the big font, see `with_font_offset`.
+    font_offset: usize,
+
+    /// Mask applied to computed addresses: 0xFFF for 12-bit addressing, 0xFFFF for 16-bit addressing in a 64KB map.
+    /// Must be less than the memory size.
+    pub address_mask: usize,
+
+    /// Nested return addresses, up to `max_stack_depth`
     pub stack: VecDeque<usize>,
 
+    /// Limit on nested subroutine calls, beyond which `2NNN` fails with a stack overflow. None for unlimited.
+    pub max_stack_depth: Option<usize>,
+
+    /// Keep the return addresses in memory at `STACK_OFFSET` as well, and return to the addresses read back from there.
+    /// The stack can then hold at most `MEMORY_STACK_DEPTH` addresses, whatever `max_stack_depth` is.
+    pub memory_stack: bool,
+
+    /// Refuse loads over the fonts with `load_at`.
+    pub protect_fonts: bool,
+
     /// Registers V0 to VF. VF is the carry flag, while in subtraction, it is the "no borrow" flag. In the draw instruction VF is set upon pixel collision.
     pub v: [u8; 16],
 
-    /// Currently pressed key, if any.
-    pub key_pressed: Option<u8>,
+    /// SUPER-CHIP RPL user flags, saved and restored with `FX75`/`FX85`.
+    pub flags: [u8; 8],
 
-    /// Time when the key was pressed.
-    pub key_pressed_at: std::time::SystemTime,
+    /// Bitmask of the held keys, with bit `n` set while key `n` is down.
+    pub keys: u16,
+
+    /// Time when each key was last reported as held.
+    pub keys_pressed_at: [Duration; constants::KEY_COUNT],
+
+    /// How long a key is held after it was last reported, if the input doesn't report releases.
+    pub key_timeout: Duration,
 
     /// If the interpreter is waiting for a key press this will be some, and the value is the register index to store the key in.
     pub waiting_for_keypress: Option<usize>,
+
+    /// If the interpreter is waiting for a vertical blank after drawing (see `Quirks::display_wait`).
+    pub waiting_for_vblank: bool,
+
+    /// Number of cycles since the interpreter started.
+    pub cycles: u64,
+
+    /// Interpreter behavior to emulate.
+    pub quirks: Quirks,
+
+    /// Fail on unknown opcodes, rather than ignoring them with a warning.
+    pub strict: bool,
+
+    /// Seed of the random number generator, to reproduce a run.
+    pub seed: u64,
+
+    /// Random number generator for `CXNN`.
+    pub rng: Rng,
 }
 
 impl State {
+    /// Create a new state, with the random number generator seeded from OS entropy.
     pub fn new() -> Self {
+        Self::with_seed(rng::entropy_seed())
+    }
+
+    /// Create a new state, with the random number generator seeded with `seed`. States with the same seed produce the
+    /// same `CXNN` results.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::build(seed, constants::MEMORY_SIZE)
+    }
+
+    /// Create a new state with a larger memory map, e.g. `XO_CHIP_MEMORY_SIZE`, and the random number generator seeded
+    /// from OS entropy. Addresses are masked to the size of the memory.
+    ///
+    /// # Arguments
+    /// * `size` - The memory size in bytes, a power of two of at least `MEMORY_SIZE`.
+    ///
+    /// # Panics
+    /// If `size` isn't a power of two, or is less than `MEMORY_SIZE`.
+    pub fn with_memory_size(size: usize) -> Self {
+        assert!(
+            size.is_power_of_two() && size >= constants::MEMORY_SIZE,
+            "Memory size {size} is not a power of two of at least {}",
+            constants::MEMORY_SIZE
+        );
+        Self::build(rng::entropy_seed(), size)
+    }
+
+    /// Set registers V0 to VF, e.g. to set up a test in one expression with the other `with_` methods.
+    pub fn with_registers(mut self, v: [u8; 16]) -> Self {
+        self.v = v;
+        self
+    }
+
+    /// Set the index register I.
+    pub fn with_i(mut self, i: usize) -> Self {
+        self.i = i;
+        self
+    }
+
+    /// Set the program counter.
+    pub fn with_pc(mut self, pc: usize) -> Self {
+        self.pc = pc;
+        self
+    }
+
+    /// Copy bytes into memory at `address`, e.g. an instruction or sprite data, see `load_at`.
+    ///
+    /// # Panics
+    /// If the bytes don't fit in memory after `address`.
+    pub fn with_memory_at(mut self, address: usize, bytes: &[u8]) -> Self {
+        self.memory[address..address + bytes.len()].copy_from_slice(bytes);
+        self
+    }
+
+    fn build(seed: u64, memory_size: usize) -> Self {
         let mut state = Self {
             delay_timer: 0,
             sound_timer: 0,
             i: 0,
-            memory: [0; constants::MEMORY_SIZE],
-            pc: 0x200,
-            screen: [false; constants::WIDTH * constants::HEIGHT],
+            memory: vec![0; memory_size],
+            pc: constants::ENTRY_POINT,
+            pc_overflow: PcOverflow::default(),
+            entry_point: constants::ENTRY_POINT,
+            rom: constants::ENTRY_POINT..constants::ENTRY_POINT,
+            font_offset: constants::SMALL_FONT_OFFSET,
+            address_mask: memory_size - 1,
+            screen: [false; constants::WIDTH * constants::HIRES_HEIGHT],
+            plane2: [false; constants::WIDTH * constants::HIRES_HEIGHT],
+            planes: 1,
+            hires: false,
+            screen_dirty: false,
             stack: VecDeque::new(),
+            max_stack_depth: Some(constants::STACK_DEPTH),
+            memory_stack: false,
+            protect_fonts: false,
             v: [0; 16],
-            key_pressed: None,
-            key_pressed_at: std::time::SystemTime::now(),
+            flags: [0; 8],
+            keys: 0,
+            keys_pressed_at: [Duration::ZERO; constants::KEY_COUNT],
+            key_timeout: constants::KEY_PRESS_TIMEOUT_MS,
             waiting_for_keypress: None,
+            waiting_for_vblank: false,
+            cycles: 0,
+            quirks: Quirks::default(),
+            strict: false,
+            seed,
+            rng: Rng::new(seed),
         };
         state.bootstrap_character_rom();
-        for i in (0x040..0x200).step_by(2) {
-            // Insert a HALT instruction in unused memory to prevent accidental execution
-            state.memory[i] = 0xFF;
-            state.memory[i + 1] = 0xFF;
+        state
+    }
+
+    /// Fill the memory after the fonts with `HALT 15` (0xFFFF) instructions, so a program that runs off into memory
+    /// it didn't load halts rather than executing zeroes or data. Call it before loading the ROM, which overwrites the
+    /// traps where it is loaded.
+    ///
+    /// This is a debugging aid, and not the default: real interpreters start with zeroed (or random) memory, and
+    /// programs may read memory they never wrote, e.g. scratch space used with `FX33` and `FX65`, expecting zeroes.
+    /// Runaway programs are also caught by strict mode, `Emulator::halt_on_infinite_loop`, and at the end of memory by
+    /// `pc_overflow`.
+    pub fn trap_uninitialized_memory(&mut self) {
+        let fonts_end = self.fonts().end;
+        self.memory[fonts_end..].fill(0xFF);
+    }
+
+    /// Load a ROM into memory at 0x200.
+    ///
+    /// # Arguments
+    /// * `rom` - The ROM image.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the ROM was loaded.
+    /// * `Err` if the ROM doesn't fit in memory.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        self.load_rom_at(rom, constants::ENTRY_POINT)
+    }
+
+    /// Load a ROM into memory at an entry point, e.g. 0x600 for ETI-660 programs, and start executing from there.
+    ///
+    /// # Arguments
+    /// * `rom` - The ROM image.
+    /// * `entry` - The address to load the ROM at, and the initial program counter. At least 0x200, so the fonts are
+    ///   kept.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the ROM was loaded.
+    /// * `Err` if the entry point is below 0x200, the ROM is too small to hold an instruction, or it doesn't fit in
+    ///   memory after the entry point.
+    pub fn load_rom_at(&mut self, rom: &[u8], entry: usize) -> Result<(), Chip8Error> {
+        if entry < constants::ENTRY_POINT {
+            return Err(Chip8Error::InvalidEntryPoint(entry));
         }
-        state.memory[0xE9E] = 0x12; // Insert a jump to start of program at 0x200 to prevent accidental execution of uninitialized memory
-        for i in (0xEA0..=0xFFF).step_by(2) {
-            // Insert a HALT instruction in unused memory to prevent accidental execution
-            state.memory[i] = 0xFF;
-            state.memory[i + 1] = 0xFF;
+        if rom.len() < 2 {
+            return Err(Chip8Error::RomTooSmall(rom.len()));
         }
-        state
+        self.copy_rom(rom, entry)?;
+        self.pc = entry;
+        self.entry_point = entry;
+
+        // Hires programs start by jumping to the VIP interpreter patch at 0x260, which is replaced by `set_hires`
+        if entry == constants::ENTRY_POINT && rom.starts_with(&[0x12, 0x60]) {
+            self.set_hires(true);
+            self.pc = constants::HIRES_ENTRY_POINT;
+            self.entry_point = constants::HIRES_ENTRY_POINT;
+        }
+
+        Ok(())
     }
 
-    /// Load the built-in character set into memory in the ROM into memory in the first 512 bytes.
-    /// Each character is 5 bytes (5 rows of 8 pixels, only the upper 4 bits are used).
-    pub fn bootstrap_character_rom(&mut self) {
-        let charmap: [[u8; 5]; 16] = [
-            [0xF0, 0x90, 0x90, 0x90, 0xF0], // 0
-            [0x20, 0x60, 0x20, 0x20, 0x70], // 1
-            [0xF0, 0x10, 0xF0, 0x80, 0xF0], // 2
-            [0xF0, 0x10, 0xF0, 0x10, 0xF0], // 3
-            [0x90, 0x90, 0xF0, 0x10, 0x10], // 4
-            [0xF0, 0x80, 0xF0, 0x10, 0xF0], // 5
-            [0xF0, 0x80, 0xF0, 0x90, 0xF0], // 6
-            [0xF0, 0x10, 0x20, 0x40, 0x40], // 7
-            [0xF0, 0x90, 0xF0, 0x90, 0xF0], // 8
-            [0xF0, 0x90, 0xF0, 0x10, 0xF0], // 9
-            [0xF0, 0x90, 0xF0, 0x90, 0x90], // A
-            [0xE0, 0x90, 0xE0, 0x90, 0xE0], // B
-            [0xF0, 0x80, 0x80, 0x80, 0xF0], // C
-            [0xE0, 0x90, 0x90, 0x90, 0xE0], // D
-            [0xF0, 0x80, 0xF0, 0x80, 0xF0], // E
-            [0xF0, 0x80, 0xF0, 0x80, 0x80], // F
-        ];
-
-        let mut i = 0;
-        for char_bytes in &charmap {
-            for &b in char_bytes {
-                self.memory[constants::CHARACTER_SPRITE_OFFSET + i] = b;
-                i += 1;
+    /// Move the fonts to `offset`, e.g. 0x050 where most interpreters kept them, with the big font right after the small
+    /// one. `FX29` and `FX30` point at the moved glyphs, and the memory they occupied before is zeroed.
+    ///
+    /// # Panics
+    /// If the fonts don't fit below 0x200 at `offset`.
+    pub fn with_font_offset(mut self, offset: usize) -> Self {
+        assert!(
+            offset + FONTS_SIZE <= constants::ENTRY_POINT,
+            "Fonts at 0x{offset:03X} don't fit below 0x{:03X}",
+            constants::ENTRY_POINT
+        );
+        let fonts = self.fonts();
+        self.memory[fonts].fill(0);
+        self.font_offset = offset;
+        self.bootstrap_character_rom();
+        self
+    }
+
+    /// Address of the small font, used by `FX29`.
+    pub fn font_offset(&self) -> usize {
+        self.font_offset
+    }
+
+    /// Address of the big font, used by `FX30`.
+    pub fn big_font_offset(&self) -> usize {
+        self.font_offset + font::SMALL_FONT.len()
+    }
+
+    /// Memory occupied by both fonts.
+    pub fn fonts(&self) -> Range<usize> {
+        self.font_offset..self.font_offset + FONTS_SIZE
+    }
+
+    /// Number of bytes of the ROM that was loaded, e.g. to tell the code and data apart from the free memory after it.
+    pub fn rom_len(&self) -> usize {
+        self.rom.len()
+    }
+
+    /// Copy a ROM over the one that was loaded, at the same address, without resetting the registers or jumping to the
+    /// entry point, e.g. to swap the ROM between runs in a test harness. Memory past the end of the new ROM is kept as
+    /// is.
+    ///
+    /// # Arguments
+    /// * `rom` - The ROM image.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the ROM was copied.
+    /// * `Err` if the ROM doesn't fit in memory.
+    pub fn reload_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        self.copy_rom(rom, self.rom.start)
+    }
+
+    /// Copy bytes into memory at any address, e.g. sprite data or a second program next to the ROM. Can be called any
+    /// number of times, and leaves the registers, the program counter and the ROM that was loaded as they are.
+    ///
+    /// # Arguments
+    /// * `bytes` - The bytes to copy.
+    /// * `address` - Where to copy the first byte to.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the bytes were copied.
+    /// * `Err` if they don't fit in memory after `address`, or would overwrite the fonts with `protect_fonts` set.
+    pub fn load_at(&mut self, bytes: &[u8], address: usize) -> Result<(), Chip8Error> {
+        if address > self.memory.len() || bytes.len() > self.memory.len() - address {
+            return Err(Chip8Error::LoadOutOfBounds {
+                size: bytes.len(),
+                address,
+            });
+        }
+        let fonts = self.fonts();
+        if self.protect_fonts
+            && !bytes.is_empty()
+            && address < fonts.end
+            && address + bytes.len() > fonts.start
+        {
+            return Err(Chip8Error::FontOverwrite {
+                size: bytes.len(),
+                address,
+            });
+        }
+
+        self.memory[address..address + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Copy a ROM into memory at `address`, and remember where it went.
+    fn copy_rom(&mut self, rom: &[u8], address: usize) -> Result<(), Chip8Error> {
+        if address > self.memory.len() || rom.len() > self.memory.len() - address {
+            return Err(Chip8Error::RomTooLarge {
+                size: rom.len(),
+                entry: address,
+            });
+        }
+
+        self.memory[address..address + rom.len()].copy_from_slice(rom);
+        self.rom = address..address + rom.len();
+        Ok(())
+    }
+
+    /// Restart the program: clear the registers, timers, stack, screen and keys, reseed the random number generator
+    /// with `seed`, and jump to the entry point. The memory, with the program and the fonts, is kept as is, as are the
+    /// RPL user flags and the configuration, like the quirks.
+    pub fn reset(&mut self) {
+        self.v = [0; 16];
+        self.i = 0;
+        self.pc = self.entry_point;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.stack.clear();
+        self.screen.fill(false);
+        self.plane2.fill(false);
+        self.screen_dirty = true;
+        self.planes = 1;
+        self.keys = 0;
+        self.keys_pressed_at = [Duration::ZERO; constants::KEY_COUNT];
+        self.waiting_for_keypress = None;
+        self.waiting_for_vblank = false;
+        self.cycles = 0;
+        self.rng = Rng::new(self.seed);
+    }
+
+    /// Switch between the standard 64×32 display and the 64×64 hires mode, and clear the screen.
+    ///
+    /// # Arguments
+    /// * `hires` - Whether to enable the hires mode.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.screen.fill(false);
+        self.plane2.fill(false);
+        self.screen_dirty = true;
+    }
+
+    /// The height of the display in the current mode.
+    pub fn height(&self) -> usize {
+        if self.hires {
+            constants::HIRES_HEIGHT
+        } else {
+            constants::HEIGHT
+        }
+    }
+
+    /// The visible part of the screen buffer, `WIDTH` × `height()` pixels.
+    pub fn visible_screen(&self) -> &[bool] {
+        &self.screen[..constants::WIDTH * self.height()]
+    }
+
+    /// The visible part of both display planes composited, with each pixel from 0 to 3: bit 0 is set if the pixel is on
+    /// in `screen`, and bit 1 if it is on in `plane2`.
+    pub fn visible_planes(&self) -> Vec<u8> {
+        let len = constants::WIDTH * self.height();
+        self.screen[..len]
+            .iter()
+            .zip(&self.plane2[..len])
+            .map(|(&first, &second)| first as u8 | (second as u8) << 1)
+            .collect()
+    }
+
+    /// Whether the pixel at `x`, `y` is on.
+    ///
+    /// # Panics
+    /// If `x` or `y` is outside of the visible screen.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.screen[self.pixel_index(x, y)]
+    }
+
+    /// Turn the pixel at `x`, `y` on or off.
+    ///
+    /// # Panics
+    /// If `x` or `y` is outside of the visible screen.
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        let index = self.pixel_index(x, y);
+        self.screen[index] = on;
+    }
+
+    /// Render the visible screen as text, with `█` for pixels that are on, and a line per row.
+    pub fn screen_to_string(&self) -> String {
+        screen::screen_to_string(self.visible_screen())
+    }
+
+    /// The visible screen packed into bytes, 8 pixels per byte, see `screen::screen_bytes`.
+    pub fn screen_bytes(&self) -> Vec<u8> {
+        screen::screen_bytes(self.visible_screen())
+    }
+
+    /// A stable fingerprint of the visible screen, for golden tests, see `screen::screen_hash`.
+    pub fn screen_hash(&self) -> u64 {
+        screen::screen_hash(self.visible_screen())
+    }
+
+    fn pixel_index(&self, x: usize, y: usize) -> usize {
+        assert!(
+            x < constants::WIDTH && y < self.height(),
+            "Pixel ({x}, {y}) is outside of the {}x{} screen",
+            constants::WIDTH,
+            self.height()
+        );
+        y * constants::WIDTH + x
+    }
+
+    /// Reseed the random number generator.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Rng::new(seed);
+    }
+
+    /// Load the RPL user flags from a file written by `save_flags`.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the flags file.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the flags were loaded.
+    /// * `Err` if the file couldn't be read, or doesn't contain exactly 8 flags. The flags are unchanged.
+    #[cfg(feature = "std")]
+    pub fn load_flags(&mut self, path: &Path) -> std::io::Result<()> {
+        let bytes = std::fs::read(path)?;
+        if bytes.len() != self.flags.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Expected {} bytes of flags, found {}",
+                    self.flags.len(),
+                    bytes.len()
+                ),
+            ));
+        }
+        self.flags.copy_from_slice(&bytes);
+        Ok(())
+    }
+
+    /// Save the RPL user flags to a file, so they survive between sessions.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the flags file.
+    #[cfg(feature = "std")]
+    pub fn save_flags(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.flags)
+    }
+
+    /// Decrement the delay and sound timers, if they are non-zero. Should be called at 60Hz.
+    pub fn tick_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
+    /// Compute how many cycles remain until the next 60Hz timer tick, at the given clock frequency.
+    ///
+    /// Timer ticks happen every `clock_hz / TIMER_FREQ` cycles (at least every cycle), counted from the start of the
+    /// interpreter, so a value of 1 means the current cycle is the last one of the frame.
+    ///
+    /// # Arguments
+    /// * `clock_hz` - The number of cycles per second.
+    pub fn cycles_until_timer_tick(&self, clock_hz: u32) -> usize {
+        let cycles_per_tick = (clock_hz / constants::TIMER_FREQ).max(1) as u64;
+        (cycles_per_tick - self.cycles % cycles_per_tick) as usize
+    }
+
+    /// Record the result of an input poll.
+    ///
+    /// Each key reported by the poll is (re-)asserted as held, and its timeout restarts. A held key that the poll didn't
+    /// report is released right away if the input reports releases. Otherwise, it is only released once `key_timeout`
+    /// has passed since it was last reported. Since the timeout is only evaluated here, `EX9E`/`EXA1` always see the
+    /// key state of the latest poll, rather than a key that timed out between two instructions.
+    ///
+    /// If the interpreter is waiting for a key press (`FX0A`), the first pressed key (the lowest, if several were
+    /// pressed since the previous poll) is stored in the waiting register, and the wait ends. If the input reports
+    /// releases, keys that were already held don't count. Otherwise, every reported key is a fresh press, so tapping a
+    /// key again counts even if its previous tap hasn't timed out yet.
+    ///
+    /// # Arguments
+    /// * `keys` - The keys reported by the poll, with bit `n` set if key `n` is held.
+    /// * `reports_releases` - Whether the input reports key releases.
+    /// * `now` - The time of the poll, from any fixed epoch, e.g. the emulated time since the program started.
+    pub fn poll_key(&mut self, keys: u16, reports_releases: bool, now: Duration) {
+        let newly_pressed = if reports_releases {
+            keys & !self.keys
+        } else {
+            keys
+        };
+
+        for key in 0..constants::KEY_COUNT {
+            if keys & (1 << key) != 0 {
+                self.keys |= 1 << key;
+                self.keys_pressed_at[key] = now;
+            } else if reports_releases
+                || now.saturating_sub(self.keys_pressed_at[key]) > self.key_timeout
+            {
+                self.keys &= !(1 << key);
             }
         }
+
+        if let Some(reg) = self.waiting_for_keypress
+            && newly_pressed != 0
+        {
+            self.v[reg] = newly_pressed.trailing_zeros() as u8;
+            self.waiting_for_keypress = None;
+        }
+    }
+
+    /// Load the built-in character sets into memory in the first 512 bytes.
+    /// Each small character is 5 bytes (5 rows of 8 pixels, only the upper 4 bits are used), and each big character is
+    /// 10 bytes (10 rows of 8 pixels).
+    pub fn bootstrap_character_rom(&mut self) {
+        let (small, big) = (self.font_offset(), self.big_font_offset());
+        self.memory[small..][..font::SMALL_FONT.len()].copy_from_slice(&font::SMALL_FONT);
+        self.memory[big..][..font::BIG_FONT.len()].copy_from_slice(&font::BIG_FONT);
+    }
+
+    /// Replace the small font used by `FX29`, e.g. with a custom typeface.
+    ///
+    /// # Arguments
+    /// * `font` - 16 glyphs of 5 bytes, for the digits 0 to F.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the font was replaced.
+    /// * `Err(Chip8Error::InvalidFont)` if the font has the wrong size.
+    pub fn set_font(&mut self, font: &[u8]) -> Result<(), Chip8Error> {
+        let offset = self.font_offset();
+        Self::load_font(&mut self.memory, offset, font, font::SMALL_FONT.len())
+    }
+
+    /// Replace the big font used by `FX30`.
+    ///
+    /// # Arguments
+    /// * `font` - 16 glyphs of 10 bytes, for the digits 0 to F.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the font was replaced.
+    /// * `Err(Chip8Error::InvalidFont)` if the font has the wrong size.
+    pub fn set_big_font(&mut self, font: &[u8]) -> Result<(), Chip8Error> {
+        let offset = self.big_font_offset();
+        Self::load_font(&mut self.memory, offset, font, font::BIG_FONT.len())
+    }
+
+    fn load_font(
+        memory: &mut [u8],
+        offset: usize,
+        font: &[u8],
+        expected: usize,
+    ) -> Result<(), Chip8Error> {
+        if font.len() != expected {
+            return Err(Chip8Error::InvalidFont {
+                expected,
+                actual: font.len(),
+            });
+        }
+        memory[offset..offset + expected].copy_from_slice(font);
+        Ok(())
     }
 }
 
-impl TryFrom<&PathBuf> for State {
-    type Error = std::io::Error;
+/// Read a ROM image from a file, failing with a clear error rather than loading nothing.
+///
+/// # Arguments
+/// * `path` - The path of the ROM file.
+///
+/// # Returns
+/// * `Ok(rom)` with the contents of the file.
+/// * `Err` if the file can't be read, the path is a directory, or the file is too small to hold an instruction
+///   (`ErrorKind::InvalidData`, wrapping `Chip8Error::RomTooSmall`).
+#[cfg(feature = "std")]
+pub fn read_rom(path: &Path) -> std::io::Result<Vec<u8>> {
+    if path.is_dir() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::IsADirectory,
+            format!("ROM path is a directory: {}", path.display()),
+        ));
+    }
 
-    fn try_from(rom_path: &PathBuf) -> Result<Self, std::io::Error> {
-        let mut state = State::new();
+    let rom = std::fs::read(path)?;
+    if rom.len() < 2 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            Chip8Error::RomTooSmall(rom.len()),
+        ));
+    }
+    Ok(rom)
+}
+
+/// Strip a 2-byte trailer from a ROM image.
+///
+/// Some homebrew ROMs end with a big-endian 16 bit trailer, holding either the length of the program, or its CRC-16
+/// (CCITT). A trailer that matches neither is still stripped, but a warning is logged.
+///
+/// # Arguments
+/// * `rom` - The ROM image, including the trailer.
+///
+/// # Returns
+/// A tuple containing the program bytes without the trailer, and whether the trailer was valid.
+pub fn strip_trailer(rom: &[u8]) -> (&[u8], bool) {
+    let Some(split) = rom.len().checked_sub(2) else {
+        warn!("ROM is too short to contain a trailer");
+        return (rom, false);
+    };
+
+    let (program, trailer) = rom.split_at(split);
+    let trailer = u16::from_be_bytes([trailer[0], trailer[1]]);
+
+    let valid = trailer as usize == program.len() || trailer == crc16(program);
+    if !valid {
+        warn!(
+            "ROM trailer {:04X} matches neither the length {:04X} nor the CRC {:04X}",
+            trailer,
+            program.len(),
+            crc16(program)
+        );
+    }
+
+    (program, valid)
+}
 
-        let mut f = File::open(rom_path)?;
-        let mut buffer: [u8; 4096] = [0; constants::MEMORY_SIZE];
-        let n = f.read(&mut buffer)?;
+/// Compute the CRC-16/CCITT-FALSE checksum of the given bytes.
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &b in bytes {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Renders the visible screen, like `screen::screen_to_string`.
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&screen::screen_to_string(self.visible_screen()))
+    }
+}
+
+/// Shows the registers, timers and stack in hex, leaving out the memory and screen.
+impl fmt::Debug for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("State")
+            .field("pc", &format_args!("{:03X}", self.pc))
+            .field("i", &format_args!("{:03X}", self.i))
+            .field("v", &format_args!("{:02X?}", self.v))
+            .field("delay_timer", &self.delay_timer)
+            .field("sound_timer", &self.sound_timer)
+            .field("stack", &format_args!("{:03X?}", self.stack))
+            .finish_non_exhaustive()
+    }
+}
 
-        // Load the ROM into memory starting at address 0x200
-        state.memory[0x200..n].copy_from_slice(&buffer[0x200..n]);
+impl TryFrom<&[u8]> for State {
+    type Error = Chip8Error;
 
+    fn try_from(rom: &[u8]) -> Result<Self, Chip8Error> {
+        let mut state = State::new();
+        state.load_rom(rom)?;
         Ok(state)
     }
 }
+
+#[cfg(feature = "std")]
+impl TryFrom<&PathBuf> for State {
+    type Error = std::io::Error;
+
+    fn try_from(rom_path: &PathBuf) -> Result<Self, std::io::Error> {
+        State::try_from(read_rom(rom_path)?.as_slice())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
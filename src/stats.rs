@@ -0,0 +1,199 @@
+//! Statistics of a run, to get a quick profile of a ROM.
+
+use crate::instruction::{self, FORMS};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+
+/// Counters accumulated by the emulator while it runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RunStats {
+    /// Number of cycles, including those spent waiting for a key press or a vertical blank.
+    pub cycles: u64,
+
+    /// Number of instructions executed.
+    pub instructions: u64,
+
+    /// Number of sprites drawn (`DXYN`).
+    pub draws: u64,
+
+    /// Number of subroutine calls (`2NNN`).
+    pub calls: u64,
+
+    /// Deepest the call stack has been.
+    pub peak_stack_depth: usize,
+
+    /// Number of instructions executed per opcode class, indexed by the top nibble of the opcode.
+    pub opcode_counts: [u64; 16],
+}
+
+impl RunStats {
+    /// Count an executed instruction.
+    ///
+    /// # Arguments
+    /// * `opcode` - The opcode of the instruction.
+    /// * `stack_depth` - The depth of the call stack after the instruction executed.
+    pub fn count_instruction(&mut self, opcode: u16, stack_depth: usize) {
+        self.instructions += 1;
+        self.opcode_counts[(opcode >> 12) as usize] += 1;
+        match opcode & 0xF000 {
+            0x2000 => self.calls += 1,
+            0xD000 => self.draws += 1,
+            _ => {}
+        }
+        self.peak_stack_depth = self.peak_stack_depth.max(stack_depth);
+    }
+
+    /// Summarize the run, including the wall time and the effective speed.
+    ///
+    /// # Arguments
+    /// * `wall_time` - How long the run took.
+    pub fn summary(&self, wall_time: Duration) -> Summary<'_> {
+        Summary {
+            stats: self,
+            wall_time,
+        }
+    }
+}
+
+/// A printable summary of a run, see `RunStats::summary`.
+pub struct Summary<'a> {
+    stats: &'a RunStats,
+    wall_time: Duration,
+}
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.wall_time.as_secs_f64();
+        let ips = if seconds > 0.0 {
+            self.stats.instructions as f64 / seconds
+        } else {
+            0.0
+        };
+
+        writeln!(f, "Cycles:           {}", self.stats.cycles)?;
+        writeln!(f, "Instructions:     {}", self.stats.instructions)?;
+        writeln!(f, "Wall time:        {seconds:.3} s")?;
+        writeln!(f, "Instructions/s:   {ips:.0}")?;
+        writeln!(f, "Draws:            {}", self.stats.draws)?;
+        writeln!(f, "Calls:            {}", self.stats.calls)?;
+        write!(f, "Peak stack depth: {}", self.stats.peak_stack_depth)
+    }
+}
+
+/// How often each instruction form executed, to find the instructions a program never exercises.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    /// Executions per instruction form, see `Instruction::form`.
+    counts: BTreeMap<&'static str, u64>,
+
+    /// Executions of opcodes that aren't instructions.
+    unknown: u64,
+}
+
+impl Coverage {
+    /// Count an executed instruction.
+    ///
+    /// # Arguments
+    /// * `opcode` - The opcode of the instruction.
+    pub fn record(&mut self, opcode: u16) {
+        match instruction::decode(opcode).form() {
+            Some(form) => *self.counts.entry(form).or_default() += 1,
+            None => self.unknown += 1,
+        }
+    }
+
+    /// How often an instruction form executed.
+    ///
+    /// # Arguments
+    /// * `form` - The form, e.g. `8XY4`.
+    pub fn count(&self, form: &str) -> u64 {
+        self.counts.get(form).copied().unwrap_or_default()
+    }
+
+    /// The instruction forms that never executed, in opcode order.
+    pub fn missed(&self) -> Vec<&'static str> {
+        FORMS
+            .into_iter()
+            .filter(|form| !self.counts.contains_key(form))
+            .collect()
+    }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Instruction coverage: {} of {} forms",
+            self.counts.len(),
+            FORMS.len()
+        )?;
+        for (form, count) in &self.counts {
+            writeln!(f, "  {form}  {count}")?;
+        }
+        if self.unknown > 0 {
+            writeln!(f, "  ????  {}", self.unknown)?;
+        }
+        write!(f, "Never executed: {}", self.missed().join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_reports_speed() {
+        let stats = RunStats {
+            cycles: 1500,
+            instructions: 1400,
+            draws: 3,
+            calls: 2,
+            peak_stack_depth: 1,
+            opcode_counts: [0; 16],
+        };
+
+        let summary = stats.summary(Duration::from_secs(2)).to_string();
+
+        assert!(summary.contains("Instructions/s:   700\n"));
+        assert!(summary.ends_with("Peak stack depth: 1"));
+    }
+
+    #[test]
+    fn count_opcode_classes() {
+        let mut stats = RunStats::default();
+
+        stats.count_instruction(0xD125, 0);
+        stats.count_instruction(0xD000, 0);
+        stats.count_instruction(0x6A2A, 0);
+
+        assert_eq!(stats.opcode_counts[0xD], 2);
+        assert_eq!(stats.opcode_counts[0x6], 1);
+        assert_eq!(stats.opcode_counts.iter().sum::<u64>(), stats.instructions);
+    }
+
+    #[test]
+    fn coverage_counts_forms() {
+        let mut coverage = Coverage::default();
+
+        coverage.record(0x8124);
+        coverage.record(0x8AB4);
+        coverage.record(0x00E0);
+        coverage.record(0x8008);
+
+        assert_eq!(coverage.count("8XY4"), 2);
+        assert_eq!(coverage.count("00E0"), 1);
+        assert_eq!(coverage.count("DXYN"), 0);
+        assert_eq!(coverage.missed().len(), FORMS.len() - 2);
+        assert!(!coverage.missed().contains(&"8XY4"));
+
+        let report = coverage.to_string();
+        assert!(
+            report.starts_with(
+                "Instruction coverage: 2 of 42 forms\n  00E0  1\n  8XY4  2\n  ????  1\n"
+            )
+        );
+        assert!(report.contains("Never executed: 0000 00EE 0NNN"));
+    }
+}
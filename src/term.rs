@@ -1,60 +1,795 @@
-use crate::constants::{HEIGHT, WIDTH};
-use clap::Parser;
+use crate::constants::WIDTH;
+use crate::decoder;
+use crate::disassembler;
+use crate::display::Display;
+use crate::input::Input;
+use crate::keymap::KeyMap;
+use crate::state::State;
 use crossterm::event::{
     KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
 };
 use crossterm::{
-    ExecutableCommand,
-    cursor::{Hide, Show},
-    event::{self, Event, KeyCode, KeyModifiers, poll},
-    execute,
-    style::{Color, SetBackgroundColor, SetForegroundColor},
-    terminal::{self, Clear, EnterAlternateScreen, LeaveAlternateScreen, SetSize, size},
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, poll},
+    execute, queue,
+    style::{Color, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, Clear, EnterAlternateScreen, LeaveAlternateScreen, size},
 };
+use log::{error, warn};
 use std::io::{Write, stdout};
-use std::{path::PathBuf, time::Duration};
+use std::str::FromStr;
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Whether the terminal was set up by a `TerminalGuard`, and hasn't been restored yet.
+static TERMINAL_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Installs the panic hook of `TerminalGuard` once.
+static PANIC_HOOK: Once = Once::new();
 
 /// Set up the terminal for the application.
 ///
+/// If the terminal supports the keyboard enhancement protocol, it is asked to report key release events.
+///
 /// # Return
-/// * `Ok(())` if the terminal was successfully set up.
+/// * `Ok(true)` if the terminal was successfully set up, and reports key releases.
+/// * `Ok(false)` if the terminal was successfully set up, but doesn't report key releases.
 /// * `Err` if there was an error during the setup process.
-pub fn setup_terminal() -> Result<(), Box<dyn std::error::Error>> {
+pub fn setup_terminal() -> Result<bool, Box<dyn std::error::Error>> {
     terminal::enable_raw_mode()?;
     let mut stdout = stdout();
     execute!(stdout, EnterAlternateScreen)?;
     execute!(stdout, Hide)?;
-    execute!(stdout, SetSize(WIDTH as u16, (HEIGHT + 2) as u16))?;
 
-    Ok(())
+    let reports_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if reports_releases {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+
+    Ok(reports_releases)
 }
 
-pub fn set_styles() -> Result<(), Box<dyn std::error::Error>> {
-    // execute!(stdout(),SetBackgroundColor(Color::Yellow))?;
-    // execute!(stdout(),SetForegroundColor(Color::Red))?;
-    // execute!(stdout(),Clear(terminal::ClearType::All))?;
+/// Color presets for the terminal renderer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    /// White on black.
+    Classic,
+
+    /// Amber phosphor.
+    Amber,
+
+    /// Green phosphor.
+    #[default]
+    Green,
+
+    /// Blue phosphor.
+    Blue,
+}
+
+impl Theme {
+    /// The colors of the theme.
+    ///
+    /// # Return
+    /// A tuple containing the foreground (pixel on) and background (pixel off) colors.
+    pub fn colors(&self) -> (Color, Color) {
+        match self {
+            Theme::Classic => (Color::White, Color::Black),
+            Theme::Amber => (
+                Color::Rgb {
+                    r: 0xFF,
+                    g: 0xB0,
+                    b: 0x00,
+                },
+                Color::Black,
+            ),
+            Theme::Green => (
+                Color::Rgb {
+                    r: 0x33,
+                    g: 0xFF,
+                    b: 0x33,
+                },
+                Color::Black,
+            ),
+            Theme::Blue => (
+                Color::Rgb {
+                    r: 0x7D,
+                    g: 0xD3,
+                    b: 0xFF,
+                },
+                Color::Black,
+            ),
+        }
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "classic" => Ok(Theme::Classic),
+            "amber" => Ok(Theme::Amber),
+            "green" => Ok(Theme::Green),
+            "blue" => Ok(Theme::Blue),
+            _ => Err(format!(
+                "Unknown theme '{s}', expected 'classic', 'amber', 'green' or 'blue'"
+            )),
+        }
+    }
+}
+
+/// Parse a color: a name like `white` or `dark_yellow` (see crossterm's `Color`), or a hex RGB value like `#FFB000`.
+///
+/// # Arguments
+/// * `s` - The color to parse.
+///
+/// # Return
+/// * `Ok(Color)` if the color is valid.
+/// * `Err` with a message otherwise.
+pub fn parse_color(s: &str) -> Result<Color, String> {
+    if let Some(hex) = s.strip_prefix('#')
+        && hex.len() == 6
+        && let Ok(rgb) = u32::from_str_radix(hex, 16)
+    {
+        let [_, r, g, b] = rgb.to_be_bytes();
+        return Ok(Color::Rgb { r, g, b });
+    }
+
+    Color::try_from(s)
+        .map_err(|_| format!("Unknown color '{s}', expected a name like 'white' or '#RRGGBB'"))
+}
+
+/// Apply the colors of the renderer, and clear the terminal with the background color.
+///
+/// # Arguments
+/// * `foreground` - The color of pixels that are on.
+/// * `background` - The color of pixels that are off.
+///
+/// # Return
+/// * `Ok(())` if the colors were applied.
+/// * `Err` if there was an error while applying the colors.
+pub fn set_styles(foreground: Color, background: Color) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stdout = stdout();
+    execute!(stdout, SetForegroundColor(foreground))?;
+    execute!(stdout, SetBackgroundColor(background))?;
+    execute!(stdout, Clear(terminal::ClearType::All))?;
     Ok(())
 }
 
-/// Restore the terminal to its original state.
+/// Colors of XO-CHIP pixels that are only on in the second plane, and that are on in both planes, after Octo.
+pub const PLANE_COLORS: [Color; 2] = [
+    Color::Rgb {
+        r: 0xFF,
+        g: 0x66,
+        b: 0x00,
+    },
+    Color::Rgb {
+        r: 0x66,
+        g: 0x22,
+        b: 0x00,
+    },
+];
+
+/// Size of the image in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scale {
+    /// The largest scale that fits the terminal.
+    Auto,
+
+    /// Each pixel is N times as wide and high as at scale 1, scaled down if the terminal is too small.
+    Fixed(u16),
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Scale::Fixed(1)
+    }
+}
+
+impl FromStr for Scale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Scale::Auto);
+        }
+
+        match s.parse() {
+            Ok(scale) if scale > 0 => Ok(Scale::Fixed(scale)),
+            _ => Err(format!(
+                "Invalid scale '{s}', expected 'auto' or a positive integer"
+            )),
+        }
+    }
+}
+
+/// How pixels are drawn with characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    /// Two rows of pixels per line, with the half blocks `▀` and `▄`, for the most compact image.
+    #[default]
+    Half,
+
+    /// Two full blocks `██` per pixel, for square pixels in most fonts.
+    Full,
+
+    /// A `#` per pixel, for terminals without Unicode.
+    Ascii,
+}
+
+impl RenderMode {
+    /// Characters per pixel horizontally, at scale 1.
+    fn columns_per_pixel(&self) -> usize {
+        match self {
+            RenderMode::Full => 2,
+            RenderMode::Half | RenderMode::Ascii => 1,
+        }
+    }
+
+    /// Rows of pixels per line, at scale 1.
+    fn pixels_per_line(&self) -> usize {
+        match self {
+            RenderMode::Half => 2,
+            RenderMode::Full | RenderMode::Ascii => 1,
+        }
+    }
+}
+
+impl FromStr for RenderMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "half" => Ok(RenderMode::Half),
+            "full" => Ok(RenderMode::Full),
+            "ascii" => Ok(RenderMode::Ascii),
+            _ => Err(format!(
+                "Unknown render mode '{s}', expected 'half', 'full' or 'ascii'"
+            )),
+        }
+    }
+}
+
+/// Number of lines below the image, for the status line and the held keys.
+pub const STATUS_LINES: usize = 2;
+
+/// Number of lines of the debugger panel below the status lines, see `TerminalDisplay::with_debug_panel`.
+pub const DEBUG_PANEL_LINES: usize = 6;
+
+/// Placement of the image in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    /// Size of a pixel, relative to scale 1.
+    pub scale: u16,
+
+    /// Column of the left edge of the image.
+    pub left: u16,
+
+    /// Row of the top edge of the image.
+    pub top: u16,
+
+    /// Row of the status line, below the image.
+    pub status: u16,
+
+    /// Whether the image and the status lines fit in the terminal, even at scale 1.
+    pub fits: bool,
+
+    /// Whether there is room for a panel below the status lines.
+    pub panel: bool,
+}
+
+impl Layout {
+    /// Scale the image as requested, or as large as fits, and center it with the status lines in the terminal.
+    ///
+    /// # Arguments
+    /// * `terminal` - The width and height of the terminal, in characters.
+    /// * `screen` - The width and height of the screen, in pixels.
+    /// * `scale` - The requested scale.
+    /// * `mode` - How the pixels are drawn, which determines their size in characters.
+    pub fn new(
+        terminal: (u16, u16),
+        screen: (usize, usize),
+        scale: Scale,
+        mode: RenderMode,
+    ) -> Self {
+        Self::place(terminal, screen, scale, mode, STATUS_LINES)
+    }
+
+    /// Like `new`, with room for a panel of `panel_lines` below the status lines. If the image doesn't fit with the
+    /// panel, it is placed without.
+    ///
+    /// # Arguments
+    /// * `terminal` - The width and height of the terminal, in characters.
+    /// * `screen` - The width and height of the screen, in pixels.
+    /// * `scale` - The requested scale.
+    /// * `mode` - How the pixels are drawn, which determines their size in characters.
+    /// * `panel_lines` - The height of the panel.
+    pub fn with_panel(
+        terminal: (u16, u16),
+        screen: (usize, usize),
+        scale: Scale,
+        mode: RenderMode,
+        panel_lines: usize,
+    ) -> Self {
+        let layout = Self::place(terminal, screen, scale, mode, STATUS_LINES + panel_lines);
+        if layout.fits && panel_lines > 0 {
+            Self {
+                panel: true,
+                ..layout
+            }
+        } else {
+            Self::new(terminal, screen, scale, mode)
+        }
+    }
+
+    /// Place the image with `text_lines` below it.
+    fn place(
+        terminal: (u16, u16),
+        screen: (usize, usize),
+        scale: Scale,
+        mode: RenderMode,
+        text_lines: usize,
+    ) -> Self {
+        let (columns, rows) = (terminal.0 as usize, terminal.1 as usize);
+        let (width, height) = (screen.0 * mode.columns_per_pixel(), screen.1);
+        let lines = |scale: usize| (height * scale).div_ceil(mode.pixels_per_line());
+
+        let largest = (columns / width)
+            .min(rows.saturating_sub(text_lines) * mode.pixels_per_line() / height);
+        let scale = match scale {
+            Scale::Auto => largest,
+            Scale::Fixed(scale) => largest.min(scale as usize),
+        }
+        .max(1);
+
+        let left = columns.saturating_sub(width * scale) / 2;
+        let top = rows.saturating_sub(lines(scale) + text_lines) / 2;
+        Self {
+            scale: scale as u16,
+            left: left as u16,
+            top: top as u16,
+            status: (top + lines(scale)) as u16,
+            fits: largest > 0,
+            panel: false,
+        }
+    }
+}
+
+/// The lines of the debugger panel: the registers, the timers, the stack, the next instruction, and the debugging keys.
+/// A stack too deep for a line shows its top entries, after a `…`.
+///
+/// # Arguments
+/// * `state` - The state to show.
+///
+/// # Returns
+/// `DEBUG_PANEL_LINES` lines, each at most `WIDTH` characters.
+pub fn debug_panel(state: &State) -> Vec<String> {
+    let registers = |range: std::ops::Range<usize>| -> String {
+        state.v[range]
+            .iter()
+            .map(|v| format!("{v:02X}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    // The top entries that fit, leaving room for the `…` of the others
+    let room = WIDTH - "Stack  … ".chars().count();
+    let mut stack: Vec<String> = Vec::new();
+    for pc in state.stack.iter().rev() {
+        let entry = format!("{pc:03X}");
+        if stack.iter().map(|entry| entry.len() + 1).sum::<usize>() + entry.len() > room {
+            break;
+        }
+        stack.push(entry);
+    }
+    stack.reverse();
+    let hidden = if stack.len() < state.stack.len() {
+        "… "
+    } else {
+        ""
+    };
+    let opcode = decoder::fetch(state);
+
+    vec![
+        format!(
+            "PC {:03X}  I {:03X}  DT {:02X}  ST {:02X}",
+            state.pc, state.i, state.delay_timer, state.sound_timer
+        ),
+        format!("V0-V7  {}", registers(0..8)),
+        format!("V8-VF  {}", registers(8..16)),
+        format!("Stack  {hidden}{}", stack.join(" ")),
+        format!(
+            "Next   {:03X}: {:04X}  {}",
+            state.pc,
+            opcode,
+            disassembler::mnemonic(opcode)
+        ),
+        "Space pause/continue  N step  B rewind".to_string(),
+    ]
+}
+
+/// The status lines of the HUD: the name of the ROM, the instructions per second, and whether the sound is on.
 ///
 /// # Arguments
-/// * `original_size` - A tuple containing the original width and height of the terminal.
+/// * `rom_name` - The name of the ROM.
+/// * `instructions_per_second` - The measured speed.
+/// * `beeping` - Whether the sound is on.
+///
+/// # Returns
+/// `STATUS_LINES` lines, each at most `WIDTH` characters.
+pub fn hud(rom_name: &str, instructions_per_second: u64, beeping: bool) -> Vec<String> {
+    let sound = if beeping { "on" } else { "off" };
+    vec![
+        rom_name.chars().take(WIDTH).collect(),
+        format!("{instructions_per_second} IPS  Sound {sound}"),
+    ]
+}
+
+/// The character for a half-block cell, from whether its upper and lower pixel are on.
+fn half_block(upper: bool, lower: bool) -> char {
+    match (upper, lower) {
+        (false, false) => ' ',
+        (true, false) => '▀',
+        (false, true) => '▄',
+        (true, true) => '█',
+    }
+}
+
+/// Renders the screen to the terminal, centered, with the pixels drawn as selected by a `RenderMode`.
+pub struct TerminalDisplay {
+    /// Color of pixels that are on, in the first plane only.
+    foreground: Color,
+
+    /// Color of pixels that are off.
+    background: Color,
+
+    /// The requested size of the image.
+    scale: Scale,
+
+    /// How pixels are drawn.
+    mode: RenderMode,
+
+    /// Placement of the last rendered image, to notice when the terminal is resized.
+    layout: Option<Layout>,
+
+    /// The pixels on the terminal, to only redraw the lines that changed.
+    drawn: Vec<u8>,
+
+    /// Height of the panel to make room for below the status lines, 0 for none.
+    panel_lines: usize,
+}
+
+impl TerminalDisplay {
+    /// Create a display.
+    ///
+    /// # Arguments
+    /// * `foreground` - The color of pixels that are on, as applied by `set_styles`.
+    /// * `background` - The color of pixels that are off, as applied by `set_styles`.
+    /// * `scale` - The size of the image.
+    /// * `mode` - How pixels are drawn.
+    pub fn new(foreground: Color, background: Color, scale: Scale, mode: RenderMode) -> Self {
+        Self {
+            foreground,
+            background,
+            scale,
+            mode,
+            layout: None,
+            drawn: Vec::new(),
+            panel_lines: 0,
+        }
+    }
+
+    /// Make room for the debugger panel below the status lines, if the terminal is large enough.
+    pub fn with_debug_panel(mut self) -> Self {
+        self.panel_lines = DEBUG_PANEL_LINES;
+        self
+    }
+
+    /// Where to write the status lines, below the last rendered image.
+    ///
+    /// # Return
+    /// * `Some((column, row))` of the first status line.
+    /// * `None` if nothing was rendered yet, or the terminal is too small to show the status.
+    pub fn status_position(&self) -> Option<(u16, u16)> {
+        self.layout
+            .filter(|layout| layout.fits)
+            .map(|layout| (layout.left, layout.status))
+    }
+
+    /// Where to write the lines of the debugger panel, below the status lines.
+    ///
+    /// # Return
+    /// * `Some((column, row))` of the first line of the panel.
+    /// * `None` if there is no panel, nothing was rendered yet, or the terminal is too small to show the panel.
+    pub fn panel_position(&self) -> Option<(u16, u16)> {
+        self.layout
+            .filter(|layout| layout.fits && layout.panel)
+            .map(|layout| (layout.left, layout.status + STATUS_LINES as u16))
+    }
+
+    /// The color of a pixel, from the planes it is on in.
+    fn color(&self, pixel: u8) -> Color {
+        match pixel {
+            0 => self.background,
+            1 => self.foreground,
+            planes => PLANE_COLORS[planes as usize - 2],
+        }
+    }
+
+    /// Draw the pixels in the color of their planes. Lines that are unchanged since the last call are skipped.
+    fn draw(&mut self, pixels: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut stdout = stdout();
+        let height = pixels.len() / WIDTH;
+        let layout = Layout::with_panel(
+            size()?,
+            (WIDTH, height),
+            self.scale,
+            self.mode,
+            self.panel_lines,
+        );
+
+        if self.layout != Some(layout) {
+            // The terminal was resized, or the resolution changed, so erase the image at its old place
+            execute!(stdout, Clear(terminal::ClearType::All))?;
+            self.drawn.clear();
+            if !layout.fits {
+                warn!(
+                    "Terminal too small for the display, it needs {}x{} characters",
+                    WIDTH * self.mode.columns_per_pixel(),
+                    height.div_ceil(self.mode.pixels_per_line()) + STATUS_LINES
+                );
+            } else if self.panel_lines > 0 && !layout.panel {
+                warn!("Terminal too small for the debugger panel");
+            }
+            self.layout = Some(layout);
+        }
+
+        if !layout.fits {
+            execute!(stdout, MoveTo(0, 0))?;
+            write!(stdout, "Terminal too small, please resize")?;
+            return Ok(());
+        }
+
+        // Each line shows `pixels_per_line` rows of pixels, each repeated `scale` times
+        let scale = layout.scale as usize;
+        let per_line = self.mode.pixels_per_line();
+        let width = scale * self.mode.columns_per_pixel();
+        let pixel_row = |unit: usize| (unit / scale).min(height - 1);
+        let lines = (height * scale).div_ceil(per_line);
+
+        let mut colors = None;
+        for line in 0..lines {
+            let upper = pixel_row(line * per_line);
+            let lower = pixel_row(line * per_line + per_line - 1);
+            let rows = upper * WIDTH..(lower + 1) * WIDTH;
+            if self.drawn.get(rows.clone()) == Some(&pixels[rows]) {
+                continue;
+            }
+            queue!(stdout, MoveTo(layout.left, layout.top + line as u16))?;
+
+            for column in 0..WIDTH {
+                let (upper, lower) = (
+                    pixels[upper * WIDTH + column],
+                    pixels[lower * WIDTH + column],
+                );
+                let (symbol, foreground, background) = match self.mode {
+                    RenderMode::Half if upper == lower || lower == 0 => {
+                        (half_block(upper != 0, lower != 0), upper, 0)
+                    }
+                    RenderMode::Half if upper == 0 => (half_block(false, true), lower, 0),
+                    // Both pixels are on, in different colors
+                    RenderMode::Half => ('▀', upper, lower),
+                    RenderMode::Full => (if upper != 0 { '█' } else { ' ' }, upper, 0),
+                    RenderMode::Ascii => (if upper != 0 { '#' } else { ' ' }, upper, 0),
+                };
+
+                let cell_colors = (self.color(foreground.max(1)), self.color(background));
+                if colors != Some(cell_colors) {
+                    queue!(
+                        stdout,
+                        SetForegroundColor(cell_colors.0),
+                        SetBackgroundColor(cell_colors.1)
+                    )?;
+                    colors = Some(cell_colors);
+                }
+                for _ in 0..width {
+                    write!(stdout, "{symbol}")?;
+                }
+            }
+        }
+        queue!(
+            stdout,
+            SetForegroundColor(self.foreground),
+            SetBackgroundColor(self.background)
+        )?;
+        stdout.flush()?;
+        self.drawn = pixels.to_vec();
+
+        Ok(())
+    }
+}
+
+impl Display for TerminalDisplay {
+    fn render(&mut self, screen: &[bool]) -> Result<(), Box<dyn std::error::Error>> {
+        let pixels: Vec<u8> = screen.iter().map(|&pixel| pixel as u8).collect();
+        self.draw(&pixels)
+    }
+
+    fn render_planes(&mut self, pixels: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        self.draw(pixels)
+    }
+}
+
+/// Reads the keypad from the terminal, mapping keys with a `KeyMap`.
+///
+/// Exit commands (see `should_exit`) are not keypad input, so they are recorded in `exit_requested` instead. Likewise,
+/// Space (unless the key map uses it) toggles pause, recorded in `pause_requested`, N (unless the key map uses it)
+/// single-steps while paused, recorded in `step_requested`, B (unless the key map uses it) rewinds, recorded in
+/// `rewind_requested`, H (unless the key map uses it) shows or hides the HUD, recorded in `hud_requested`, and holding
+/// Tab (unless the key map uses it) runs in turbo, recorded in `turbo_held`.
+///
+/// On terminals that report key releases, a key is held from its press event until its release event. Other terminals
+/// only report presses (and auto-repeats), so each poll reports the keys pressed since the previous poll, and the
+/// interpreter holds them until they time out.
+#[derive(Default)]
+pub struct TerminalInput {
+    /// Whether the user asked to exit.
+    pub exit_requested: bool,
+
+    /// Whether the user asked to pause or resume, since this was last reset.
+    pub pause_requested: bool,
+
+    /// Whether the user asked to execute a single instruction, since this was last reset.
+    pub step_requested: bool,
+
+    /// Whether the user asked to go back in time, since this was last reset.
+    pub rewind_requested: bool,
+
+    /// Whether the user asked to show or hide the HUD, since this was last reset.
+    pub hud_requested: bool,
+
+    /// Whether Tab is held, to run faster. Only on terminals that report key releases, as a held key can't be told
+    /// apart from repeated presses on others.
+    pub turbo_held: bool,
+
+    /// Bitmask of the held keys.
+    keys: u16,
+
+    /// Whether the terminal reports key releases.
+    reports_releases: bool,
+
+    /// Mapping of terminal keys to the keypad.
+    keymap: KeyMap,
+}
+
+impl TerminalInput {
+    /// Create a terminal input.
+    ///
+    /// # Arguments
+    /// * `reports_releases` - Whether the terminal reports key releases, as returned by `setup_terminal`.
+    /// * `keymap` - The mapping of terminal keys to the keypad.
+    pub fn new(reports_releases: bool, keymap: KeyMap) -> Self {
+        Self {
+            reports_releases,
+            keymap,
+            ..Self::default()
+        }
+    }
+}
+
+impl Input for TerminalInput {
+    fn poll(&mut self, _cycle: u64) -> Result<u16, Box<dyn std::error::Error>> {
+        if !self.reports_releases {
+            self.keys = 0;
+        }
+
+        while poll(Duration::from_millis(0))? {
+            let event = event::read()?;
+
+            if should_exit(&event)? {
+                self.exit_requested = true;
+            }
+
+            if let Event::Key(KeyEvent { code, kind, .. }) = event {
+                if let Some(key) = self.keymap.get(code) {
+                    if kind == KeyEventKind::Release {
+                        self.keys &= !(1 << key);
+                    } else {
+                        self.keys |= 1 << key;
+                    }
+                } else if code == KeyCode::Tab && self.reports_releases {
+                    self.turbo_held = kind != KeyEventKind::Release;
+                } else if kind == KeyEventKind::Press {
+                    match code {
+                        KeyCode::Char(' ') => self.pause_requested = true,
+                        KeyCode::Char('n') => self.step_requested = true,
+                        KeyCode::Char('b') => self.rewind_requested = true,
+                        KeyCode::Char('h') => self.hud_requested = true,
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        Ok(self.keys)
+    }
+
+    fn reports_releases(&self) -> bool {
+        self.reports_releases
+    }
+}
+
+/// Restore the terminal to its original state.
 ///
 /// # Return
 /// * `Ok(())` if the terminal was successfully restored.
 /// * `Err` if there was an error during the restoration process.
-pub fn cleanup_terminal(original_size: (u16, u16)) -> Result<(), Box<dyn std::error::Error>> {
+pub fn cleanup_terminal() -> Result<(), Box<dyn std::error::Error>> {
     let mut stdout = stdout();
+    execute!(stdout, ResetColor)?;
     execute!(stdout, Show)?;
     execute!(stdout, LeaveAlternateScreen)?;
-    execute!(stdout, SetSize(original_size.0, original_size.1))?;
     execute!(stdout, PopKeyboardEnhancementFlags)?;
     terminal::disable_raw_mode()?;
 
     Ok(())
 }
 
+/// Sets up the terminal with `setup_terminal`, and restores it with `cleanup_terminal` when dropped, so an error
+/// returned early doesn't leave the shell in raw mode on the alternate screen.
+///
+/// A panic restores the terminal too, from a panic hook before the message is printed, as release builds abort rather
+/// than unwinding.
+pub struct TerminalGuard {
+    reports_releases: bool,
+}
+
+impl TerminalGuard {
+    /// Set up the terminal, see `setup_terminal`.
+    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        PANIC_HOOK.call_once(|| {
+            let previous = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                if TERMINAL_ACTIVE.swap(false, Ordering::SeqCst) {
+                    cleanup_terminal().ok();
+                }
+                previous(info);
+            }));
+        });
+
+        let reports_releases = setup_terminal()?;
+        TERMINAL_ACTIVE.store(true, Ordering::SeqCst);
+        Ok(Self { reports_releases })
+    }
+
+    /// Whether the terminal reports key releases.
+    pub fn reports_releases(&self) -> bool {
+        self.reports_releases
+    }
+
+    /// Restore the terminal now, reporting any error, rather than when dropped.
+    pub fn restore(self) -> Result<(), Box<dyn std::error::Error>> {
+        // Dropping self afterwards finds the terminal restored
+        if TERMINAL_ACTIVE.swap(false, Ordering::SeqCst) {
+            cleanup_terminal()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if TERMINAL_ACTIVE.swap(false, Ordering::SeqCst)
+            && let Err(e) = cleanup_terminal()
+        {
+            error!("Failed to restore the terminal: {e}");
+        }
+    }
+}
+
 /// Check if the event is an exit command (Esc key or Ctrl+C).
 ///
 /// # Arguments
@@ -75,3 +810,204 @@ pub fn should_exit(event: &Event) -> Result<bool, Box<dyn std::error::Error>> {
         Ok(false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::HEIGHT;
+
+    #[test]
+    fn parse_colors() {
+        assert_eq!(parse_color("dark_yellow"), Ok(Color::DarkYellow));
+        assert_eq!(
+            parse_color("#FFB000"),
+            Ok(Color::Rgb {
+                r: 0xFF,
+                g: 0xB0,
+                b: 0x00
+            })
+        );
+        assert!(parse_color("#FFB0").is_err());
+        assert!(parse_color("chartreuse").is_err());
+    }
+
+    #[test]
+    fn parse_render_modes() {
+        assert_eq!("half".parse(), Ok(RenderMode::Half));
+        assert_eq!("FULL".parse(), Ok(RenderMode::Full));
+        assert_eq!("ascii".parse(), Ok(RenderMode::Ascii));
+        assert!("quarter".parse::<RenderMode>().is_err());
+    }
+
+    #[test]
+    fn parse_scales() {
+        assert_eq!("auto".parse(), Ok(Scale::Auto));
+        assert_eq!("3".parse(), Ok(Scale::Fixed(3)));
+        assert!("0".parse::<Scale>().is_err());
+        assert!("big".parse::<Scale>().is_err());
+    }
+
+    #[test]
+    fn center_scaled_image() {
+        // 200x80 fits 2x, as 3x would be 96 rows high, and the image is centered with the status lines below it
+        let layout = Layout::new((200, 80), (WIDTH, HEIGHT), Scale::Auto, RenderMode::Ascii);
+        assert_eq!(
+            layout,
+            Layout {
+                scale: 2,
+                left: 36,
+                top: 7,
+                status: 71,
+                fits: true,
+                panel: false
+            }
+        );
+
+        // A fixed scale that doesn't fit is clamped
+        assert_eq!(
+            Layout::new(
+                (200, 80),
+                (WIDTH, HEIGHT),
+                Scale::Fixed(4),
+                RenderMode::Ascii
+            )
+            .scale,
+            2
+        );
+        assert_eq!(
+            Layout::new(
+                (200, 80),
+                (WIDTH, HEIGHT),
+                Scale::Fixed(1),
+                RenderMode::Ascii
+            ),
+            Layout {
+                scale: 1,
+                left: 68,
+                top: 23,
+                status: 55,
+                fits: true,
+                panel: false
+            }
+        );
+    }
+
+    #[test]
+    fn render_mode_sizes() {
+        // 2 rows of pixels per line: 80x20 fits 1x with the status lines, and 200x80 fits 3x
+        assert_eq!(
+            Layout::new((80, 20), (WIDTH, HEIGHT), Scale::Auto, RenderMode::Half),
+            Layout {
+                scale: 1,
+                left: 8,
+                top: 1,
+                status: 17,
+                fits: true,
+                panel: false
+            }
+        );
+        assert_eq!(
+            Layout::new((200, 80), (WIDTH, HEIGHT), Scale::Auto, RenderMode::Half).scale,
+            3
+        );
+
+        // 2 characters per pixel: 128 columns wide
+        let layout = Layout::new((130, 40), (WIDTH, HEIGHT), Scale::Auto, RenderMode::Full);
+        assert_eq!((layout.scale, layout.left, layout.fits), (1, 1, true));
+        assert!(!Layout::new((120, 40), (WIDTH, HEIGHT), Scale::Auto, RenderMode::Full).fits);
+    }
+
+    #[test]
+    fn half_blocks() {
+        assert_eq!(half_block(false, false), ' ');
+        assert_eq!(half_block(true, false), '▀');
+        assert_eq!(half_block(false, true), '▄');
+        assert_eq!(half_block(true, true), '█');
+    }
+
+    #[test]
+    fn terminal_too_small() {
+        let layout = Layout::new((40, 20), (WIDTH, HEIGHT), Scale::Auto, RenderMode::Half);
+
+        assert_eq!(layout.scale, 1);
+        assert!(!layout.fits);
+    }
+
+    #[test]
+    fn debug_panel_falls_back_without_room() {
+        let layout = |rows| {
+            Layout::with_panel(
+                (80, rows),
+                (WIDTH, HEIGHT),
+                Scale::Auto,
+                RenderMode::Half,
+                DEBUG_PANEL_LINES,
+            )
+        };
+
+        // 16 lines of image, 2 status lines and the panel
+        assert_eq!((layout(24).fits, layout(24).panel), (true, true));
+        assert_eq!(layout(24).status, 16);
+        assert_eq!((layout(20).fits, layout(20).panel), (true, false));
+        assert_eq!(
+            layout(20),
+            Layout::new((80, 20), (WIDTH, HEIGHT), Scale::Auto, RenderMode::Half)
+        );
+    }
+
+    #[test]
+    fn hud_lines() {
+        assert_eq!(
+            hud("pong.ch8", 700, true),
+            ["pong.ch8", "700 IPS  Sound on"]
+        );
+        assert_eq!(hud(&"x".repeat(100), 0, false)[0].len(), WIDTH);
+    }
+
+    #[test]
+    fn debug_panel_lines() {
+        let mut state = State::with_seed(0);
+        state
+            .load_rom(&[0x60, 0x2A, 0x12, 0x00])
+            .expect("Failed to load ROM");
+        state.v[0xF] = 1;
+        state.i = 0x300;
+        state.stack.push_back(0x20A);
+
+        let panel = debug_panel(&state);
+
+        assert_eq!(panel.len(), DEBUG_PANEL_LINES);
+        assert_eq!(panel[0], "PC 200  I 300  DT 00  ST 00");
+        assert_eq!(panel[2], "V8-VF  00 00 00 00 00 00 00 01");
+        assert_eq!(panel[3], "Stack  20A");
+        assert_eq!(panel[4], "Next   200: 602A  LD V0, 0x2A");
+        assert!(panel.iter().all(|line| line.chars().count() <= WIDTH));
+
+        // A full stack, or a deeper one without a limit, shows the top entries that fit
+        for depth in [16, 100] {
+            state.stack = (0..depth).map(|n| 0x200 + 2 * n).collect();
+            let stack = &debug_panel(&state)[3];
+            assert!(stack.starts_with("Stack  … "), "{stack}");
+            assert!(stack.ends_with(&format!("{:03X}", 0x200 + 2 * (depth - 1))));
+            assert!(stack.chars().count() <= WIDTH);
+        }
+    }
+
+    #[test]
+    fn green_theme_colors() {
+        let theme: Theme = "green".parse().expect("Failed to parse theme");
+
+        assert_eq!(theme, Theme::Green);
+        assert_eq!(
+            theme.colors(),
+            (
+                Color::Rgb {
+                    r: 0x33,
+                    g: 0xFF,
+                    b: 0x33
+                },
+                Color::Black
+            )
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Execution traces written to a file, for offline analysis.
+//!
+//! Each executed instruction becomes one line, `ADDR: OPCODE  MNEMONIC  [V0..VF]`, e.g.
+//!
+//! ```text
+//! 0200: 602A  LD V0, 0x2A     [00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00]
+//! ```
+//!
+//! The registers are those before the instruction executes.
+//!
+//! A lighter alternative is the log: at the trace level, the emulator logs each instruction with the program counter
+//! before and after, and the registers it changed (see `format_transition`), e.g. with `RUST_LOG=trace`.
+
+use crate::disassembler;
+#[cfg(feature = "std")]
+use crate::emulator::TraceHook;
+use crate::state::State;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use log::*;
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::{BufWriter, Write};
+#[cfg(feature = "std")]
+use std::path::Path;
+
+/// Number of lines between explicit flushes of the trace file.
+#[cfg(feature = "std")]
+const FLUSH_INTERVAL: u64 = 4096;
+
+/// Format a single trace line, without the trailing newline.
+///
+/// # Arguments
+/// * `pc` - The address of the instruction.
+/// * `opcode` - The opcode of the instruction.
+/// * `state` - The state before the instruction executes.
+pub fn format_trace_line(pc: usize, opcode: u16, state: &State) -> String {
+    let registers: Vec<String> = state.v.iter().map(|v| format!("{v:02X}")).collect();
+    format!(
+        "{:04X}: {:04X}  {:<16} [{}]",
+        pc,
+        opcode,
+        disassembler::mnemonic(opcode),
+        registers.join(" ")
+    )
+}
+
+/// Create a trace hook that writes every executed instruction to a file.
+///
+/// The file is buffered and flushed periodically, and when the hook is dropped.
+///
+/// # Arguments
+/// * `path` - The path of the trace file, which is created or truncated.
+/// * `max_lines` - Stop tracing after this many instructions, if set.
+///
+/// # Returns
+/// * `Ok(TraceHook)` to install with `Emulator::set_trace_hook`.
+/// * `Err` if the file couldn't be created.
+#[cfg(feature = "std")]
+pub fn file_trace_hook(path: &Path, max_lines: Option<u64>) -> std::io::Result<TraceHook> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut lines: u64 = 0;
+    let mut failed = false;
+
+    Ok(Box::new(move |pc, opcode, state| {
+        if failed || max_lines.is_some_and(|max| lines >= max) {
+            return;
+        }
+
+        let mut result = writeln!(writer, "{}", format_trace_line(pc, opcode, state));
+        lines += 1;
+        if result.is_ok() && (lines.is_multiple_of(FLUSH_INTERVAL) || max_lines == Some(lines)) {
+            result = writer.flush();
+        }
+
+        if let Err(e) = result {
+            warn!("Failed to write trace, tracing stopped: {e}");
+            failed = true;
+        }
+    }))
+}
+
+/// Format an executed instruction for the log: the program counter before and after, and the registers it changed.
+///
+/// # Arguments
+/// * `pc` - The address of the instruction.
+/// * `opcode` - The opcode of the instruction.
+/// * `v_before` - Registers V0 to VF before the instruction executed.
+/// * `i_before` - The address register before the instruction executed.
+/// * `state` - The state after the instruction executed.
+pub fn format_transition(
+    pc: usize,
+    opcode: u16,
+    v_before: &[u8; 16],
+    i_before: usize,
+    state: &State,
+) -> String {
+    let mut line = format!(
+        "{:03X}: {:04X}  {:<16} PC {:03X} -> {:03X}",
+        pc,
+        opcode,
+        disassembler::mnemonic(opcode),
+        pc,
+        state.pc
+    );
+    for (register, (before, after)) in v_before.iter().zip(state.v).enumerate() {
+        if *before != after {
+            line += &format!("  V{register:X}={after:02X}");
+        }
+    }
+    if i_before != state.i {
+        line += &format!("  I={:03X}", state.i);
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_line_format() {
+        let mut state = State::new();
+        state.v[0xF] = 0x01;
+
+        assert_eq!(
+            format_trace_line(0x200, 0x602A, &state),
+            "0200: 602A  LD V0, 0x2A      [00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 01]"
+        );
+    }
+
+    #[test]
+    fn transition_format() {
+        let mut state = State::new();
+        let v_before = state.v;
+        state.v[0x3] = 0x2A;
+        state.v[0xF] = 0x01;
+        state.i = 0x300;
+        state.pc = 0x202;
+
+        assert_eq!(
+            format_transition(0x200, 0x8344, &v_before, 0, &state),
+            "200: 8344  ADD V3, V4       PC 200 -> 202  V3=2A  VF=01  I=300"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Static checks of a ROM, for instructions this interpreter won't run as the program expects.
+//!
+//! The ROM is scanned two bytes at a time from the start, without following the control flow, so sprite data or an
+//! odd-aligned instruction between the code can produce false positives (and hide real instructions).
+
+use crate::disassembler::InstructionIter;
+use crate::instruction::Instruction;
+use crate::quirks::Quirks;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A problem found in a ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Address of the instruction, as loaded at 0x200.
+    pub address: usize,
+
+    /// The opcode.
+    pub opcode: u16,
+
+    /// What is wrong with it.
+    pub message: &'static str,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:03X}: {:04X}  {}",
+            self.address, self.opcode, self.message
+        )
+    }
+}
+
+/// Scan a ROM for instructions that are unknown, can't be supported, or need other quirks.
+///
+/// # Arguments
+/// * `rom` - The ROM image, as loaded at 0x200.
+/// * `quirks` - The quirks the ROM will run with.
+///
+/// # Returns
+/// The diagnostics, in address order.
+pub fn validate_rom(rom: &[u8], quirks: &Quirks) -> Vec<Diagnostic> {
+    let hires = rom.starts_with(&[0x12, 0x60]);
+
+    InstructionIter::new(rom, 0)
+        .filter_map(|(offset, instruction)| {
+            let message = match instruction {
+                // Clears the screen in the hires mode of the COSMAC VIP
+                Instruction::Sys(0x230) if hires => return None,
+                Instruction::Sys(nnn) => describe_unsupported(nnn),
+                Instruction::Plane(_) if !quirks.xo_chip_planes => {
+                    "XO-CHIP plane selection, which needs the xochip quirks"
+                }
+                Instruction::LoadLongI if !quirks.xo_chip_long_addressing => {
+                    "XO-CHIP long addressing, which needs the xochip quirks"
+                }
+                Instruction::Unknown(opcode) => describe_unsupported(opcode),
+                _ => return None,
+            };
+            Some(Diagnostic {
+                address: 0x200 + offset,
+                opcode: instruction.encode(),
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Explain an unknown opcode, which may be an instruction of a later interpreter, or a call to machine code.
+fn describe_unsupported(opcode: u16) -> &'static str {
+    match opcode {
+        0x0000..=0x0FFF if opcode & 0xFF00 != 0 => {
+            "Machine code subroutine, which can't be supported"
+        }
+        0x00FB..=0x00FF => "SUPER-CHIP instruction, which isn't supported",
+        _ if opcode & 0xFFF0 == 0x00C0 => "SUPER-CHIP instruction, which isn't supported",
+        0xF002 => "XO-CHIP instruction, which isn't supported",
+        _ if opcode & 0xFFF0 == 0x00D0 => "XO-CHIP instruction, which isn't supported",
+        _ if opcode & 0xF00E == 0x5002 || opcode & 0xF0FF == 0xF03A => {
+            "XO-CHIP instruction, which isn't supported"
+        }
+        0x0000..=0x0FFF => "Machine code subroutine, which can't be supported",
+        _ => "Unknown opcode",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_unsupported_instructions() {
+        let rom = [
+            0x60, 0x2A, // LD V0, 0x2A
+            0x03, 0x00, // SYS 0x300
+            0x00, 0xFF, // HIGH (SUPER-CHIP)
+            0xF1, 0x01, // PLANE 1
+            0x80, 0x08, // DW 0x8008
+            0x12, 0x00, // JP 0x200
+        ];
+
+        let diagnostics: Vec<_> = validate_rom(&rom, &Quirks::chip8())
+            .iter()
+            .map(|diagnostic| (diagnostic.address, diagnostic.opcode))
+            .collect();
+
+        assert_eq!(
+            diagnostics,
+            [
+                (0x202, 0x0300),
+                (0x204, 0x00FF),
+                (0x206, 0xF101),
+                (0x208, 0x8008)
+            ]
+        );
+        assert_eq!(validate_rom(&rom, &Quirks::xochip()).len(), 3);
+        assert_eq!(
+            validate_rom(&rom, &Quirks::chip8())[1].message,
+            "SUPER-CHIP instruction, which isn't supported"
+        );
+    }
+
+    #[test]
+    fn hires_clear_is_supported() {
+        let rom = [
+            0x12, 0x60, // JP 0x260
+            0x02, 0x30, // SYS 0x230, clears the screen in hires mode
+        ];
+
+        assert_eq!(validate_rom(&rom, &Quirks::chip8()), []);
+        assert_eq!(
+            validate_rom(&rom[2..], &Quirks::chip8())[0].to_string(),
+            "200: 0230  Machine code subroutine, which can't be supported"
+        );
+    }
+}
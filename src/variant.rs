@@ -0,0 +1,255 @@
+//! Detection of the interpreter a ROM was written for.
+//!
+//! ROMs don't say which interpreter they target, but SUPER-CHIP and XO-CHIP programs tend to give themselves away by
+//! using instructions the older interpreters don't have. `detect_variant` looks for those, following the control flow
+//! from the entry point, so sprite data that happens to look like an instruction isn't mistaken for one.
+//!
+//! Some ROMs need quirks that don't follow from their variant, so `known_rom` recognizes ROMs by their hash, and gives
+//! the quirks they are known to run best with.
+
+use crate::hash;
+use crate::quirks::Quirks;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use core::fmt;
+use core::str::FromStr;
+
+/// A family of CHIP-8 interpreters, with its own quirks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Variant {
+    /// The original COSMAC VIP interpreter.
+    #[default]
+    Chip8,
+
+    /// SUPER-CHIP.
+    Schip,
+
+    /// XO-CHIP.
+    XoChip,
+}
+
+impl Variant {
+    /// The quirks preset of the variant.
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Variant::Chip8 => Quirks::chip8(),
+            Variant::Schip => Quirks::schip(),
+            Variant::XoChip => Quirks::xochip(),
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Variant::Chip8 => write!(f, "chip8"),
+            Variant::Schip => write!(f, "schip"),
+            Variant::XoChip => write!(f, "xochip"),
+        }
+    }
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "chip8" => Ok(Variant::Chip8),
+            "schip" => Ok(Variant::Schip),
+            "xochip" => Ok(Variant::XoChip),
+            _ => Err(format!(
+                "Unknown variant '{s}', expected 'chip8', 'schip' or 'xochip'"
+            )),
+        }
+    }
+}
+
+/// A ROM that is recognized by its hash, with the quirks it runs best with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownRom {
+    /// The `rom_hash` of the ROM image.
+    pub hash: u64,
+
+    /// Name of the program, for the log.
+    pub name: &'static str,
+
+    /// The quirks to run it with.
+    pub quirks: Quirks,
+}
+
+/// ROMs with known quirks, see `known_rom`.
+///
+/// To add a ROM, get its hash from `chip8 info`, and start from the preset of its variant, overriding the quirks it
+/// needs, e.g. `Quirks { display_wait: false, ..Quirks::chip8() }` for a CHIP-8 game that draws several sprites per
+/// frame. Only add ROMs whose quirks were checked by running them.
+const KNOWN_ROMS: &[KnownRom] = &[
+    // The self-checking test ROMs in `roms/test`, written for the COSMAC VIP
+    KnownRom {
+        hash: 0xB61D_B9B2_4760_2F3E,
+        name: "opcodes test ROM",
+        quirks: Quirks::chip8(),
+    },
+    KnownRom {
+        hash: 0xB608_502C_7755_F1D4,
+        name: "flags test ROM",
+        quirks: Quirks::chip8(),
+    },
+    // Written for SUPER-CHIP, but only with CHIP-8 instructions, so `detect_variant` takes it for CHIP-8
+    KnownRom {
+        hash: 0xBD3B_E0E4_1BC3_6637,
+        name: "SUPER-CHIP quirks test ROM",
+        quirks: Quirks::schip(),
+    },
+];
+
+/// Hash of a ROM image, to recognize it with `known_rom`: the 64 bit FNV-1a of its bytes.
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    hash::fnv1a(rom)
+}
+
+/// Look up a ROM in the table of ROMs with known quirks.
+///
+/// # Arguments
+/// * `rom` - The ROM image.
+///
+/// # Returns
+/// The entry of the ROM, or `None` if it isn't known, to fall back to `detect_variant`.
+pub fn known_rom(rom: &[u8]) -> Option<&'static KnownRom> {
+    let hash = rom_hash(rom);
+    KNOWN_ROMS.iter().find(|known| known.hash == hash)
+}
+
+/// Guess the variant a ROM was written for.
+///
+/// The instructions reachable from 0x200 are scanned for telltale opcodes: plane selection (`FN01`), long loads
+/// (`F000 NNNN`), register ranges (`5XY2`/`5XY3`), audio (`F002`, `FX3A`) or scrolling up (`00DN`) mean XO-CHIP, and
+/// the hires mode (`00FE`/`00FF`), scrolling (`00CN`/`00FB`/`00FC`), exit (`00FD`), big font (`FX30`) or user flags
+/// (`FX75`/`FX85`) mean SUPER-CHIP. Anything else is assumed to be plain CHIP-8. Computed jumps (`BNNN`) aren't
+/// followed, so instructions only reachable through them are missed.
+///
+/// # Arguments
+/// * `rom` - The ROM image, as loaded at 0x200.
+pub fn detect_variant(rom: &[u8]) -> Variant {
+    let mut variant = Variant::Chip8;
+    let mut visited = vec![false; rom.len()];
+    let mut pending = vec![0usize];
+
+    while let Some(offset) = pending.pop() {
+        if offset + 1 >= rom.len() || visited[offset] {
+            continue;
+        }
+        visited[offset] = true;
+
+        let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+        let next = offset + 2;
+        // Offset of an address in the ROM, if it is inside it
+        let target = |nnn: u16| (nnn as usize).checked_sub(0x200);
+
+        match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00EE | 0x00FD => {}
+                0x00FB | 0x00FC | 0x00FE | 0x00FF => {
+                    variant = variant.max(Variant::Schip);
+                    pending.push(next);
+                }
+                _ if opcode & 0xFFF0 == 0x00C0 => {
+                    variant = variant.max(Variant::Schip);
+                    pending.push(next);
+                }
+                _ if opcode & 0xFFF0 == 0x00D0 => {
+                    variant = Variant::XoChip;
+                    pending.push(next);
+                }
+                _ => pending.push(next),
+            },
+            0x1000 => pending.extend(target(opcode & 0x0FFF)),
+            0x2000 => {
+                pending.extend(target(opcode & 0x0FFF));
+                pending.push(next);
+            }
+            0x3000 | 0x4000 | 0x9000 | 0xE000 => {
+                pending.push(next);
+                pending.push(next + 2);
+            }
+            0x5000 => {
+                if matches!(opcode & 0x000F, 0x2 | 0x3) {
+                    variant = Variant::XoChip;
+                    pending.push(next);
+                } else {
+                    pending.push(next);
+                    pending.push(next + 2);
+                }
+            }
+            0xB000 => {}
+            0xF000 => match opcode & 0x00FF {
+                0x00 if opcode == 0xF000 => {
+                    variant = Variant::XoChip;
+                    pending.push(next + 2);
+                }
+                0x01 | 0x3A => {
+                    variant = Variant::XoChip;
+                    pending.push(next);
+                }
+                0x02 if opcode == 0xF002 => {
+                    variant = Variant::XoChip;
+                    pending.push(next);
+                }
+                0x30 | 0x75 | 0x85 => {
+                    variant = variant.max(Variant::Schip);
+                    pending.push(next);
+                }
+                0xFF => {}
+                _ => pending.push(next),
+            },
+            _ => pending.push(next),
+        }
+    }
+
+    variant
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_variants() {
+        // CLS, JP 0x202
+        assert_eq!(detect_variant(&[0x00, 0xE0, 0x12, 0x02]), Variant::Chip8);
+        // HIGH, JP 0x202
+        assert_eq!(detect_variant(&[0x00, 0xFF, 0x12, 0x02]), Variant::Schip);
+        // CALL 0x206, JP 0x202, ..., PLANE 3, RET
+        assert_eq!(
+            detect_variant(&[0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0xF3, 0x01, 0x00, 0xEE]),
+            Variant::XoChip
+        );
+    }
+
+    #[test]
+    fn ignore_unreachable_data() {
+        // JP 0x200, followed by sprite data that looks like HIGH
+        assert_eq!(detect_variant(&[0x12, 0x00, 0x00, 0xFF]), Variant::Chip8);
+    }
+
+    #[test]
+    fn recognize_known_roms() {
+        let flags = include_bytes!("../roms/test/flags.ch8");
+
+        assert_eq!(
+            known_rom(flags).map(|known| known.name),
+            Some("flags test ROM")
+        );
+        assert_eq!(known_rom(&flags[2..]), None);
+
+        // Known quirks that differ from those of the detected variant
+        let schip_quirks = include_bytes!("../roms/test/schip-quirks.ch8");
+        assert_eq!(detect_variant(schip_quirks), Variant::Chip8);
+        assert_eq!(
+            known_rom(schip_quirks).map(|known| known.quirks),
+            Some(Quirks::schip())
+        );
+        // FNV-1a of no bytes is its offset basis
+        assert_eq!(rom_hash(&[]), 0xCBF2_9CE4_8422_2325);
+    }
+}
@@ -0,0 +1,210 @@
+//! Pixel-exact tests of `DXYN`: the framebuffer and VF after drawing overlapping sprites, with collisions in any row,
+//! sprites that are clipped at the edges of the screen, or wrap around with the `clip_sprites` quirk disabled, sprite
+//! data that wraps around the end of memory, and the 10-row glyphs of the large font.
+
+use chip8_rs::constants::{HEIGHT, WIDTH};
+use chip8_rs::decoder::decode_and_execute;
+use chip8_rs::font::BIG_GLYPH_SIZE;
+use chip8_rs::state::State;
+
+/// Where the sprite data is placed.
+const SPRITE: usize = 0x300;
+
+/// Draw a sprite with `DRW V0, V1, N`.
+///
+/// # Arguments
+/// * `state` - The state to draw in.
+/// * `x` - The column, in V0.
+/// * `y` - The row, in V1.
+/// * `sprite` - The sprite data, one byte per row.
+///
+/// # Returns
+/// VF after drawing.
+fn draw(state: &mut State, x: u8, y: u8, sprite: &[u8]) -> u8 {
+    state.memory[SPRITE..SPRITE + sprite.len()].copy_from_slice(sprite);
+    state.i = SPRITE;
+    state.v[0] = x;
+    state.v[1] = y;
+
+    let pc = state.pc;
+    state.memory[pc] = 0xD0;
+    state.memory[pc + 1] = 0x10 | sprite.len() as u8;
+    decode_and_execute(state).expect("Failed to execute instruction");
+
+    state.v[0xF]
+}
+
+/// The coordinates of the pixels that are on, row by row.
+fn lit(state: &State) -> Vec<(usize, usize)> {
+    state
+        .visible_screen()
+        .iter()
+        .enumerate()
+        .filter(|&(_, &on)| on)
+        .map(|(index, _)| (index % WIDTH, index / WIDTH))
+        .collect()
+}
+
+#[test]
+fn draw_on_blank_screen() {
+    let mut state = State::new();
+
+    assert_eq!(draw(&mut state, 10, 5, &[0b1001_0000, 0b0110_0000]), 0);
+    assert_eq!(lit(&state), [(10, 5), (13, 5), (11, 6), (12, 6)]);
+}
+
+#[test]
+fn partial_overlap_sets_vf() {
+    let mut state = State::new();
+    draw(&mut state, 0, 0, &[0b1111_0000]);
+
+    // Pixels 2 and 3 are on in both, so they are erased
+    assert_eq!(draw(&mut state, 2, 0, &[0b1111_0000]), 1);
+    assert_eq!(lit(&state), [(0, 0), (1, 0), (4, 0), (5, 0)]);
+}
+
+#[test]
+fn adjacent_sprites_dont_collide() {
+    let mut state = State::new();
+    draw(&mut state, 0, 0, &[0b1111_0000, 0b1111_0000]);
+
+    // Overlaps the sprite's bounding box, but no pixel that is on
+    assert_eq!(draw(&mut state, 0, 0, &[0b0000_1111, 0b0000_1111]), 0);
+    assert_eq!(lit(&state).len(), 16);
+
+    // Below the sprite
+    assert_eq!(draw(&mut state, 0, 2, &[0xFF]), 0);
+    assert_eq!(lit(&state).len(), 24);
+}
+
+#[test]
+fn drawing_twice_erases() {
+    let mut state = State::new();
+    let sprite = [0xF0, 0x90, 0x90, 0x90, 0xF0];
+
+    assert_eq!(draw(&mut state, 20, 10, &sprite), 0);
+    assert_eq!(lit(&state).len(), 14);
+
+    assert_eq!(draw(&mut state, 20, 10, &sprite), 1);
+    assert_eq!(lit(&state), []);
+}
+
+#[test]
+fn collision_in_a_later_row_sets_vf() {
+    let mut state = State::new();
+    draw(&mut state, 0, 2, &[0b0010_0000]);
+
+    // Only the third row overlaps, and VF stays set for the rows after it
+    state.v[0xF] = 0;
+    assert_eq!(draw(&mut state, 0, 0, &[0x80, 0x40, 0x20, 0x10, 0x08]), 1);
+    assert_eq!(lit(&state), [(0, 0), (1, 1), (3, 3), (4, 4)]);
+}
+
+#[test]
+fn vf_is_cleared_without_collision() {
+    let mut state = State::new();
+    state.v[0xF] = 1;
+
+    assert_eq!(draw(&mut state, 0, 0, &[0x80, 0x40, 0x20]), 0);
+}
+
+#[test]
+fn sprite_data_wraps_past_end_of_memory() {
+    let mut state = State::new();
+    let end = state.memory.len();
+    state.memory[end - 1] = 0x80;
+    state.memory[0] = 0xF0; // The top row of the 0 glyph
+    state.i = end - 1;
+    state.memory[0x200..0x202].copy_from_slice(&[0xD0, 0x12]); // DRW V0, V1, 2
+
+    decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+    assert_eq!(lit(&state), [(0, 0), (0, 1), (1, 1), (2, 1), (3, 1)]);
+}
+
+#[test]
+fn clip_at_right_edge() {
+    let mut state = State::new();
+
+    // Only the two leftmost columns of the sprite are on the screen, and nothing wraps to the left edge
+    assert_eq!(draw(&mut state, 62, 0, &[0xFF, 0x81]), 0);
+    assert_eq!(lit(&state), [(62, 0), (63, 0), (62, 1)]);
+
+    // A collision in the visible part still counts, while the clipped part is ignored
+    assert_eq!(draw(&mut state, 62, 0, &[0x00, 0xFF]), 1);
+    assert_eq!(lit(&state), [(62, 0), (63, 0), (63, 1)]);
+}
+
+#[test]
+fn clip_at_bottom_edge() {
+    let mut state = State::new();
+    let bottom = HEIGHT as u8 - 2;
+
+    assert_eq!(draw(&mut state, 0, bottom, &[0x80, 0x80, 0x80, 0x80]), 0);
+    assert_eq!(
+        lit(&state),
+        [(0, HEIGHT - 2), (0, HEIGHT - 1)],
+        "Rows below the screen must not wrap to the top"
+    );
+}
+
+#[test]
+fn start_position_wraps() {
+    let mut state = State::new();
+
+    assert_eq!(
+        draw(&mut state, WIDTH as u8 + 3, HEIGHT as u8 + 1, &[0x80]),
+        0
+    );
+    assert_eq!(lit(&state), [(3, 1)]);
+}
+
+#[test]
+fn clip_at_corner() {
+    let mut state = State::new();
+    state.screen[0] = true;
+
+    assert_eq!(draw(&mut state, 63, 31, &[0xC0, 0xC0]), 0);
+    assert_eq!(lit(&state), [(0, 0), (63, 31)]);
+}
+
+#[test]
+fn wrap_at_corner() {
+    let mut state = State::new();
+    state.quirks.clip_sprites = false;
+    state.screen[0] = true;
+
+    // The pixel at the top left is erased by the part of the sprite that wraps there
+    assert_eq!(draw(&mut state, 63, 31, &[0xC0, 0xC0]), 1);
+    assert_eq!(lit(&state), [(63, 0), (0, 31), (63, 31)]);
+}
+
+#[test]
+fn draw_large_font_digit() {
+    let mut state = State::new();
+    state.v[0] = 4;
+    state.v[1] = 2;
+    state.v[2] = 0x8;
+
+    let program = [
+        0xF2, 0x30, // LD HF, V2
+        0xD0, 0x1A, // DRW V0, V1, 10
+    ];
+    state.memory[0x200..0x204].copy_from_slice(&program);
+    decode_and_execute(&mut state).expect("Failed to execute instruction");
+    decode_and_execute(&mut state).expect("Failed to execute instruction");
+
+    assert_eq!(state.v[0xF], 0);
+    let row = |y: usize| -> Vec<usize> {
+        (0..WIDTH)
+            .filter(|&x| state.visible_screen()[y * WIDTH + x])
+            .collect()
+    };
+    // 0xFF: the top of the glyph
+    assert_eq!(row(2), [4, 5, 6, 7, 8, 9, 10, 11]);
+    // 0xC3: the sides of the upper loop
+    assert_eq!(row(4), [4, 5, 10, 11]);
+    // 0xFF: the bottom, in the tenth row
+    assert_eq!(row(2 + BIG_GLYPH_SIZE - 1), [4, 5, 6, 7, 8, 9, 10, 11]);
+    assert_eq!(lit(&state).len(), 64);
+}
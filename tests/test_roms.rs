@@ -0,0 +1,107 @@
+//! Runs the test ROMs in `roms/test` headless, and compares the final screen to a golden frame in `tests/golden`.
+//!
+//! Each test ROM draws the number of every test that passes, so a missing number in a failure points at the broken
+//! instruction (see the `.asm` source of the ROM). Golden frames are stored as text, with trailing spaces trimmed.
+//!
+//! The test ROMs are written for this repository, as extra tests. The community test suites are run by
+//! `tests/test_suites.rs`.
+
+use chip8_rs::assembler::assemble;
+use chip8_rs::emulator::Emulator;
+use chip8_rs::screen::screen_to_string;
+use chip8_rs::variant::known_rom;
+use chip8_rs::{run_rom_coverage, run_rom_headless};
+
+/// The test ROMs end by jumping to themselves, which halts a headless run well before this.
+const MAX_CYCLES: usize = 100_000;
+
+fn trim_lines(text: &str) -> String {
+    text.lines()
+        .map(|line| line.trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn assert_golden(rom: &[u8], golden: &str) {
+    let screen = run_rom_headless(rom, MAX_CYCLES).expect("Failed to run ROM");
+    let actual = screen_to_string(&screen);
+
+    assert_eq!(
+        trim_lines(&actual),
+        trim_lines(golden),
+        "Screen differs from the golden frame:\n{actual}"
+    );
+}
+
+#[test]
+fn opcodes_test_rom() {
+    assert_golden(
+        include_bytes!("../roms/test/opcodes.ch8"),
+        include_str!("golden/opcodes.txt"),
+    );
+}
+
+#[test]
+fn flags_test_rom() {
+    assert_golden(
+        include_bytes!("../roms/test/flags.ch8"),
+        include_str!("golden/flags.txt"),
+    );
+}
+
+#[test]
+fn schip_quirks_test_rom_with_known_quirks() {
+    let rom = include_bytes!("../roms/test/schip-quirks.ch8");
+    let known = known_rom(rom).expect("Not a known ROM");
+    let mut emulator = Emulator::builder().quirks(known.quirks).build();
+    emulator.state.load_rom(rom).expect("Failed to load ROM");
+    emulator.turbo = true;
+    emulator.halt_on_infinite_loop = true;
+    for _ in 0..MAX_CYCLES {
+        if emulator.step().expect("Failed to run ROM").is_some() {
+            break;
+        }
+    }
+
+    let actual = screen_to_string(emulator.state.visible_screen());
+    assert_eq!(
+        trim_lines(&actual),
+        trim_lines(include_str!("golden/schip-quirks.txt")),
+        "Screen differs from the golden frame:\n{actual}"
+    );
+
+    // With the quirks of the detected variant, every test fails
+    let screen = run_rom_headless(rom, MAX_CYCLES).expect("Failed to run ROM");
+    assert!(screen.iter().all(|&pixel| !pixel));
+}
+
+#[test]
+fn test_roms_assemble_from_source() {
+    for (source, rom) in [
+        (
+            include_str!("../roms/test/opcodes.asm"),
+            &include_bytes!("../roms/test/opcodes.ch8")[..],
+        ),
+        (
+            include_str!("../roms/test/flags.asm"),
+            &include_bytes!("../roms/test/flags.ch8")[..],
+        ),
+        (
+            include_str!("../roms/test/schip-quirks.asm"),
+            &include_bytes!("../roms/test/schip-quirks.ch8")[..],
+        ),
+    ] {
+        assert_eq!(assemble(source).expect("Failed to assemble"), rom);
+    }
+}
+
+#[test]
+fn opcodes_test_rom_coverage() {
+    let coverage = run_rom_coverage(include_bytes!("../roms/test/opcodes.ch8"), MAX_CYCLES)
+        .expect("Failed to run ROM");
+
+    for form in ["6XNN", "7XNN", "8XY4", "ANNN", "DXYN", "FX29"] {
+        assert!(coverage.count(form) > 0, "{form} was never executed");
+    }
+    assert!(coverage.missed().contains(&"0NNN"));
+}
@@ -0,0 +1,76 @@
+//! Runs the community test suites headless, and compares the final screen to a golden frame in `tests/golden/suites`.
+//!
+//! The suites aren't bundled, so this is opt-in: point `CHIP8_TEST_SUITES` at a directory with any of the ROMs in
+//! `SUITES`, under their original names, e.g. from Timendus' chip8-test-suite, BC_test and corax89's chip8-test-rom.
+//! Without it, or for a ROM that isn't there, the test passes without running anything.
+//!
+//! A ROM without a golden frame fails, printing its screen. Once the screen was checked to show every test passing,
+//! run again with `CHIP8_BLESS=1` to store it as the golden frame.
+
+use chip8_rs::run_rom_headless;
+use chip8_rs::screen::screen_to_string;
+use std::path::{Path, PathBuf};
+
+/// The test suites end by jumping to themselves, which halts a headless run well before this.
+const MAX_CYCLES: usize = 1_000_000;
+
+/// The ROMs of the suites: the file name, and the name of the golden frame.
+const SUITES: [(&str, &str); 4] = [
+    ("3-corax+.ch8", "timendus-corax"),
+    ("4-flags.ch8", "timendus-flags"),
+    ("BC_test.ch8", "bc-test"),
+    ("test_opcode.ch8", "corax89-opcode"),
+];
+
+fn trim_lines(text: &str) -> String {
+    text.lines()
+        .map(|line| line.trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn run_suite(directory: &Path, file_name: &str, golden_name: &str) {
+    let rom_path = directory.join(file_name);
+    let Ok(rom) = std::fs::read(&rom_path) else {
+        eprintln!("Skipping {}, it isn't there", rom_path.display());
+        return;
+    };
+
+    let screen = run_rom_headless(&rom, MAX_CYCLES).expect("Failed to run ROM");
+    let actual = trim_lines(&screen_to_string(&screen));
+
+    let golden_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden/suites")
+        .join(golden_name)
+        .with_extension("txt");
+    if std::env::var_os("CHIP8_BLESS").is_some() {
+        std::fs::create_dir_all(golden_path.parent().expect("No parent directory"))
+            .expect("Failed to create the golden frame directory");
+        std::fs::write(&golden_path, format!("{actual}\n")).expect("Failed to write golden frame");
+        return;
+    }
+
+    let Ok(golden) = std::fs::read_to_string(&golden_path) else {
+        panic!(
+            "No golden frame {} for {file_name}, check its screen and store it with CHIP8_BLESS=1:\n{actual}",
+            golden_path.display()
+        );
+    };
+    assert_eq!(
+        actual,
+        trim_lines(&golden),
+        "{file_name}: screen differs from the golden frame:\n{actual}"
+    );
+}
+
+#[test]
+fn community_test_suites() {
+    let Some(directory) = std::env::var_os("CHIP8_TEST_SUITES") else {
+        eprintln!("Skipping the community test suites, set CHIP8_TEST_SUITES to run them");
+        return;
+    };
+
+    for (file_name, golden_name) in SUITES {
+        run_suite(Path::new(&directory), file_name, golden_name);
+    }
+}